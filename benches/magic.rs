@@ -1,7 +1,13 @@
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkGroup, Criterion};
+use criterion::measurement::WallTime;
 use tejuino::util::PRNG;
 use tejuino::types::*;
 use tejuino::magic::*;
+use tejuino::magic::constants::*;
+#[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+use tejuino::pext::Bmi2;
+use tejuino::pext::{PextBitboard, Software};
+use tejuino::fill::KoggeStone;
 
 use rand::Rng;
 
@@ -11,8 +17,7 @@ pub fn benchmarks(c: &mut Criterion) {
     single_square_group.bench_function("random square random seed", |b| { b.iter(|| {
         // choose a random square
         let mut rng = rand::thread_rng();
-        let square = Square::from(rng.gen_range(0..64) as usize);
-        assert_ne!(square, Square::InvalidSquare);
+        let square = Square::from_index(rng.gen_range(0..64));
         // generate RNG based on rank
         // let seed = RNG_SEEDS[square.rank() as usize];
         // let mut rng = PRNG::new(seed);
@@ -23,8 +28,7 @@ pub fn benchmarks(c: &mut Criterion) {
     single_square_group.bench_function("random square RNG_SEEDS", |b| { b.iter(|| {
         // choose a random square
         let mut rng = rand::thread_rng();
-        let square = Square::from(rng.gen_range(0..64) as usize);
-        assert_ne!(square, Square::InvalidSquare);
+        let square = Square::from_index(rng.gen_range(0..64));
         // generate RNG based on rank
         let seed = RNG_SEEDS[square.rank() as usize];
         let mut rng = PRNG::new(seed);
@@ -39,6 +43,28 @@ pub fn benchmarks(c: &mut Criterion) {
         generate_magics(&MagicPiece::Rook);
     }));
     all_square_group.finish();
+
+
+    // each backend through its own type, as movegen sees them, not through a trait object
+    let mut lookup_group = c.benchmark_group("rook lookup");
+    let occupancies: Vec<Bitboard> = (0..1024).map(|i| Bitboard(PRNG::new(i + 1).sparse())).collect();
+    let magics = load_magics(&MagicPiece::Rook, &ROOK_MAGICS, ROOK_TABLE_SIZE);
+    bench_lookup(&mut lookup_group, "magic", &magics, &occupancies);
+    #[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+    bench_lookup(&mut lookup_group, "pext bmi2", &PextBitboard::<Bmi2>::new(&MagicPiece::Rook), &occupancies);
+    bench_lookup(&mut lookup_group, "pext software", &PextBitboard::<Software>::new(&MagicPiece::Rook), &occupancies);
+    bench_lookup(&mut lookup_group, "kogge-stone", &KoggeStone(MagicPiece::Rook), &occupancies);
+    lookup_group.finish();
+}
+
+fn bench_lookup<S: SliderAttacks>(group: &mut BenchmarkGroup<WallTime>, name: &str, backend: &S, occupancies: &[Bitboard]) {
+    group.bench_function(name, |b| b.iter(|| {
+        let mut all = Bitboard(0);
+        for (i, occupied) in occupancies.iter().enumerate() {
+            all |= backend.attacks(Square::from_index(i % 64), black_box(*occupied));
+        }
+        all
+    }));
 }
 
 criterion_group!(benches, benchmarks);
@@ -0,0 +1,34 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use tejuino::movegen::legal_moves;
+use tejuino::nnue::*;
+use tejuino::types::*;
+use tejuino::util::PRNG;
+
+pub fn benchmarks(c: &mut Criterion) {
+    let mut rng = PRNG::new(0x891750FBADEA5);
+    let mut net = Network::new(1024, Activation::SCReLU);
+    net.feature_weights.iter_mut().for_each(|w| *w = (rng.next() % 41) as i16 - 20);
+    net.output_weights.iter_mut().for_each(|w| *w = (rng.next() % 129) as i16 - 64);
+    let board = Board::from("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+    let moves = legal_moves(&board);
+
+    let mut group = c.benchmark_group("nnue");
+    for kernel in Kernel::ALL.into_iter().filter(|k| k.available()) {
+        let net = net.clone().with_kernel(kernel);
+        let mut stack = AccumulatorStack::new(&net, &board);
+        // an update for every move of the position, and an evaluation after each one
+        group.bench_function(format!("{:?}", kernel), |b| b.iter(|| {
+            let mut sum = 0;
+            for m in &moves {
+                stack.push(&net, &board, *m);
+                sum += net.evaluate(stack.current(), Color::Black);
+                stack.pop();
+            }
+            black_box(sum)
+        }));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, benchmarks);
+criterion_main!(benches);
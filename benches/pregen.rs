@@ -1,11 +1,51 @@
+use std::sync::LazyLock;
+
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use tejuino::pregen::*;
+use tejuino::magic::*;
+use tejuino::magic::constants::*;
+use tejuino::types::*;
+use tejuino::util::PRNG;
+
+// the knight table behind a lazy static, which is how every table was read before they were
+// built at compile time
+static LAZY_KNIGHT: LazyLock<[Bitboard; 64]> = LazyLock::new(|| ATTACKS.knight);
 
 pub fn benchmarks(c: &mut Criterion) {
     c.bench_function("pregen", |b| b.iter(|| {
         let p = Pregen::init();
         black_box(p)
     }));
+
+    // what startup used to cost, before the magics were precomputed
+    c.bench_function("rook magics search", |b| b.iter(|| {
+        black_box(generate_magics(&MagicPiece::Rook))
+    }));
+    c.bench_function("rook magics load", |b| b.iter(|| {
+        black_box(load_magics(&MagicPiece::Rook, &ROOK_MAGICS, ROOK_TABLE_SIZE))
+    }));
+
+    let squares: Vec<Square> = (0..1024).map(|i| Square::from_index(i * 37 % 64)).collect();
+    let mut leaper_group = c.benchmark_group("knight lookup");
+    leaper_group.bench_function("static", |b| b.iter(|| {
+        squares.iter().fold(Bitboard(0), |all, sq| all | ATTACKS.knight[*black_box(sq)])
+    }));
+    leaper_group.bench_function("lazy", |b| b.iter(|| {
+        squares.iter().fold(Bitboard(0), |all, sq| all | LAZY_KNIGHT[*black_box(sq)])
+    }));
+    leaper_group.finish();
+
+    tejuino::init();
+    let occupancies: Vec<Bitboard> = (0..1024).map(|i| Bitboard(PRNG::new(i + 1).sparse())).collect();
+    let loaded = Pregen::init();
+    let mut slider_group = c.benchmark_group("slider lookup");
+    slider_group.bench_function("once lock", |b| b.iter(|| {
+        squares.iter().zip(&occupancies).fold(Bitboard(0), |all, (sq, occ)| all | tejuino::pregen().rook.attacks(*sq, *occ))
+    }));
+    slider_group.bench_function("direct", |b| b.iter(|| {
+        squares.iter().zip(&occupancies).fold(Bitboard(0), |all, (sq, occ)| all | loaded.rook.attacks(*sq, *occ))
+    }));
+    slider_group.finish();
 }
 
 criterion_group!(benches, benchmarks);
@@ -0,0 +1,28 @@
+//! Picks the sliding attack backend from the crate features and the target, as the `sliders`
+//! cfg, so that [`pregen::Sliders`](src/pregen.rs) is a single concrete type.
+
+use std::env;
+
+fn main() {
+    println!("cargo::rustc-check-cfg=cfg(sliders, values(\"pext\", \"kogge-stone\", \"black-magic\", \"magic\"))");
+    let feature = |name: &str| env::var_os(format!("CARGO_FEATURE_{}", name)).is_some();
+    let bmi2 = env::var("CARGO_CFG_TARGET_ARCH").is_ok_and(|arch| arch == "x86_64")
+        && env::var("CARGO_CFG_TARGET_FEATURE").is_ok_and(|features| features.split(',').any(|f| f == "bmi2"));
+
+    if feature("PEXT") && !bmi2 {
+        println!(
+            "cargo::warning=the pext feature needs a target with BMI2 (e.g. RUSTFLAGS=\"-C target-cpu=native\"), \
+             falling back to the next sliding attack backend"
+        );
+    }
+    let sliders = if feature("PEXT") && bmi2 {
+        "pext"
+    } else if feature("KOGGE_STONE") {
+        "kogge-stone"
+    } else if feature("BLACK_MAGIC") {
+        "black-magic"
+    } else {
+        "magic"
+    };
+    println!("cargo::rustc-cfg=sliders=\"{}\"", sliders);
+}
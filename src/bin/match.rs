@@ -0,0 +1,296 @@
+//! # Match runner
+//!
+//! Play UCI engines against each other, cutechess-cli style:
+//!
+//! ```text
+//! match -engine cmd=./new name=new -engine cmd=./old name=old -each tc=10+0.1 \
+//!     -openings file=book.epd order=random -games 2 -rounds 500 -concurrency 4 \
+//!     -resign movecount=3 score=600 -draw movenumber=40 movecount=8 score=10 -pgnout games.pgn
+//! ```
+//!
+//! Every game is reported as it finishes, and appended to the `-pgnout` file if one is given.
+//! Between two engines, the score so far follows, with the Elo difference (see
+//! [`tejuino::stats`]) and, with `-sprt`, the state of the test, the match stopping as soon as
+//! it's over.
+
+use std::env;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::time::Duration;
+
+use tejuino::datagen::GameResult;
+use tejuino::stats::SprtConfig;
+use tejuino::tournament::{
+    load_openings, pentanomial, run_match, standings, Adjudication, DrawAdjudication, EngineConfig, MatchOptions,
+    OpeningFormat, ResignAdjudication, TimeControl,
+};
+
+const USAGE: &str = "usage: match -engine <engine options> -engine <engine options> [options]
+
+engine options, for -engine and -each:
+    cmd=COMMAND         the engine executable (required)
+    name=NAME           name in the results and PGN, the executable's name by default
+    dir=DIR             working directory
+    arg=ARG             command line argument, can be repeated
+    option.NAME=VALUE   UCI option, can be repeated
+    tc=[MOVES/]TIME[+INC]   time control, in seconds, or inf
+    st=SECONDS          fixed time per move
+    nodes=N             nodes per move
+    depth=N             depth per move
+
+options:
+    -each <engine options>              options for every engine
+    -openings file=FILE [format=epd|pgn] [order=sequential|random] [plies=N]
+    -variant standard|fischerandom
+    -rounds N                           times every engine meets every other one (1)
+    -games N                            games per encounter, colors alternating (2)
+    -concurrency N                      games played at the same time (1)
+    -srand N                            seed for the random opening order
+    -resign movecount=N score=CP        adjudicate a loss
+    -draw movenumber=N movecount=N score=CP   adjudicate a draw
+    -maxmoves N                         adjudicate a draw after N moves
+    -timemargin MS                      time an engine can overrun its clock by (50)
+    -movetimeout MS                     time for a move without a clock, e.g. with nodes=N (60000)
+    -event NAME                         the Event tag of the games
+    -sprt elo0=E elo1=E alpha=A beta=B  stop once an SPRT of elo0 against elo1 is over (0, 5, 0.05, 0.05)
+    -pgnout FILE                        write the games to FILE";
+
+/// Split `key=value` arguments.
+fn pairs(values: &[String]) -> Result<Vec<(&str, &str)>, String> {
+    values
+        .iter()
+        .map(|v| v.split_once('=').ok_or_else(|| format!("Expected key=value: {}", v)))
+        .collect()
+}
+
+fn parse<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Invalid value for {}: {}", name, value))
+}
+
+/// The single value of an option like `-rounds N`.
+fn single<'a>(name: &str, values: &'a [String]) -> Result<&'a str, String> {
+    match values {
+        [value] => Ok(value),
+        _ => Err(format!("{} takes a single value", name)),
+    }
+}
+
+fn set_engine_option(engine: &mut EngineConfig, key: &str, value: &str) -> Result<(), String> {
+    match key {
+        "cmd" => engine.command = value.to_string(),
+        "name" => engine.name = value.to_string(),
+        "dir" => engine.dir = Some(PathBuf::from(value)),
+        "arg" => engine.args.push(value.to_string()),
+        "tc" => {
+            let clock: TimeControl = value.parse()?;
+            let tc = &mut engine.time_control;
+            (tc.moves, tc.time, tc.increment) = (clock.moves, clock.time, clock.increment);
+        }
+        "st" => engine.time_control.movetime = Some(Duration::from_secs_f64(parse::<f64>(key, value)?.max(0.0))),
+        "nodes" => engine.time_control.nodes = Some(parse(key, value)?),
+        "depth" => engine.time_control.depth = Some(parse(key, value)?),
+        _ => match key.strip_prefix("option.") {
+            Some(name) => engine.options.push((name.to_string(), value.to_string())),
+            None => return Err(format!("Unknown engine option: {}", key)),
+        },
+    }
+    Ok(())
+}
+
+fn parse_args(args: &[String]) -> Result<(MatchOptions, Option<String>, Option<SprtConfig>), String> {
+    // group every option with the values that follow it
+    let mut groups: Vec<(&str, Vec<String>)> = vec![];
+    for arg in args {
+        match arg.strip_prefix('-').filter(|_| !arg.contains('=')) {
+            Some(name) => groups.push((name, vec![])),
+            None => groups.last_mut().ok_or(USAGE)?.1.push(arg.clone()),
+        }
+    }
+
+    let mut options = MatchOptions::default();
+    let mut engines = vec![];
+    let mut each = vec![];
+    let mut pgn_out = None;
+    let mut sprt = None;
+    let mut adjudication = Adjudication::default();
+    for (name, values) in &groups {
+        match *name {
+            "engine" => engines.push(values),
+            "each" => each.extend(values.iter().cloned()),
+            "openings" => {
+                let mut file = None;
+                let mut format = None;
+                let mut plies = None;
+                for (key, value) in pairs(values)? {
+                    match key {
+                        "file" => file = Some(value),
+                        "format" => format = Some(value.parse()?),
+                        "order" => options.random_order = value == "random",
+                        "plies" => plies = Some(parse(key, value)?),
+                        _ => return Err(format!("Unknown openings option: {}", key)),
+                    }
+                }
+                let file = file.ok_or("-openings needs a file")?;
+                let format = format.unwrap_or(match Path::new(file).extension().and_then(|e| e.to_str()) {
+                    Some("pgn") => OpeningFormat::Pgn,
+                    _ => OpeningFormat::Epd,
+                });
+                options.openings = load_openings(file, format, plies)?;
+            }
+            "variant" => {
+                options.chess960 = match single(name, values)? {
+                    "standard" => false,
+                    "fischerandom" => true,
+                    v => return Err(format!("Unsupported variant: {}", v)),
+                }
+            }
+            "rounds" => options.rounds = parse(name, single(name, values)?)?,
+            "games" => options.games = parse(name, single(name, values)?)?,
+            "concurrency" => options.concurrency = parse(name, single(name, values)?)?,
+            "srand" => options.seed = parse(name, single(name, values)?)?,
+            "maxmoves" => adjudication.max_moves = Some(parse(name, single(name, values)?)?),
+            "timemargin" => options.time_margin = Duration::from_millis(parse(name, single(name, values)?)?),
+            "movetimeout" => options.move_timeout = Duration::from_millis(parse(name, single(name, values)?)?),
+            "event" => options.event = values.join(" "),
+            "pgnout" => pgn_out = Some(single(name, values)?.to_string()),
+            "resign" => {
+                let mut resign = ResignAdjudication { move_count: 1, score: 0 };
+                for (key, value) in pairs(values)? {
+                    match key {
+                        "movecount" => resign.move_count = parse(key, value)?,
+                        "score" => resign.score = parse(key, value)?,
+                        _ => return Err(format!("Unknown resign option: {}", key)),
+                    }
+                }
+                adjudication.resign = Some(resign);
+            }
+            "sprt" => {
+                let mut config = SprtConfig::default();
+                for (key, value) in pairs(values)? {
+                    match key {
+                        "elo0" => config.elo0 = parse(key, value)?,
+                        "elo1" => config.elo1 = parse(key, value)?,
+                        "alpha" => config.alpha = parse(key, value)?,
+                        "beta" => config.beta = parse(key, value)?,
+                        _ => return Err(format!("Unknown SPRT option: {}", key)),
+                    }
+                }
+                sprt = Some(config);
+            }
+            "draw" => {
+                let mut draw = DrawAdjudication { move_number: 0, move_count: 1, score: 0 };
+                for (key, value) in pairs(values)? {
+                    match key {
+                        "movenumber" => draw.move_number = parse(key, value)?,
+                        "movecount" => draw.move_count = parse(key, value)?,
+                        "score" => draw.score = parse(key, value)?,
+                        _ => return Err(format!("Unknown draw option: {}", key)),
+                    }
+                }
+                adjudication.draw = Some(draw);
+            }
+            _ => return Err(format!("Unknown option: -{}\n\n{}", name, USAGE)),
+        }
+    }
+    options.adjudication = adjudication;
+
+    // -each applies to every engine, before the engine's own options
+    for values in engines {
+        let mut engine = EngineConfig::default();
+        for (key, value) in pairs(&each)?.into_iter().chain(pairs(values)?) {
+            set_engine_option(&mut engine, key, value)?;
+        }
+        if engine.command.is_empty() {
+            return Err(format!("-engine needs a cmd\n\n{}", USAGE));
+        }
+        if engine.name.is_empty() {
+            engine.name = Path::new(&engine.command)
+                .file_stem()
+                .map_or(engine.command.clone(), |s| s.to_string_lossy().into_owned());
+        }
+        options.engines.push(engine);
+    }
+    if options.engines.len() < 2 {
+        return Err(USAGE.to_string());
+    }
+    if sprt.is_some() && options.engines.len() != 2 {
+        return Err("-sprt needs exactly two engines".to_string());
+    }
+    Ok((options, pgn_out, sprt))
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let (options, pgn_out, sprt) = parse_args(args)?;
+    let mut pgn = pgn_out
+        .map(|path| File::create(&path).map(BufWriter::new).map_err(|e| format!("{}: {}", path, e)))
+        .transpose()?;
+    let names: Vec<&str> = options.engines.iter().map(|e| e.name.as_str()).collect();
+
+    let mut finished = vec![];
+    let outcomes = run_match(&options, |outcome| {
+        let (white, black) = (names[outcome.pairing.white], names[outcome.pairing.black]);
+        let marker = match outcome.result {
+            GameResult::WhiteWin => "1-0",
+            GameResult::BlackWin => "0-1",
+            GameResult::Draw => "1/2-1/2",
+        };
+        println!("Finished game {} ({} vs {}): {} {{{}}}", outcome.pairing.number, white, black, marker, outcome.reason);
+        if let Some(pgn) = &mut pgn {
+            write!(pgn, "{}", outcome.game).and_then(|_| pgn.flush()).map_err(|e| e.to_string())?;
+        }
+        finished.push(outcome.clone());
+        if names.len() != 2 {
+            return Ok(true);
+        }
+        let score = standings(2, &finished)[0];
+        println!(
+            "Score of {} vs {}: {} - {} - {}  [{:.3}] {}",
+            names[0], names[1], score.wins, score.losses, score.draws,
+            score.points() / score.games() as f64, score.games()
+        );
+        // pairs when the games come in pairs, single games otherwise
+        let pairs = pentanomial(&finished, 0, 1);
+        let paired = options.games >= 2 && pairs.pairs() > 0;
+        if paired {
+            println!("Ptnml(0-2): {:?}", pairs.0);
+        }
+        let estimate = if paired { pairs.elo() } else { score.elo() };
+        println!(
+            "Elo difference: {:.1} +/- {:.1}, LOS: {:.1} %, DrawRatio: {:.1} %",
+            estimate.elo, estimate.error, estimate.los * 100.0, score.draw_ratio() * 100.0
+        );
+        let Some(config) = &sprt else { return Ok(true) };
+        let report = if paired { pairs.sprt(config) } else { score.sprt(config) };
+        print!("SPRT: llr {:.2}, lbound {:.2}, ubound {:.2}", report.llr, report.lower, report.upper);
+        match report.decision() {
+            Some(hypothesis) => {
+                println!(" - {:?} was accepted", hypothesis);
+                Ok(false)
+            }
+            None => {
+                println!();
+                Ok(true)
+            }
+        }
+    })?;
+
+    let table = standings(names.len(), &outcomes);
+    let mut order: Vec<usize> = (0..names.len()).collect();
+    order.sort_by(|a, b| table[*b].points().total_cmp(&table[*a].points()));
+    println!("\nRank Name                      Games  Points   +    -    =");
+    for (rank, &i) in order.iter().enumerate() {
+        let s = table[i];
+        println!("{:>4} {:<24} {:>6} {:>7.1} {:>4} {:>4} {:>4}", rank + 1, names[i], s.games(), s.points(), s.wins, s.losses, s.draws);
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(e) = run(&args) {
+        eprintln!("{}", e);
+        exit(1);
+    }
+}
@@ -0,0 +1,278 @@
+//! # Opening Books
+//!
+//! Reading opening books in the Polyglot `.bin` format, the de facto standard supported by most
+//! engines and GUIs.
+//!
+//! A Polyglot book is a flat array of 16 byte big-endian entries, sorted by position key:
+//!
+//! | bytes  | field  |                                                |
+//! |--------|--------|------------------------------------------------|
+//! | 0..8   | key    | Polyglot hash of the position (see [`polyglot_key`]) |
+//! | 8..10  | move   | the move, see [`encode_move`]                  |
+//! | 10..12 | weight | how often the move should be played            |
+//! | 12..16 | learn  | unused                                         |
+//!
+//! Since every program hashes positions its own way, Polyglot defines its own Zobrist keys,
+//! which we need to reproduce bit for bit to find anything in the book.
+//!
+//! ### Links
+//! - [Polyglot book format](http://hgm.nubati.net/book_format.html)
+
+pub mod keys;
+pub mod builder;
+
+use std::path::Path;
+
+use crate::types::*;
+use crate::movegen::legal_moves;
+use crate::util::PRNG;
+use keys::RANDOM64;
+
+/// Size of a single book entry, in bytes.
+pub const ENTRY_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BookEntry {
+    pub key: u64,
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+/// An opening book, loaded in memory.
+pub struct Book {
+    entries: Vec<BookEntry>,
+}
+
+impl BookEntry {
+    pub fn from_bytes(bytes: &[u8; ENTRY_SIZE]) -> BookEntry {
+        BookEntry {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            raw_move: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.raw_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+}
+
+impl Book {
+    /// Build a book from its entries, sorting them by key. The order of entries with the
+    /// same key is kept.
+    pub fn new(mut entries: Vec<BookEntry>) -> Book {
+        entries.sort_by_key(|e| e.key);
+        Book { entries }
+    }
+
+    /// Load a book from a Polyglot `.bin` file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Book, String> {
+        let bytes = std::fs::read(&path).map_err(|e| format!("{}: {}", path.as_ref().display(), e))?;
+        Book::from_bytes(&bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Book, String> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(format!("Book size should be a multiple of {} bytes, got {}", ENTRY_SIZE, bytes.len()));
+        }
+        let entries = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(|chunk| BookEntry::from_bytes(chunk.try_into().unwrap()))
+            .collect();
+        Ok(Book::new(entries))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.entries.iter().flat_map(|e| e.to_bytes()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// All entries for the given key.
+    pub fn entries(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|e| e.key < key);
+        let end = start + self.entries[start..].partition_point(|e| e.key == key);
+        &self.entries[start..end]
+    }
+
+    /// The book moves for a position, with their weights. Entries with moves that are not
+    /// legal in the position (hash collisions, broken books) are skipped.
+    pub fn moves(&self, board: &Board) -> Vec<(Move, u16)> {
+        self.entries(polyglot_key(board))
+            .iter()
+            .filter_map(|e| decode_move(board, e.raw_move).map(|m| (m, e.weight)))
+            .collect()
+    }
+
+    /// Pick a book move at random, with probability proportional to its weight.
+    pub fn pick(&self, board: &Board, rng: &mut PRNG) -> Option<Move> {
+        let moves = self.moves(board);
+        let total: u64 = moves.iter().map(|(_, w)| *w as u64).sum();
+        if total == 0 {
+            // all weights zero: every move is equally (un)likely
+            return match moves.len() {
+                0 => None,
+                n => Some(moves[(rng.next() % n as u64) as usize].0),
+            };
+        }
+        let mut choice = rng.next() % total;
+        for (m, weight) in moves {
+            if choice < weight as u64 {
+                return Some(m);
+            }
+            choice -= weight as u64;
+        }
+        unreachable!("choice should be smaller than the total weight")
+    }
+}
+
+/// The Polyglot index of a piece: black pawn 0, white pawn 1, black knight 2, ..., white king 11.
+#[inline]
+fn piece_kind(piece: Piece) -> usize {
+    let color = match piece.color() {
+        Some(Color::White) => 1,
+        _ => 0,
+    };
+    (piece.ptype() as usize - PieceType::Pawn as usize) * 2 + color
+}
+
+/// Compute the Polyglot hash of a position.
+///
+/// # Example
+/// ```
+/// use tejuino::types::Board;
+/// use tejuino::book::polyglot_key;
+///
+/// assert_eq!(polyglot_key(&Board::from(tejuino::BOARD_START_FEN)), 0x463b96181691fc9c);
+/// ```
+pub fn polyglot_key(board: &Board) -> u64 {
+    let mut key = 0;
+    for sq in board.pieces().iter() {
+        key ^= RANDOM64[64 * piece_kind(board.at(sq)) + sq as usize];
+    }
+
+    for (i, rights) in [
+        CastlingRights::WHITE_KINGSIDE,
+        CastlingRights::WHITE_QUEENSIDE,
+        CastlingRights::BLACK_KINGSIDE,
+        CastlingRights::BLACK_QUEENSIDE,
+    ].into_iter().enumerate() {
+        if board.castling.has(rights) {
+            key ^= RANDOM64[768 + i];
+        }
+    }
+
+    // the en passant square only counts if a pawn can actually capture there
+    if let Some(ep) = board.en_passant {
+        let us = board.side_to_move;
+        let capturers = crate::pregen::ATTACKS.pawn[!us as usize][ep] & board.by_piece(Piece::Pawn(us));
+        if !capturers.empty() {
+            key ^= RANDOM64[772 + ep.file() as usize];
+        }
+    }
+
+    if board.side_to_move == Color::White {
+        key ^= RANDOM64[780];
+    }
+    key
+}
+
+/// Encode a move the Polyglot way: `to` in bits 0-5, `from` in bits 6-11 and the promotion piece
+/// (1 = knight, ..., 4 = queen) in bits 12-14. Castling is encoded as the king capturing its rook.
+pub fn encode_move(m: Move) -> u16 {
+    let to = m.to;
+    let promotion = match m.move_type {
+        MoveType::Promotion(ptype) => ptype as u16 - PieceType::Pawn as u16,
+        _ => 0,
+    };
+    (to as u16) | ((m.from as u16) << 6) | (promotion << 12)
+}
+
+/// Decode a Polyglot move into the matching legal move of the position, if any.
+pub fn decode_move(board: &Board, raw: u16) -> Option<Move> {
+    let to = Square::from_index((raw & 63) as usize);
+    let from = Square::from_index(((raw >> 6) & 63) as usize);
+    let promotion = (raw >> 12) & 7;
+    legal_moves(board).into_iter().find(|m| {
+        m.from == from && encode_move(*m) & 63 == to as u16 && match m.move_type {
+            MoveType::Promotion(ptype) => ptype as u16 - PieceType::Pawn as u16 == promotion,
+            _ => promotion == 0,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::book::*;
+    use crate::san::parse_san;
+
+    #[test]
+    fn test_polyglot_key() {
+        // keys from the Polyglot book format specification
+        let positions = [
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 0x463b96181691fc9c),
+            ("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1", 0x823c9b50fd114196),
+            ("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2", 0x0756b94461c50fb0),
+            ("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2", 0x662fafb965db29d4),
+            ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3", 0x22a48b5a8e47ff78),
+            ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR b kq - 0 3", 0x652a607ca3f242c1),
+            ("rnbq1bnr/ppp1pkpp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR w - - 0 4", 0x00fdd303c946bdd9),
+            ("rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3 0 3", 0x3c8123ea7b067637),
+            ("rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq - 0 4", 0x5c3f9b829b279560),
+        ];
+        for (fen, key) in positions {
+            assert_eq!(polyglot_key(&Board::from(fen)), key, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_book() {
+        let board = Board::from(crate::BOARD_START_FEN);
+        let key = polyglot_key(&board);
+        let e4 = parse_san(&board, "e4").unwrap();
+        let d4 = parse_san(&board, "d4").unwrap();
+
+        let castle_board = Board::from("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        let castle = parse_san(&castle_board, "O-O-O").unwrap();
+        assert_eq!(encode_move(castle), (Square::A1 as u16) | ((Square::E1 as u16) << 6));
+        let promotion_board = Board::from("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1");
+        let promotion = parse_san(&promotion_board, "b8=N").unwrap();
+
+        // a tiny book, written out and read back like a real one
+        let book = Book::new(vec![
+            BookEntry { key, raw_move: encode_move(d4), weight: 1, learn: 0 },
+            BookEntry { key: polyglot_key(&castle_board), raw_move: encode_move(castle), weight: 1, learn: 0 },
+            BookEntry { key, raw_move: encode_move(e4), weight: 3, learn: 0 },
+            BookEntry { key: polyglot_key(&promotion_board), raw_move: encode_move(promotion), weight: 1, learn: 0 },
+        ]);
+        let path = std::env::temp_dir().join("tejuino_test_book.bin");
+        std::fs::write(&path, book.to_bytes()).unwrap();
+        let book = Book::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(book.len(), 4);
+        assert_eq!(book.moves(&board), vec![(d4, 1), (e4, 3)]);
+        assert_eq!(book.moves(&castle_board), vec![(castle, 1)]);
+        assert_eq!(book.moves(&promotion_board), vec![(promotion, 1)]);
+        assert!(book.moves(&Board::from("4k3/8/8/8/8/8/8/4K3 w - - 0 1")).is_empty());
+
+        let mut rng = PRNG::new(42);
+        let picks: Vec<Move> = (0..1000).filter_map(|_| book.pick(&board, &mut rng)).collect();
+        let e4_count = picks.iter().filter(|m| **m == e4).count();
+        assert_eq!(picks.len(), 1000);
+        assert!((650..850).contains(&e4_count), "{}", e4_count);
+    }
+}
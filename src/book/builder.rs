@@ -0,0 +1,176 @@
+//! Building Polyglot books out of game collections.
+//!
+//! Every move played in the first plies of each game is counted, along with the result it led to
+//! from the point of view of the side that played it. Moves that were played often enough become
+//! book entries, weighted by their score (2 points per win, 1 per draw, as Polyglot does) or
+//! simply by how often they were played.
+
+use std::collections::HashMap;
+
+use crate::types::*;
+use crate::pgn::Game;
+use crate::book::{encode_move, polyglot_key, Book, BookEntry};
+
+pub struct BuildOptions {
+    /// Only moves within the first `max_ply` half-moves of a game are counted.
+    pub max_ply: usize,
+    /// Moves played in fewer games than this are left out of the book.
+    pub min_games: u32,
+    /// Weight moves by their results instead of how often they were played.
+    pub result_weighted: bool,
+    /// Include moves played by white.
+    pub white: bool,
+    /// Include moves played by black.
+    pub black: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct MoveStats {
+    games: u32,
+    /// Half points: 2 for a win, 1 for a draw.
+    score: u32,
+}
+
+pub struct BookBuilder {
+    pub options: BuildOptions,
+    stats: HashMap<(u64, u16), MoveStats>,
+    games: usize,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        BuildOptions {
+            max_ply: 16,
+            min_games: 1,
+            result_weighted: true,
+            white: true,
+            black: true,
+        }
+    }
+}
+
+impl BookBuilder {
+    pub fn new(options: BuildOptions) -> BookBuilder {
+        BookBuilder {
+            options,
+            stats: HashMap::new(),
+            games: 0,
+        }
+    }
+
+    /// Number of games added so far.
+    pub fn games(&self) -> usize {
+        self.games
+    }
+
+    /// Count the opening moves of a game. Unfinished games (`*`) have no result to weight
+    /// moves by, so they are ignored when building a result-weighted book.
+    pub fn add_game(&mut self, game: &Game) -> Result<(), String> {
+        let winner = match game.result.as_str() {
+            "1-0" => Some(Color::White),
+            "0-1" => Some(Color::Black),
+            "1/2-1/2" => None,
+            _ if self.options.result_weighted => return Ok(()),
+            _ => None,
+        };
+
+        let mut board = game.start_board()?;
+        let moves = game.mainline()?;
+        for m in moves.into_iter().take(self.options.max_ply) {
+            let us = board.side_to_move;
+            let included = match us {
+                Color::White => self.options.white,
+                Color::Black => self.options.black,
+            };
+            if included {
+                let stats = self.stats.entry((polyglot_key(&board), encode_move(m))).or_default();
+                stats.games += 1;
+                stats.score += match winner {
+                    Some(color) if color == us => 2,
+                    Some(_) => 0,
+                    None => 1,
+                };
+            }
+            board.make_move(m);
+        }
+        self.games += 1;
+        Ok(())
+    }
+
+    /// Turn the collected statistics into a book. Weights are scaled down if needed to fit in
+    /// 16 bits, and moves that end up with no weight are dropped.
+    pub fn build(&self) -> Book {
+        let weight = |s: &MoveStats| {
+            if self.options.result_weighted { s.score } else { s.games }
+        };
+        let kept: Vec<(&(u64, u16), &MoveStats)> = self
+            .stats
+            .iter()
+            .filter(|(_, s)| s.games >= self.options.min_games)
+            .collect();
+        let max = kept.iter().map(|(_, s)| weight(s)).max().unwrap_or(0);
+        let scale = (max as u64).div_ceil(u16::MAX as u64).max(1);
+
+        let mut entries: Vec<BookEntry> = kept
+            .into_iter()
+            .map(|(&(key, raw_move), s)| BookEntry {
+                key,
+                raw_move,
+                weight: (weight(s) as u64 / scale) as u16,
+                learn: 0,
+            })
+            .filter(|e| e.weight > 0)
+            .collect();
+        // most played moves first within each position, like Polyglot does
+        entries.sort_by_key(|e| (e.key, std::cmp::Reverse(e.weight), e.raw_move));
+        Book::new(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::book::builder::*;
+    use crate::pgn::PgnReader;
+    use crate::san::parse_san;
+
+    const GAMES: &str = "
+1. e4 e5 2. Nf3 1-0
+1. e4 c5 2. Nf3 0-1
+1. d4 d5 1/2-1/2
+1. e4 e5 2. Bc4 1-0
+1. c4 *
+";
+
+    #[test]
+    fn test_book_builder() {
+        let games: Vec<Game> = PgnReader::new(GAMES.as_bytes()).collect::<Result<_, _>>().unwrap();
+        assert_eq!(games.len(), 5);
+
+        let build = |options: BuildOptions| {
+            let mut builder = BookBuilder::new(options);
+            for game in &games {
+                builder.add_game(game).unwrap();
+            }
+            builder.build()
+        };
+        let start = Board::from(crate::BOARD_START_FEN);
+        let san = |s| parse_san(&start, s).unwrap();
+
+        let book = build(BuildOptions { max_ply: 2, ..Default::default() });
+        assert_eq!(book.moves(&start), vec![(san("e4"), 4), (san("d4"), 1)]);
+
+        let book = build(BuildOptions { max_ply: 2, min_games: 2, result_weighted: false, ..Default::default() });
+        assert_eq!(book.moves(&start), vec![(san("e4"), 3)]);
+        let mut after_e4 = start.clone();
+        after_e4.make_move(san("e4"));
+        assert_eq!(book.moves(&after_e4), vec![(parse_san(&after_e4, "e5").unwrap(), 2)]);
+
+        let book = build(BuildOptions { white: false, ..Default::default() });
+        assert!(book.moves(&start).is_empty());
+        // c5 won, e5 lost twice: only c5 has any weight
+        assert_eq!(book.moves(&after_e4), vec![(parse_san(&after_e4, "c5").unwrap(), 2)]);
+
+        let book = build(BuildOptions { result_weighted: false, max_ply: 1, ..Default::default() });
+        assert_eq!(book.moves(&start).len(), 3);
+    }
+}
@@ -0,0 +1,471 @@
+//! # Training Data Generation
+//!
+//! Self-play games at a fixed number of nodes per move, recording positions with the search
+//! score and the final result of the game, to train networks and tune the evaluation.
+//!
+//! Each game starts from the start position, follows an opening book if one is given and then
+//! plays a few random moves, so that no two games are alike. Openings that are already lost for
+//! one side are thrown away. Positions are only kept when they are quiet enough to be useful:
+//! not in check, with a best move that isn't a capture or a promotion, and without a mate score.
+//!
+//! Games end by the rules (mate, stalemate, the fifty move rule, threefold repetition), on
+//! insufficient material, or are adjudicated: as a win once the score has stayed past
+//! [`DatagenOptions::win_score`] for a few plies, as a draw once it has stayed close to zero.
+//!
+//! Records are 32 bytes each: the position packed with [`Board::pack`], which leaves its last
+//! four bytes free for the rest, little-endian:
+//!
+//! | bytes  | field    |                                                    |
+//! |--------|----------|----------------------------------------------------|
+//! | 0..28  | position | see [`mod@crate::types::pack`]                     |
+//! | 28..30 | score    | search score, from white's point of view           |
+//! | 30     | result   | 0 black won, 1 draw, 2 white won                   |
+//! | 31     | unused   | zero                                               |
+//!
+//! ### Links
+//! - [Texel's Tuning Method](https://www.chessprogramming.org/Texel%27s_Tuning_Method)
+//! - [NNUE](https://www.chessprogramming.org/NNUE)
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+
+use crate::BOARD_START_FEN;
+use crate::types::*;
+use crate::book::{polyglot_key, Book};
+use crate::movegen::legal_moves;
+use crate::search::{is_mate_score, is_tactical, search, Limits};
+use crate::util::PRNG;
+
+/// Size of a single record, in bytes.
+pub const RECORD_SIZE: usize = PACKED_SIZE;
+
+/// Book moves followed at most, in case the book goes around in circles.
+const MAX_BOOK_PLIES: usize = 64;
+
+/// Result of a game, from white's point of view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    BlackWin,
+    Draw,
+    WhiteWin,
+}
+
+/// A position from a self-play game, with its score and how the game ended.
+#[derive(Debug, Clone)]
+pub struct DataRecord {
+    pub board: Board,
+    /// Search score in centipawns, from white's point of view.
+    pub score: i16,
+    pub result: GameResult,
+}
+
+#[derive(Debug, Clone)]
+pub struct DatagenOptions {
+    pub games: usize,
+    pub threads: usize,
+    /// Nodes searched for every move.
+    pub nodes: u64,
+    /// Random moves played after the book, if any.
+    pub random_plies: usize,
+    pub seed: u64,
+    /// Openings scored further than this from zero are thrown away.
+    pub max_opening_score: i32,
+    /// Adjudicate a win once the score has been at least this for `win_plies` plies in a row.
+    pub win_score: i32,
+    pub win_plies: usize,
+    /// Adjudicate a draw once the score has been within this of zero for `draw_plies` plies
+    /// in a row, after `draw_after` plies have been played.
+    pub draw_score: i32,
+    pub draw_plies: usize,
+    pub draw_after: usize,
+    /// Games still going after this many plies are drawn.
+    pub max_plies: usize,
+}
+
+/// Games and positions generated so far.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DatagenStats {
+    pub games: usize,
+    pub positions: usize,
+    pub white_wins: usize,
+    pub draws: usize,
+    pub black_wins: usize,
+}
+
+/// Writes records to a file, or anything else.
+pub struct DataWriter<W: Write> {
+    writer: W,
+}
+
+/// Reads records back, one at a time.
+pub struct DataReader<R: Read> {
+    reader: R,
+}
+
+impl Default for DatagenOptions {
+    fn default() -> DatagenOptions {
+        DatagenOptions {
+            games: 100,
+            threads: 1,
+            nodes: 5000,
+            random_plies: 8,
+            seed: 1,
+            max_opening_score: 400,
+            win_score: 2000,
+            win_plies: 4,
+            draw_score: 10,
+            draw_plies: 10,
+            draw_after: 80,
+            max_plies: 400,
+        }
+    }
+}
+
+impl GameResult {
+    /// The result as a score for white: 0, 0.5 or 1.
+    pub fn score(&self) -> f64 {
+        match self {
+            GameResult::BlackWin => 0.0,
+            GameResult::Draw => 0.5,
+            GameResult::WhiteWin => 1.0,
+        }
+    }
+
+    /// The result when `color` wins.
+    pub fn win(color: Color) -> GameResult {
+        match color {
+            Color::White => GameResult::WhiteWin,
+            Color::Black => GameResult::BlackWin,
+        }
+    }
+}
+
+impl DataRecord {
+    pub fn to_bytes(&self) -> [u8; RECORD_SIZE] {
+        let mut bytes = self.board.pack();
+        bytes[28..30].copy_from_slice(&self.score.to_le_bytes());
+        bytes[30] = self.result as u8;
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; RECORD_SIZE]) -> Result<DataRecord, String> {
+        let board = Board::unpack(bytes)?;
+        let score = i16::from_le_bytes(bytes[28..30].try_into().unwrap());
+        let result = match bytes[30] {
+            0 => GameResult::BlackWin,
+            1 => GameResult::Draw,
+            2 => GameResult::WhiteWin,
+            other => return Err(format!("Invalid result: {}", other)),
+        };
+        Ok(DataRecord { board, score, result })
+    }
+}
+
+impl DataWriter<BufWriter<File>> {
+    /// Create (or truncate) a file to write records to.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<DataWriter<BufWriter<File>>, String> {
+        let file = File::create(&path).map_err(|e| format!("{}: {}", path.as_ref().display(), e))?;
+        Ok(DataWriter::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> DataWriter<W> {
+    pub fn new(writer: W) -> DataWriter<W> {
+        DataWriter { writer }
+    }
+
+    pub fn write(&mut self, record: &DataRecord) -> Result<(), String> {
+        self.writer.write_all(&record.to_bytes()).map_err(|e| e.to_string())
+    }
+
+    pub fn flush(&mut self) -> Result<(), String> {
+        self.writer.flush().map_err(|e| e.to_string())
+    }
+
+    /// Flush the records written and give back the inner writer.
+    pub fn into_inner(mut self) -> Result<W, String> {
+        self.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl DataReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<DataReader<BufReader<File>>, String> {
+        let file = File::open(&path).map_err(|e| format!("{}: {}", path.as_ref().display(), e))?;
+        Ok(DataReader::new(BufReader::new(file)))
+    }
+}
+
+impl<R: Read> DataReader<R> {
+    pub fn new(reader: R) -> DataReader<R> {
+        DataReader { reader }
+    }
+}
+
+impl<R: Read> Iterator for DataReader<R> {
+    type Item = Result<DataRecord, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut bytes = [0; RECORD_SIZE];
+        let mut filled = 0;
+        while filled < RECORD_SIZE {
+            match self.reader.read(&mut bytes[filled..]) {
+                Ok(0) if filled == 0 => return None,
+                Ok(0) => return Some(Err(format!("Truncated record: {} of {} bytes", filled, RECORD_SIZE))),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Some(Err(e.to_string())),
+            }
+        }
+        Some(DataRecord::from_bytes(&bytes))
+    }
+}
+
+/// Checks if neither side has enough material left to mate: bare kings, or a single minor piece.
+pub fn insufficient_material(board: &Board) -> bool {
+    let heavy = board.by_piece_type(PieceType::Pawn)
+        | board.by_piece_type(PieceType::Rook)
+        | board.by_piece_type(PieceType::Queen);
+    let minors = board.by_piece_type(PieceType::Knight) | board.by_piece_type(PieceType::Bishop);
+    heavy == Bitboard(0) && !minors.more_than_one()
+}
+
+/// The result of the game at `board` by the rules, if it's over: mate, stalemate, the fifty move
+/// rule, threefold repetition or insufficient material. `seen` counts how many times each
+/// position of the game has been reached, and gets `board` added.
+pub fn game_over(board: &Board, seen: &mut HashMap<u64, usize>) -> Option<GameResult> {
+    if legal_moves(board).is_empty() {
+        return Some(if board.in_check() { GameResult::win(!board.side_to_move) } else { GameResult::Draw });
+    }
+    let repetitions = seen.entry(polyglot_key(board)).or_default();
+    *repetitions += 1;
+    if *repetitions >= 3 || board.halfmove_clock >= 100 || insufficient_material(board) {
+        return Some(GameResult::Draw);
+    }
+    None
+}
+
+/// A seed for each game, so that a game is the same whatever thread plays it.
+fn game_seed(seed: u64, game: usize) -> u64 {
+    // splitmix64
+    let mut z = seed.wrapping_add((game as u64 + 1).wrapping_mul(0x9E3779B97F4A7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    (z ^ (z >> 31)).max(1)
+}
+
+/// Follow the book, then play `random_plies` random moves. `None` if the game ended on the way.
+pub fn random_opening(book: Option<&Book>, random_plies: usize, rng: &mut PRNG) -> Option<Board> {
+    let mut board = Board::from(BOARD_START_FEN);
+    if let Some(book) = book {
+        for _ in 0..MAX_BOOK_PLIES {
+            match book.pick(&board, rng) {
+                Some(m) => board.make_move(m),
+                None => break,
+            }
+        }
+    }
+    for _ in 0..random_plies {
+        let moves = legal_moves(&board);
+        if moves.is_empty() {
+            return None;
+        }
+        board.make_move(moves[(rng.next() % moves.len() as u64) as usize]);
+    }
+    if legal_moves(&board).is_empty() {
+        return None;
+    }
+    Some(board)
+}
+
+/// Play a game from a random opening, and return its result with the positions worth keeping.
+pub fn play_game(options: &DatagenOptions, book: Option<&Book>, rng: &mut PRNG) -> (GameResult, Vec<DataRecord>) {
+    let limits = Limits::nodes(options.nodes);
+    let mut board = loop {
+        if let Some(board) = random_opening(book, options.random_plies, rng) {
+            if search(&board, &limits).score.abs() <= options.max_opening_score {
+                break board;
+            }
+        }
+    };
+
+    let mut positions: Vec<(Board, i16)> = vec![];
+    let mut seen: HashMap<u64, usize> = HashMap::new();
+    let (mut win_plies, mut draw_plies) = (0, 0);
+    let mut ply = 0;
+    let result = loop {
+        if let Some(result) = game_over(&board, &mut seen) {
+            break result;
+        }
+        if ply >= options.max_plies {
+            break GameResult::Draw;
+        }
+
+        let found = search(&board, &limits);
+        let m = found.best_move.expect("There should be a legal move");
+        let score = match board.side_to_move {
+            Color::White => found.score,
+            Color::Black => -found.score,
+        };
+
+        win_plies = if score.abs() >= options.win_score { win_plies + 1 } else { 0 };
+        if win_plies >= options.win_plies {
+            break GameResult::win(if score > 0 { Color::White } else { Color::Black });
+        }
+        draw_plies = if ply >= options.draw_after && score.abs() <= options.draw_score { draw_plies + 1 } else { 0 };
+        if draw_plies >= options.draw_plies {
+            break GameResult::Draw;
+        }
+
+        if found.depth > 0 && !board.in_check() && !is_tactical(&board, m) && !is_mate_score(score) {
+            positions.push((board.clone(), score.clamp(i16::MIN as i32, i16::MAX as i32) as i16));
+        }
+        board.make_move(m);
+        ply += 1;
+    };
+
+    let records = positions
+        .into_iter()
+        .map(|(board, score)| DataRecord { board, score, result })
+        .collect();
+    (result, records)
+}
+
+/// Play `options.games` games over `options.threads` threads, writing every record kept.
+/// `progress` is called after each game with the totals so far.
+pub fn generate<W: Write>(
+    options: &DatagenOptions,
+    book: Option<&Book>,
+    writer: &mut DataWriter<W>,
+    mut progress: impl FnMut(&DatagenStats),
+) -> Result<DatagenStats, String> {
+    crate::init();
+    let next_game = AtomicUsize::new(0);
+    let mut stats = DatagenStats::default();
+    std::thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..options.threads.max(1) {
+            let sender = sender.clone();
+            let next_game = &next_game;
+            scope.spawn(move || loop {
+                let game = next_game.fetch_add(1, Ordering::Relaxed);
+                if game >= options.games {
+                    break;
+                }
+                let mut rng = PRNG::new(game_seed(options.seed, game));
+                if sender.send(play_game(options, book, &mut rng)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        for (result, records) in receiver {
+            for record in &records {
+                writer.write(record)?;
+            }
+            stats.games += 1;
+            stats.positions += records.len();
+            match result {
+                GameResult::WhiteWin => stats.white_wins += 1,
+                GameResult::Draw => stats.draws += 1,
+                GameResult::BlackWin => stats.black_wins += 1,
+            }
+            progress(&stats);
+        }
+        writer.flush()
+    })?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::datagen::*;
+
+    #[test]
+    fn test_records() {
+        let fens = [
+            BOARD_START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 10",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 b kq - 7 31",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 99 1000",
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9",
+        ];
+        let mut writer = DataWriter::new(vec![]);
+        for (i, fen) in fens.iter().enumerate() {
+            let record = DataRecord {
+                board: Board::from(fen),
+                score: i as i16 * 300 - 900,
+                result: [GameResult::BlackWin, GameResult::Draw, GameResult::WhiteWin][i % 3],
+            };
+            let decoded = DataRecord::from_bytes(&record.to_bytes()).unwrap();
+            assert_eq!(decoded.board.fen(), *fen);
+            assert_eq!((decoded.score, decoded.result), (record.score, record.result));
+            writer.write(&record).unwrap();
+        }
+
+        let bytes = writer.into_inner().unwrap();
+        assert_eq!(bytes.len(), fens.len() * RECORD_SIZE);
+        let read: Vec<DataRecord> = DataReader::new(&bytes[..]).collect::<Result<_, _>>().unwrap();
+        assert_eq!(read.iter().map(|r| r.board.fen()).collect::<Vec<_>>(), fens);
+
+        let mut truncated = DataReader::new(&bytes[..RECORD_SIZE + 5]);
+        assert!(truncated.next().unwrap().is_ok());
+        assert!(truncated.next().unwrap().is_err());
+
+        let mut invalid = bytes[..RECORD_SIZE].to_vec();
+        invalid[30] = 3;
+        assert!(DataRecord::from_bytes(&invalid[..].try_into().unwrap()).is_err());
+        invalid[30] = 1;
+        invalid[24] = 2;
+        assert!(DataRecord::from_bytes(&invalid[..].try_into().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        assert!(insufficient_material(&Board::from("8/8/4k3/8/8/3K4/8/8 w - - 0 1")));
+        assert!(insufficient_material(&Board::from("8/8/4k3/8/8/3KB3/8/8 w - - 0 1")));
+        assert!(insufficient_material(&Board::from("8/8/4kn2/8/8/3K4/8/8 b - - 0 1")));
+        assert!(!insufficient_material(&Board::from("8/8/4kn2/8/8/3KB3/8/8 w - - 0 1")));
+        assert!(!insufficient_material(&Board::from("8/8/4k3/8/8/3K4/7P/8 w - - 0 1")));
+        assert!(!insufficient_material(&Board::from(BOARD_START_FEN)));
+    }
+
+    #[test]
+    fn test_generate() {
+        let options = DatagenOptions {
+            games: 4,
+            threads: 2,
+            nodes: 300,
+            max_plies: 60,
+            ..Default::default()
+        };
+        let mut writer = DataWriter::new(vec![]);
+        let mut calls = 0;
+        let stats = generate(&options, None, &mut writer, |_| calls += 1).unwrap();
+        assert_eq!((stats.games, calls), (4, 4));
+        assert_eq!(stats.white_wins + stats.draws + stats.black_wins, 4);
+
+        let bytes = writer.into_inner().unwrap();
+        assert_eq!(bytes.len(), stats.positions * RECORD_SIZE);
+        for record in DataReader::new(&bytes[..]) {
+            let record = record.unwrap();
+            assert!(!record.board.in_check());
+            assert!(!is_mate_score(record.score as i32));
+        }
+
+        // a game only depends on its seed, not on the thread playing it
+        let mut a = PRNG::new(game_seed(options.seed, 1));
+        let mut b = PRNG::new(game_seed(options.seed, 1));
+        let (ra, a) = play_game(&options, None, &mut a);
+        let (rb, b) = play_game(&options, None, &mut b);
+        assert_eq!(ra, rb);
+        assert_eq!(a.iter().map(|r| (r.board.fen(), r.score)).collect::<Vec<_>>(),
+                   b.iter().map(|r| (r.board.fen(), r.score)).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,262 @@
+//! # EPD
+//!
+//! Extended Position Description: the first four fields of a FEN, followed by a list of
+//! `opcode operand...;` operations describing the position. Test suites (WAC, STS, ...) are
+//! distributed as EPD files, with `bm` (best move) and `am` (avoid move) operations giving the
+//! expected solution, so they double as a tactical regression test for the engine.
+//!
+//! ```text
+//! 2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";
+//! ```
+//!
+//! ### Links
+//! - [EPD Specification](https://www.chessprogramming.org/Extended_Position_Description)
+
+use std::fmt::Display;
+
+use crate::types::*;
+use crate::san::{parse_san, to_san};
+use crate::search::{search, Limits};
+
+/// A position along with its operations.
+#[derive(Clone)]
+pub struct Epd {
+    pub board: Board,
+    /// Operations in the order they appear, as opcode and operands.
+    pub ops: Vec<(String, Vec<String>)>,
+}
+
+/// The outcome of searching a single test position.
+#[derive(Debug, Clone)]
+pub struct PositionResult {
+    pub id: Option<String>,
+    pub found: Option<Move>,
+    pub solved: bool,
+}
+
+/// Solved/failed counts for a whole test suite.
+#[derive(Debug, Clone, Default)]
+pub struct SuiteReport {
+    pub solved: usize,
+    pub failed: usize,
+    pub results: Vec<PositionResult>,
+}
+
+impl Epd {
+    /// Parse a single EPD line. The moves of `bm` and `am` operations must be legal SAN.
+    ///
+    /// # Example
+    /// ```
+    /// use tejuino::epd::Epd;
+    ///
+    /// let epd = Epd::parse("4k3/8/8/8/8/8/3R4/4K3 w - - bm Rd8+; id \"test; 1\"; acd 5;").unwrap();
+    /// assert_eq!(epd.id(), Some("test; 1"));
+    /// assert_eq!(epd.op("acd"), Some(&["5".to_string()][..]));
+    /// assert_eq!(epd.best_moves().unwrap().len(), 1);
+    /// ```
+    pub fn parse(line: &str) -> Result<Epd, String> {
+        let line = line.trim();
+        let (mut fen, mut rest) = (vec![], line);
+        while fen.len() < 4 && !rest.is_empty() {
+            let (field, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            fen.push(field);
+            rest = tail.trim_start();
+        }
+        if fen.len() < 4 {
+            return Err(format!("EPD should have at least four fields: {}", line));
+        }
+        let mut epd = Epd {
+            board: Board::from_fen(&fen.join(" "))?,
+            ops: parse_ops(rest)?,
+        };
+        // the move counters are not part of the position, but can be given as operations
+        let counter = |opcode: &str, value: Result<i64, String>| {
+            let value = value?;
+            u32::try_from(value).map_err(|_| format!("Invalid integer operand for {}: {}", opcode, value))
+        };
+        if let Some(hmvc) = epd.op_int("hmvc") {
+            epd.board.halfmove_clock = counter("hmvc", hmvc)?;
+        }
+        if let Some(fmvn) = epd.op_int("fmvn") {
+            epd.board.fullmove_number = counter("fmvn", fmvn)?;
+        }
+        epd.best_moves().map_err(|e| format!("Invalid bm operation: {}", e))?;
+        epd.avoid_moves().map_err(|e| format!("Invalid am operation: {}", e))?;
+        Ok(epd)
+    }
+
+    /// The operands of the first operation with the given opcode.
+    pub fn op(&self, opcode: &str) -> Option<&[String]> {
+        self.ops.iter().find(|(op, _)| op == opcode).map(|(_, operands)| operands.as_slice())
+    }
+
+    /// The first operand of `opcode` as an integer, used for `ce`, `acd`, `dm` and the like.
+    pub fn op_int(&self, opcode: &str) -> Option<Result<i64, String>> {
+        let operand = self.op(opcode)?.first()?;
+        Some(operand.parse().map_err(|_| format!("Invalid integer operand for {}: {}", opcode, operand)))
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.op("id")?.first().map(|s| s.as_str())
+    }
+
+    /// The comment operation `c0`.
+    pub fn comment(&self) -> Option<&str> {
+        self.op("c0")?.first().map(|s| s.as_str())
+    }
+
+    fn san_moves(&self, opcode: &str) -> Result<Vec<Move>, String> {
+        self.op(opcode)
+            .unwrap_or(&[])
+            .iter()
+            .map(|san| parse_san(&self.board, san))
+            .collect()
+    }
+
+    /// Moves listed under `bm`.
+    pub fn best_moves(&self) -> Result<Vec<Move>, String> {
+        self.san_moves("bm")
+    }
+
+    /// Moves listed under `am`.
+    pub fn avoid_moves(&self) -> Result<Vec<Move>, String> {
+        self.san_moves("am")
+    }
+
+    /// Checks if `m` solves the position: it must be one of the best moves, if any are given,
+    /// and none of the moves to avoid.
+    pub fn is_solution(&self, m: Move) -> Result<bool, String> {
+        let best = self.best_moves()?;
+        let avoid = self.avoid_moves()?;
+        Ok((best.is_empty() || best.contains(&m)) && !avoid.contains(&m))
+    }
+}
+
+fn parse_ops(text: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    let mut ops = vec![];
+    let mut operands: Vec<String> = vec![];
+    let mut chars = text.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match chars.next() {
+            None => break,
+            Some(';') => {
+                if !operands.is_empty() {
+                    let opcode = operands.remove(0);
+                    ops.push((opcode, std::mem::take(&mut operands)));
+                }
+            }
+            Some('"') => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => s.push(c),
+                        None => return Err(format!("Unterminated string in EPD operations: {}", text)),
+                    }
+                }
+                operands.push(s);
+            }
+            Some(c) => {
+                let mut s = c.to_string();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != ';') {
+                    s.push(c);
+                }
+                operands.push(s);
+            }
+        }
+    }
+    if !operands.is_empty() {
+        return Err(format!("Unterminated EPD operation: {}", text));
+    }
+    Ok(ops)
+}
+
+impl Display for Epd {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fen = self.board.fen();
+        let fields: Vec<&str> = fen.split(' ').take(4).collect();
+        write!(f, "{}", fields.join(" "))?;
+        for (opcode, operands) in &self.ops {
+            write!(f, " {}", opcode)?;
+            // identification and comment operands are strings, and always quoted
+            let string_op = opcode == "id" || (opcode.len() == 2 && opcode.starts_with('c') && opcode != "ce");
+            for operand in operands {
+                if string_op || operand.is_empty() || operand.contains(|c: char| c.is_whitespace() || c == ';') {
+                    write!(f, " \"{}\"", operand)?;
+                } else {
+                    write!(f, " {}", operand)?;
+                }
+            }
+            write!(f, ";")?;
+        }
+        Ok(())
+    }
+}
+
+/// Search every position of a test suite with the given limits, and count how many were solved.
+/// Positions without `bm` or `am` operations are skipped.
+pub fn run_suite(positions: &[Epd], limits: &Limits) -> Result<SuiteReport, String> {
+    let mut report = SuiteReport::default();
+    for epd in positions.iter().filter(|e| e.op("bm").is_some() || e.op("am").is_some()) {
+        let found = search(&epd.board, limits).best_move;
+        let solved = match found {
+            Some(m) => epd.is_solution(m)?,
+            None => false,
+        };
+        if solved {
+            report.solved += 1;
+        } else {
+            report.failed += 1;
+        }
+        report.results.push(PositionResult {
+            id: epd.id().map(|s| s.to_string()),
+            found,
+            solved,
+        });
+    }
+    Ok(report)
+}
+
+impl PositionResult {
+    /// The found move in SAN, for reporting.
+    pub fn found_san(&self, epd: &Epd) -> String {
+        self.found.map_or("(none)".to_string(), |m| to_san(&epd.board, m))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::epd::*;
+
+    #[test]
+    fn test_epd() {
+        let line = r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001"; c0 "Mate; in 3";"#;
+        let epd = Epd::parse(line).unwrap();
+        assert_eq!(epd.id(), Some("WAC.001"));
+        assert_eq!(epd.comment(), Some("Mate; in 3"));
+        assert_eq!(epd.to_string(), line);
+        assert!(Epd::parse("8/8/8 w - -").is_err());
+        assert!(Epd::parse("4k3/8/8/8/8/8/8/4K3 w - - id \"x\"").is_err());
+        // runs of whitespace between the fields
+        let epd = Epd::parse("4k3/8/8/8/8/8/8/4K3  w -\t-   bm Kd1;").unwrap();
+        assert_eq!(epd.best_moves().unwrap().len(), 1);
+        assert!(Epd::parse("4k3/8/8/8/8/8/8/4K3 w - - bm Qe8;").is_err());
+        assert!(Epd::parse("4k3/8/8/8/8/8/8/4K3 w - - am Kd1 Qe8;").is_err());
+        assert!(Epd::parse("4k3/8/8/8/8/8/8/4K3 w - - hmvc -1;").is_err());
+        assert!(Epd::parse("4k3/8/8/8/8/8/8/4K3 w - - fmvn 4294967296;").is_err());
+
+        let epd = Epd::parse("4k3/8/8/8/8/8/8/4K3 b - - hmvc 12; fmvn 40;").unwrap();
+        assert_eq!(epd.board.halfmove_clock, 12);
+        assert_eq!(epd.board.fullmove_number, 40);
+
+        let suite = [
+            Epd::parse("6k1/5ppp/8/8/8/8/8/4R1K1 w - - bm Re8#; id \"mate\";").unwrap(),
+            Epd::parse("4k3/8/8/3q4/8/8/3R4/4K3 w - - am Rd1; id \"hanging queen\";").unwrap(),
+            Epd::parse("4k3/8/8/8/8/8/8/4K3 w - - id \"no solution\";").unwrap(),
+        ];
+        let report = run_suite(&suite, &Limits::depth(3)).unwrap();
+        assert_eq!((report.solved, report.failed), (2, 0));
+        assert_eq!(report.results.len(), 2);
+    }
+}
@@ -0,0 +1,183 @@
+//! # Evaluation
+//!
+//! A static evaluation of a position: material plus piece-square tables, taken from the
+//! "Simplified Evaluation Function" on the Chess Programming Wiki. Scores are in centipawns,
+//! from the point of view of the side to move.
+//!
+//! The evaluation is linear in its weights, so for tuning they can be handled as a flat vector
+//! (see [`params`]), with the evaluation of a position being the dot product of its [`trace`]
+//! with them.
+//!
+//! ### Links
+//! - [Simplified Evaluation Function](https://www.chessprogramming.org/Simplified_Evaluation_Function)
+
+use crate::types::*;
+
+/// Piece values, indexed by `PieceType`.
+pub const PIECE_VALUES: [i32; 8] = [0, 0, 100, 320, 330, 500, 900, 0];
+
+// Tables are written as seen from white's side of the board (rank 8 first),
+// so a white piece on `sq` is looked up at `sq ^ 56` and a black one at `sq`.
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const ROOK_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const QUEEN_TABLE: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const KING_TABLE: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+/// Piece-square tables, indexed by `PieceType`.
+pub const PSQT: [&[i32; 64]; 8] = [
+    &[0; 64], &[0; 64], &PAWN_TABLE, &KNIGHT_TABLE, &BISHOP_TABLE, &ROOK_TABLE, &QUEEN_TABLE, &KING_TABLE,
+];
+
+/// Number of evaluation weights: the piece values from pawn to queen, then the piece-square
+/// tables from pawn to king, each in the order it's written in.
+pub const NUM_PARAMS: usize = 5 + 6 * 64;
+
+/// Index of the value of a piece type in the flat weights.
+#[inline]
+fn value_index(ptype: PieceType) -> usize {
+    ptype as usize - PieceType::Pawn as usize
+}
+
+/// Index of a piece-square table entry in the flat weights.
+#[inline]
+fn psqt_index(ptype: PieceType, index: usize) -> usize {
+    5 + (ptype as usize - PieceType::Pawn as usize) * 64 + index
+}
+
+/// The evaluation weights as a flat vector, laid out as described in [`NUM_PARAMS`].
+pub fn params() -> Vec<i32> {
+    let values = PIECE_VALUES[PieceType::Pawn as usize..PieceType::King as usize].iter();
+    let tables = PSQT[PieceType::Pawn as usize..].iter().flat_map(|table| table.iter());
+    values.chain(tables).copied().collect()
+}
+
+/// How many times each weight counts in the evaluation of `board`, from white's point of view,
+/// sorted by index and without zeros. The evaluation is the dot product of this with [`params`].
+pub fn trace(board: &Board) -> Vec<(usize, i32)> {
+    let mut coefficients = [0; NUM_PARAMS];
+    for sq in board.pieces().iter() {
+        let piece = board.at(sq);
+        let ptype = piece.ptype();
+        let (sign, index) = match piece.color() {
+            Some(Color::White) => (1, sq as usize ^ 56),
+            _ => (-1, sq as usize),
+        };
+        if ptype != PieceType::King {
+            coefficients[value_index(ptype)] += sign;
+        }
+        coefficients[psqt_index(ptype, index)] += sign;
+    }
+    coefficients.into_iter().enumerate().filter(|(_, c)| *c != 0).collect()
+}
+
+/// Evaluate the position from the point of view of the side to move.
+pub fn evaluate(board: &Board) -> i32 {
+    let mut score = 0;
+    for sq in board.pieces().iter() {
+        let piece = board.at(sq);
+        let ptype = piece.ptype() as usize;
+        match piece.color() {
+            Some(Color::White) => score += PIECE_VALUES[ptype] + PSQT[ptype][sq as usize ^ 56],
+            Some(Color::Black) => score -= PIECE_VALUES[ptype] + PSQT[ptype][sq as usize],
+            None => {}
+        }
+    }
+    match board.side_to_move {
+        Color::White => score,
+        Color::Black => -score,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::*;
+
+    #[test]
+    fn test_trace() {
+        let params = params();
+        assert_eq!(params.len(), NUM_PARAMS);
+        assert_eq!(params[value_index(PieceType::Rook)], 500);
+        assert_eq!(params[psqt_index(PieceType::King, 63)], 20);
+
+        for fen in [
+            crate::BOARD_START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 0 1",
+            "4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1",
+        ] {
+            let board = Board::from(fen);
+            let white_eval: i32 = trace(&board).iter().map(|(i, c)| params[*i] * c).sum();
+            let eval = if board.side_to_move == Color::White { white_eval } else { -white_eval };
+            assert_eq!(eval, evaluate(&board), "{}", fen);
+        }
+        // symmetric positions cancel out
+        assert!(trace(&Board::from(crate::BOARD_START_FEN)).is_empty());
+    }
+}
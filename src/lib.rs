@@ -7,37 +7,107 @@ pub mod types;
 pub mod util;
 pub mod pregen;
 pub mod magic;
+pub mod pext;
+pub mod fill;
+pub mod san;
+pub mod pgn;
+pub mod epd;
+pub mod eval;
+pub mod nnue;
+pub mod search;
+pub mod book;
+pub mod bench;
+pub mod datagen;
+pub mod tune;
+pub mod tunables;
+pub mod uci;
+pub mod spsa;
+pub mod tournament;
+pub mod stats;
 
-use pregen::Pregen;
-use types::*;
+use std::sync::OnceLock;
 
-use lazy_static::lazy_static;
+use pregen::Pregen;
 
 pub const BOARD_START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
-lazy_static! {
-    pub static ref PREGEN: Pregen = Pregen::init();
+static PREGEN: OnceLock<Pregen> = OnceLock::new();
+
+/// The sliding attack tables, built the first time they're needed. The leaper, between and
+/// line tables are plain statics in [`mod@pregen`], built at compile time.
+#[inline]
+pub fn pregen() -> &'static Pregen {
+    PREGEN.get_or_init(Pregen::init)
+}
+
+/// Build the sliding attack tables now, instead of on the first move generated, so that the
+/// cost isn't paid in the middle of a search. Calling it again does nothing.
+pub fn init() {
+    pregen();
 }
 
 // Tests
 #[cfg(test)]
 mod tests {
     use crate::*;
+    use crate::types::*;
 
     #[test]
     fn test_square() {
-        let all_files = vec![File::FileA, File::FileB, File::FileC, File::FileD, File::FileE, File::FileF, File::FileG, File::FileH];
-        let all_ranks = vec![Rank::Rank1, Rank::Rank2, Rank::Rank3, Rank::Rank4, Rank::Rank5, Rank::Rank6, Rank::Rank7, Rank::Rank8];
+        let all_files = [File::FileA, File::FileB, File::FileC, File::FileD, File::FileE, File::FileF, File::FileG, File::FileH];
+        let all_ranks = [Rank::Rank1, Rank::Rank2, Rank::Rank3, Rank::Rank4, Rank::Rank5, Rank::Rank6, Rank::Rank7, Rank::Rank8];
 
         for sq in Square::all() {
             let n = sq as usize;
-            assert_eq!(Square::from(n as isize), sq);
+            assert_eq!(Square::from_index(n), sq);
+            assert_eq!(Square::try_from(n as u8), Ok(sq));
             let file_n = n % 8;
             let rank_n = n / 8;
             assert_eq!(sq.file(), all_files[file_n]);
             assert_eq!(sq.rank(), all_ranks[rank_n]);
         }
 
+        assert!(Square::try_from(64u8).is_err());
+        assert!(Square::try_from(-1isize).is_err());
+        assert_eq!(Square::new(File::FileE, Rank::Rank4), Square::E4);
+        assert_eq!(File::all().collect::<Vec<_>>(), all_files);
+        assert_eq!(Rank::all().collect::<Vec<_>>(), all_ranks);
+        for (file, c) in File::all().zip('a'..='h') {
+            assert_eq!((file.char(), File::try_from(c)), (c, Ok(file)));
+        }
+        for (rank, c) in Rank::all().zip('1'..='8') {
+            assert_eq!((rank.char(), Rank::try_from(c)), (c, Ok(rank)));
+        }
+        assert!(File::try_from(8usize).is_err() && File::try_from('i').is_err());
+        assert!(Rank::try_from(-1isize).is_err() && Rank::try_from('9').is_err());
+
+        // parsing
+        for sq in Square::all() {
+            assert_eq!(sq.algebraic().parse(), Ok(sq));
+        }
+        for name in ["", "e", "e44", "i4", "e9", "E4", "4e"] {
+            assert!(name.parse::<Square>().is_err(), "{}", name);
+        }
+
+        // offsets never wrap around the board
+        assert_eq!(Square::E4.offset(1, 2), Some(Square::F6));
+        assert_eq!(Square::H4.offset(1, 0), None);
+        assert_eq!(Square::A1.offset(0, -1), None);
+        assert_eq!(Square::H4.shift(Direction::UpRight), None);
+        assert_eq!(Square::H4.shift(Direction::UpLeft), Some(Square::G5));
+        assert_eq!(Square::A8.step(6), None);
+        assert_eq!(Square::B1.step(15), Some(Square::A3));
+
+        // relative to each color
+        assert_eq!(Square::E2.flip(), Square::E7);
+        assert_eq!(Square::E2.relative(Color::Black), Square::E7);
+        assert_eq!(Square::E7.relative_rank(Color::Black), Rank::Rank2);
+        assert_eq!(Square::E7.relative_rank(Color::White), Rank::Rank7);
+        for sq in Square::all() {
+            assert_eq!(sq.flip().flip(), sq);
+            assert_eq!(sq.flip().relative_rank(Color::Black), sq.rank());
+        }
+
         // square methods
         assert_eq!(Square::E4.dist(Square::E4), 0);
         assert_eq!(Square::E4.dist(Square::E5), 1);
@@ -72,19 +142,43 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_fen() {
+        assert_eq!(Board::from_fen(BOARD_START_FEN).unwrap().fen(), BOARD_START_FEN);
+        // each side needs exactly one king
+        assert!(Board::from_fen("8/8/8/8/8/8/8/4K3 w - - 0 1").is_err());
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/8 b - - 0 1").is_err());
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/3KK3 w - - 0 1").is_err());
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").is_ok());
+    }
+
     #[test]
     fn test_pregen() {
         assert_eq!(
-            PREGEN.attacks.king[Square::E4],
+            pregen::ATTACKS.king[Square::E4],
             Bitboard::squares(&[Square::D3, Square::E3, Square::F3, Square::D4,
                                 Square::F4, Square::D5, Square::E5, Square::F5])
         );
 
         assert_eq!(
-            PREGEN.attacks.knight[Square::E4],
+            pregen::ATTACKS.knight[Square::E4],
             Bitboard::squares(&[Square::C3, Square::G3, Square::C5, Square::G5,
                                 Square::D2, Square::F2, Square::D6, Square::F6])
-        )
+        );
+
+        // the compile time tables against stepping at runtime and the sliding backends
+        let attacks = &pregen::ATTACKS;
+        for sq in Square::all() {
+            let steps = |steps: [isize; 8]| steps.iter().filter_map(|s| sq.step(*s)).collect::<Bitboard>();
+            assert_eq!(attacks.knight[sq], steps([-17, -15, -10, -6, 6, 10, 15, 17]));
+            assert_eq!(attacks.king[sq], steps([-9, -8, -7, -1, 1, 7, 8, 9]));
+            for color in [Color::White, Color::Black] {
+                assert_eq!(attacks.pawn[color as usize][sq], Bitboard::square(sq).pawn_attacks(color));
+            }
+            assert_eq!(attacks.bishop[sq], pregen().bishop.attacks(sq, Bitboard(0)));
+            assert_eq!(attacks.rook[sq], pregen().rook.attacks(sq, Bitboard(0)));
+            assert_eq!(attacks.queen[sq], attacks.bishop[sq] | attacks.rook[sq]);
+        }
     }
 
     #[test]
@@ -93,4 +187,135 @@ mod tests {
         let b = ((Bitboard::FILE_A | Bitboard::FILE_H) & !Bitboard::file(s)) | ((Bitboard::RANK_1 | Bitboard::RANK_8) & !Bitboard::rank(s));
         println!("{:?}", b);
     }
+
+    #[test]
+    fn test_shift() {
+        let directions = [
+            Direction::Up, Direction::Right, Direction::Down, Direction::Left,
+            Direction::UpRight, Direction::UpLeft, Direction::DownRight, Direction::DownLeft,
+        ];
+        // shifting each square must agree with stepping it, including off the board
+        for sq in Square::all() {
+            for d in directions {
+                for amt in 0..9 {
+                    let mut expected = Bitboard::square(sq);
+                    let mut to = sq;
+                    for _ in 0..amt {
+                        match to.shift(d) {
+                            Some(next) => to = next,
+                            None => {
+                                expected = Bitboard(0);
+                                break;
+                            }
+                        }
+                        expected = Bitboard::square(to);
+                    }
+                    assert_eq!(Bitboard::square(sq).shift(d, amt), expected);
+                }
+            }
+        }
+        assert_eq!(Bitboard::FILE_H.shift(Direction::Right, 1), Bitboard(0));
+        assert_eq!(Bitboard::FILE_A.shift(Direction::UpLeft, 1), Bitboard(0));
+
+        let pawns = Bitboard::squares(&[Square::B2, Square::G5]);
+        assert_eq!(pawns.file_fill(), Bitboard::FILE_B | Bitboard::FILE_G);
+        assert_eq!(pawns.front_span(Color::White), Bitboard(Bitboard::FILE_B.0 & !0x1FFFF) | Bitboard(Bitboard::FILE_G.0 & !0xFF_FFFF_FFFF));
+        assert_eq!(pawns.front_span(Color::Black), Bitboard::squares(&[Square::B1, Square::G4, Square::G3, Square::G2, Square::G1]));
+        assert_eq!(
+            Bitboard::square(Square::H2).pawn_attack_span(Color::White),
+            Bitboard(Bitboard::FILE_G.0 & !0xFFFF),
+        );
+        assert_eq!(
+            Bitboard::square(Square::A1).king_ring(),
+            Bitboard::squares(&[Square::A1, Square::B1, Square::A2, Square::B2]),
+        );
+        for sq in Square::all() {
+            assert_eq!(Bitboard::square(sq).king_ring(), pregen::ATTACKS.king[sq] | Bitboard::square(sq));
+        }
+    }
+
+    #[test]
+    fn test_between_line() {
+        use crate::movegen::get_moves;
+
+        assert_eq!(Bitboard::between(Square::A1, Square::H8), Bitboard::squares(&[Square::B2, Square::C3, Square::D4, Square::E5, Square::F6, Square::G7]));
+        assert_eq!(Bitboard::between(Square::H8, Square::A1), Bitboard::between(Square::A1, Square::H8));
+        assert_eq!(Bitboard::between(Square::E1, Square::E2), Bitboard(0));
+        assert_eq!(Bitboard::between(Square::E1, Square::E1), Bitboard(0));
+        assert_eq!(Bitboard::line(Square::B1, Square::G1), Bitboard::RANK_1);
+        assert_eq!(Bitboard::line(Square::H1, Square::G2), Bitboard::squares(&[Square::H1, Square::G2, Square::F3, Square::E4, Square::D5, Square::C6, Square::B7, Square::A8]));
+
+        for a in Square::all() {
+            for b in Square::all() {
+                let line = Bitboard::line(a, b);
+                let between = Bitboard::between(a, b);
+                assert_eq!(line, Bitboard::line(b, a));
+                assert!((between & !line).empty());
+                if !line.empty() {
+                    // a slider on `a` reaches `b` exactly when nothing is in between
+                    let queen = Piece::Queen(Color::White);
+                    assert!(get_moves(queen, a, !line).contains(b));
+                    for blocker in between.iter() {
+                        assert!(!get_moves(queen, a, Bitboard::square(blocker)).contains(b));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_bitboard_api() {
+        for sq in Square::all() {
+            let b = Bitboard::square(sq);
+            assert_eq!(b.count(), 1);
+            assert!(b.contains(sq) && b.is_single() && !b.more_than_one());
+            assert_eq!((b.lsb(), b.msb()), (Some(sq), Some(sq)));
+
+            // each flip moves the square to its mirror
+            let n = sq as usize;
+            assert_eq!(b.flip_vertical(), Bitboard::square(Square::from_index(n ^ 56)));
+            assert_eq!(b.flip_horizontal(), Bitboard::square(Square::from_index(n ^ 7)));
+            assert_eq!(b.flip_diagonal(), Bitboard::square(Square::from_index((n % 8) * 8 + n / 8)));
+            assert_eq!(b.rotate_180(), Bitboard::square(Square::from_index(63 - n)));
+        }
+        let empty = Bitboard(0);
+        assert_eq!((empty.count(), empty.lsb(), empty.msb()), (0, None, None));
+        assert!(!empty.is_single() && !empty.more_than_one());
+
+        let mut rng = crate::util::PRNG::new(0x891750FBADEA5);
+        for _ in 0..1000 {
+            let b = Bitboard(rng.next() & rng.next());
+            let squares: Vec<Square> = b.iter().collect();
+            assert_eq!(b.count() as usize, squares.len());
+            assert_eq!(b.iter().len(), squares.len());
+            assert_eq!(b.lsb(), squares.first().copied());
+            assert_eq!(b.msb(), squares.last().copied());
+            assert_eq!(b.more_than_one(), squares.len() > 1);
+            assert_eq!(squares.iter().copied().collect::<Bitboard>(), b);
+
+            let mut popped = b;
+            for sq in &squares {
+                assert!(b.contains(*sq));
+                assert_eq!(popped.pop_lsb(), Some(*sq));
+            }
+            assert_eq!(popped.pop_lsb(), None);
+
+            for flip in [Bitboard::flip_vertical, Bitboard::flip_horizontal, Bitboard::flip_diagonal, Bitboard::rotate_180] {
+                assert_eq!(flip(&flip(&b)), b);
+                assert_eq!(flip(&b).count(), b.count());
+            }
+            assert_eq!(b.flip_vertical().flip_horizontal(), b.rotate_180());
+        }
+
+        // every subset exactly once, the empty set included
+        let set = Bitboard::squares(&[Square::A1, Square::E4, Square::H8, Square::C6, Square::B2]);
+        let subsets: Vec<Bitboard> = set.subsets().collect();
+        assert_eq!(subsets.len(), 1 << set.count());
+        assert!(subsets.iter().all(|s| (*s & !set).empty()));
+        let mut sorted: Vec<u64> = subsets.iter().map(|s| s.0).collect();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), subsets.len());
+        assert_eq!(Bitboard(0).subsets().collect::<Vec<_>>(), vec![Bitboard(0)]);
+    }
 }
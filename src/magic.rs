@@ -31,7 +31,18 @@
 //! - [analog-hors](https://analog-hors.github.io/site/magic-bitboards/)
 //! - [Stockfish implementation](https://github.com/official-stockfish/Stockfish/blob/e699fee513ce26b3794ac43d08826c89106e10ea/src/bitboard.cpp#L142)
 //! 
+//! ### Precomputed magics
+//! Searching for magics takes a while, so the ones found by [`generate_magics`] are checked in
+//! as constants in [`constants`], and [`load_magics`] only has to fill in the attack tables.
+//! They can be regenerated with `cargo run --release -- magics > src/magic/constants.rs`.
+//! A denser variant, black magics, lives in [`black`].
 
+pub mod constants;
+pub mod black;
+pub mod black_constants;
+
+use std::fmt::Write;
+use std::time::{Duration, Instant};
 
 use crate::types::*;
 use crate::util::PRNG;
@@ -43,19 +54,157 @@ pub fn magic_index(magic: u64, mask: Bitboard, shift: u8, occupied: Bitboard) ->
     (hash >> shift) as usize
 }
 
+/// Options for [`search_magics`].
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    /// Seed of the RNG used for the squares of each rank. The RNG restarts for every square.
+    pub seeds: [u64; 8],
+    /// Give up on a square after trying this many magics.
+    pub max_attempts: Option<u64>,
+    /// Look for magics using this many index bits less than the mask has, which halves the
+    /// table of a square for each bit. When none is found within `max_attempts` (or
+    /// [`REDUCED_ATTEMPTS`] without it), one more bit is tried, and so on. At least one index
+    /// bit is always kept.
+    pub reduce: u8,
+}
+
+/// Magics tried with fewer index bits than the mask has when [`SearchOptions::max_attempts`]
+/// isn't set, since there may not be any magic that small.
+pub const REDUCED_ATTEMPTS: u64 = 100_000;
+
+/// How the search for a single square went.
+#[derive(Debug, Clone)]
+pub struct SquareReport {
+    pub square: Square,
+    /// Index bits of the magic found, so its table has `2^bits` entries.
+    pub bits: u8,
+    /// Magics tried, over every number of bits tried.
+    pub attempts: u64,
+    pub time: Duration,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            // RNG seeds shamelessly stolen from Stockfish
+            seeds: RNG_SEEDS.try_into().unwrap(),
+            max_attempts: None,
+            reduce: 0,
+        }
+    }
+}
+
 pub fn generate_magics(piece: &MagicPiece) -> MagicBitboard {
+    search_magics(piece, &SearchOptions::default())
+        .expect("The search can't fail without an attempt limit")
+        .0
+}
+
+/// Search for magics for every square, reporting how long each one took.
+pub fn search_magics(piece: &MagicPiece, options: &SearchOptions) -> Result<(MagicBitboard, Vec<SquareReport>), String> {
     let mut super_table: Vec<Bitboard> = vec![];
     let mut magics: Vec<SuperMagic> = vec![];
-    let mut offset = 0;
+    let mut reports = vec![];
     for sq in Square::all() {
-        // RNG seeds shamelessly stolen from Stockfish
-        let (magic, table) = find_magic(piece, sq, &mut PRNG::new(RNG_SEEDS[sq.rank() as usize]));
-        magics.push(SuperMagic::new(magic.mask, magic.magic, magic.shift, offset));
-        offset += table.len();
+        let start = Instant::now();
+        let mut rng = PRNG::new(options.seeds[sq.rank() as usize]);
+        let (found, attempts) = find_square_magic(piece, sq, options, None, &mut rng);
+        let (magic, table, bits) = found.ok_or(format!("No magic found for {} within {} attempts", sq, attempts))?;
+        magics.push(SuperMagic::new(magic.mask, magic.magic, magic.shift, super_table.len()));
         super_table.extend(table);
+        reports.push(SquareReport {
+            square: sq,
+            bits,
+            attempts,
+            time: start.elapsed(),
+        });
+    }
+    Ok((MagicBitboard::new(magics.try_into().unwrap(), super_table), reports))
+}
+
+/// Search for the magic of `sq` with as few index bits as `options` allow, giving up after
+/// `limit` attempts in total. Returns the magic, its table and its index bits, if found, and
+/// the number of magics tried.
+fn find_square_magic(
+    piece: &MagicPiece,
+    sq: Square,
+    options: &SearchOptions,
+    limit: Option<u64>,
+    rng: &mut PRNG,
+) -> (Option<(Magic, Vec<Bitboard>, u8)>, u64) {
+    let mask_bits = (sliding_attack(piece.ptype(), sq, Bitboard(0)) & !Bitboard::edges(sq)).count() as u8;
+    let mut attempts = 0;
+    for bits in mask_bits.saturating_sub(options.reduce).max(1)..=mask_bits {
+        let left = limit.map(|l| l.saturating_sub(attempts));
+        let per_width = options.max_attempts.or((bits < mask_bits).then_some(REDUCED_ATTEMPTS));
+        let max_attempts = match (per_width, left) {
+            (Some(max), Some(left)) => Some(max.min(left)),
+            (max, left) => max.or(left),
+        };
+        match try_find_magic(piece, sq, bits, max_attempts, rng) {
+            Some((magic, table, tried)) => return (Some((magic, table, bits)), attempts + tried),
+            None => attempts += max_attempts.unwrap_or(0),
+        }
+        if limit.is_some_and(|l| attempts >= l) {
+            break;
+        }
     }
-    assert_eq!(magics.len(), 64);
-    MagicBitboard::new(magics.try_into().unwrap(), super_table)
+    (None, attempts)
+}
+
+/// Total magics tried to find every square of `rank` (0 to 7) for both pieces, starting from
+/// `seed`, as a measure of how good a seed is for a search with `options`. Returns `None` when
+/// it goes over `limit`, or a square isn't found within `options.max_attempts`.
+pub fn seed_cost(rank: usize, seed: u64, options: &SearchOptions, limit: Option<u64>) -> Option<u64> {
+    let mut total = 0;
+    for piece in [MagicPiece::Bishop, MagicPiece::Rook] {
+        for file in File::all() {
+            let sq = Square::new(file, Rank::ALL[rank]);
+            let limit = limit.map(|l| l.saturating_sub(total));
+            let (found, attempts) = find_square_magic(&piece, sq, options, limit, &mut PRNG::new(seed));
+            found?;
+            total += attempts;
+        }
+    }
+    Some(total)
+}
+
+/// Build the attack table for already known magics, as found by [`generate_magics`].
+/// No searching is done: every blocker subset is simply stored at its index.
+pub fn load_magics(piece: &MagicPiece, magics: &MagicTable, table_size: usize) -> MagicBitboard {
+    let mut table = vec![Bitboard(0); table_size];
+    for sq in Square::all() {
+        let magic = &magics[sq];
+        for subset in magic.mask.subsets() {
+            table[magic.index(subset)] = sliding_attack(piece.ptype(), sq, subset);
+        }
+    }
+    MagicBitboard::new(*magics, table)
+}
+
+/// Write the magics as Rust source, in the format of [`constants`]. The header names the
+/// `options` of the `tejuino magics` command that generated them, if any.
+pub fn magics_source(bishop: &MagicBitboard, rook: &MagicBitboard, options: &str) -> String {
+    let command = format!("magics {}", options);
+    let command = command.trim_end();
+    let mut out = String::new();
+    writeln!(out, "//! Precomputed magics, generated by `tejuino {}`. Don't edit by hand, regenerate", command).unwrap();
+    writeln!(out, "//! with `cargo run --release -- {} > src/magic/constants.rs` instead.\n", command).unwrap();
+    out += "use crate::types::*;\n";
+    for (name, magics) in [("BISHOP", bishop), ("ROOK", rook)] {
+        out += "\n";
+        writeln!(out, "pub const {}_TABLE_SIZE: usize = {};", name, magics.table_size()).unwrap();
+        writeln!(out, "\n#[rustfmt::skip]\npub const {}_MAGICS: MagicTable = [", name).unwrap();
+        for m in magics.magics() {
+            writeln!(
+                out,
+                "    SuperMagic::new(Bitboard(0x{:016X}), 0x{:016X}, {}, {}),",
+                m.mask.0, m.magic, m.shift, m.offset
+            ).unwrap();
+        }
+        out += "];\n";
+    }
+    out
 }
 
 // Magic bitboards implementation
@@ -69,38 +218,48 @@ pub fn find_magic(
     square: Square,
     rng: &mut PRNG,
 ) -> (Magic, Vec<Bitboard>) {
+    let mask = sliding_attack(piece.ptype(), square, Bitboard(0)) & !Bitboard::edges(square);
+    let (magic, table, _) = try_find_magic(piece, square, mask.count() as u8, None, rng)
+        .expect("The search can't fail without an attempt limit");
+    (magic, table)
+}
+
+/// Search for a magic for `square` with a table of `2^index_bits` entries, giving up after
+/// `max_attempts` tries. Returns the magic, its table and the number of magics tried.
+pub fn try_find_magic(
+    piece: &MagicPiece,
+    square: Square,
+    index_bits: u8,
+    max_attempts: Option<u64>,
+    rng: &mut PRNG,
+) -> Option<(Magic, Vec<Bitboard>, u64)> {
     let blockers = sliding_attack(piece.ptype(), square, Bitboard(0));
     let mask = blockers & !Bitboard::edges(square);
-    let index_bits = mask.0.count_ones();
+    let mask_bits = mask.count();
     let mut table = vec![Bitboard(0); 1 << index_bits];
     let mut m = Magic {
         mask,
         magic: 0,
-        shift: 64 - index_bits as u8,
+        shift: 64 - index_bits,
     };
 
     // Store every subset of blockers along with the attack squares, computed with sliding_attack.
-    let mut perms: Vec<(Bitboard, Bitboard)> = vec![(Bitboard(0), Bitboard(0)); 1 << index_bits];
-
-    let mut blockers_subset = Bitboard(0);
-    for i in 0..(1 << index_bits) {
-        let attack = sliding_attack(piece.ptype(), square, blockers_subset);
-        perms[i] = (blockers_subset, attack);
-
-        blockers_subset.0 = m.mask.0 & blockers_subset.0.wrapping_sub(m.mask.0);
-        if blockers_subset.empty() {
-            break;
-        }
-    }
+    let perms: Vec<(Bitboard, Bitboard)> = mask
+        .subsets()
+        .map(|subset| (subset, sliding_attack(piece.ptype(), square, subset)))
+        .collect();
     // sanity check
-    assert!(blockers_subset.empty());
+    assert_eq!(perms.len(), 1 << mask_bits);
 
     // Keep track of attempts
     let mut epoch = vec![0; 1 << index_bits];
-    let mut attempt_no = 0;
+    let mut attempt_no: u64 = 0;
 
     // With all the subsets and their attacks computed, we can brute force the magic
     loop {
+        if max_attempts.is_some_and(|max| attempt_no >= max) {
+            return None;
+        }
         // Magic calculation from stockfish (bitboard.cpp line 196)
         // generate magic until it has less than 6 ones in the most significant bits
         m.magic = 0;
@@ -111,8 +270,7 @@ pub fn find_magic(
         let mut success = true;
 
         attempt_no += 1;
-        for i in 0..(1 << index_bits) {
-            let (subset, attack) = perms[i];
+        for &(subset, attack) in perms.iter() {
             let idx = m.index(subset);
             // Previous attempt is stored, can be safely ignored
             if epoch[idx] < attempt_no {
@@ -129,7 +287,7 @@ pub fn find_magic(
             break;
         }
     }
-    (m, table)
+    Some((m, table, attempt_no))
 }
 
 #[cfg(test)]
@@ -153,4 +311,65 @@ mod tests {
         assert_eq!(table[m.index(Bitboard(0))], magic[Bitboard(0)]);
         assert_eq!(table[m.index(blockers)], magic[blockers]);
     }
+
+    #[test]
+    fn test_search_magics() {
+        let (bishop, reports) = search_magics(&MagicPiece::Bishop, &SearchOptions::default()).unwrap();
+        assert_eq!(bishop.magics(), generate_magics(&MagicPiece::Bishop).magics());
+        assert_eq!(reports.len(), 64);
+        assert_eq!(reports.iter().map(|r| 1 << r.bits).sum::<usize>(), bishop.table_size());
+
+        // the seed of a rank is used for both pieces
+        let (_, rook_reports) = search_magics(&MagicPiece::Rook, &SearchOptions::default()).unwrap();
+        let first_rank: u64 = reports.iter().chain(&rook_reports).filter(|r| r.square.rank() == Rank::Rank1).map(|r| r.attempts).sum();
+        assert_eq!(seed_cost(0, RNG_SEEDS[0], &SearchOptions::default(), None), Some(first_rank));
+        assert_eq!(seed_cost(0, RNG_SEEDS[0], &SearchOptions::default(), Some(first_rank - 1)), None);
+
+        let limited = SearchOptions { max_attempts: Some(1), ..Default::default() };
+        assert!(search_magics(&MagicPiece::Bishop, &limited).is_err());
+        assert_eq!(seed_cost(0, RNG_SEEDS[0], &limited, None), None);
+
+        // seeds are scored for the search they're used in: the corner rooks need more than
+        // 50000 attempts at full width once 11 bits have been given up on
+        let reduced = SearchOptions { max_attempts: Some(50_000), reduce: 1, ..Default::default() };
+        assert_eq!(seed_cost(0, RNG_SEEDS[0], &reduced, None), None);
+        let (found, attempts) = find_square_magic(&MagicPiece::Rook, Square::B1, &reduced, None, &mut PRNG::new(RNG_SEEDS[0]));
+        assert_eq!((found.unwrap().2, attempts > 50_000), (11, true));
+
+        // reductions past the mask width keep one index bit, and without an attempt limit the
+        // reduced widths are still given up on
+        let too_far = SearchOptions { max_attempts: Some(5), reduce: 20, ..Default::default() };
+        assert!(search_magics(&MagicPiece::Bishop, &too_far).is_err());
+        let uncapped = SearchOptions { reduce: 1, ..Default::default() };
+        let (found, attempts) = find_square_magic(&MagicPiece::Rook, Square::A1, &uncapped, None, &mut PRNG::new(RNG_SEEDS[0]));
+        assert_eq!((found.unwrap().2, attempts > REDUCED_ATTEMPTS), (12, true));
+
+        // a rook in the corner has 12 blocker squares, 11 bits is too few for a quick search
+        assert!(try_find_magic(&MagicPiece::Rook, Square::A1, 11, Some(100), &mut PRNG::new(RNG_SEEDS[0])).is_none());
+    }
+
+    #[test]
+    fn test_precomputed_magics() {
+        use crate::magic::constants::*;
+
+        // the checked in magics should be exactly what the search finds
+        let bishop = generate_magics(&MagicPiece::Bishop);
+        let rook = generate_magics(&MagicPiece::Rook);
+        assert_eq!(bishop.magics(), &BISHOP_MAGICS);
+        assert_eq!(rook.magics(), &ROOK_MAGICS);
+        assert_eq!(std::fs::read_to_string("src/magic/constants.rs").unwrap(), magics_source(&bishop, &rook, ""));
+        assert!(magics_source(&bishop, &rook, "--seed 7 --reduce 1").contains("cargo run --release -- magics --seed 7 --reduce 1 > "));
+
+        for (piece, magics, size) in [
+            (MagicPiece::Bishop, &BISHOP_MAGICS, BISHOP_TABLE_SIZE),
+            (MagicPiece::Rook, &ROOK_MAGICS, ROOK_TABLE_SIZE),
+        ] {
+            let loaded = load_magics(&piece, magics, size);
+            for sq in Square::all() {
+                for subset in magics[sq].mask.subsets() {
+                    assert_eq!(loaded.get(sq)[subset], sliding_attack(piece.ptype(), sq, subset));
+                }
+            }
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,144 @@
+//! Precomputed black magics, generated by `tejuino magics --black --published`. Don't edit by hand,
+//! regenerate with `cargo run --release -- magics --black --published > src/magic/black_constants.rs` instead.
+
+use crate::types::*;
+
+pub const BLACK_BISHOP_TABLE_SIZE: usize = 4054;
+
+#[rustfmt::skip]
+pub const BLACK_BISHOP_MAGICS: [BlackMagic; 64] = [
+    BlackMagic::new(Bitboard(0xFFBFDFEFF7FBFDFF), 0xA7020080601803D8, 55, 785),
+    BlackMagic::new(Bitboard(0xFFFFBFDFEFF7FBFF), 0x13802040400801F1, 55, 3136),
+    BlackMagic::new(Bitboard(0xFFFFFFBFDFEFF5FF), 0x0A0080181001F60C, 55, 3370),
+    BlackMagic::new(Bitboard(0xFFFFFFFFBFDDEBFF), 0x1840802004238008, 55, 970),
+    BlackMagic::new(Bitboard(0xFFFFFFFFFDBBD7FF), 0xC03FE00100000000, 55, 2469),
+    BlackMagic::new(Bitboard(0xFFFFFFFDFBF7AFFF), 0x24C00BFFFF400000, 55, 2177),
+    BlackMagic::new(Bitboard(0xFFFFFDFBF7EFDFFF), 0x0808101F40007F04, 55, 3025),
+    BlackMagic::new(Bitboard(0xFFFDFBF7EFDFBFFF), 0x100808201EC00080, 55, 2732),
+    BlackMagic::new(Bitboard(0xFFDFEFF7FBFDFFFF), 0xFFA2FEFFBFEFB7FF, 55, 0),
+    BlackMagic::new(Bitboard(0xFFBFDFEFF7FBFFFF), 0x083E3EE040080801, 55, 3315),
+    BlackMagic::new(Bitboard(0xFFFFBFDFEFF5FFFF), 0xC0800080181001F8, 55, 3124),
+    BlackMagic::new(Bitboard(0xFFFFFFBFDDEBFFFF), 0x0440007FE0031000, 55, 2138),
+    BlackMagic::new(Bitboard(0xFFFFFFFDBBD7FFFF), 0x2010007FFC000000, 55, 3260),
+    BlackMagic::new(Bitboard(0xFFFFFDFBF7AFFFFF), 0x1079FFE000FF8000, 55, 2179),
+    BlackMagic::new(Bitboard(0xFFFDFBF7EFDFFFFF), 0x3C0708101F400080, 55, 3439),
+    BlackMagic::new(Bitboard(0xFFFBF7EFDFBFFFFF), 0x080614080FA00040, 55, 2763),
+    BlackMagic::new(Bitboard(0xFFEFF7FBFDFFFDFF), 0x7FFE7FFF817FCFF9, 55, 251),
+    BlackMagic::new(Bitboard(0xFFDFEFF7FBFFFBFF), 0x7FFEBFFFA01027FD, 55, 3241),
+    BlackMagic::new(Bitboard(0xFFBFDFEFF5FFF5FF), 0x53018080C00F4001, 55, 3097),
+    BlackMagic::new(Bitboard(0xFFFFBFDDEBFFEBFF), 0x407E0001000FFB8A, 55, 1192),
+    BlackMagic::new(Bitboard(0xFFFFFDBBD7FFD7FF), 0x201FE000FFF80010, 55, -237),
+    BlackMagic::new(Bitboard(0xFFFDFBF7AFFFAFFF), 0xFFDFEFFFDE39FFEF, 55, 1737),
+    BlackMagic::new(Bitboard(0xFFFBF7EFDFFFDFFF), 0xCC8808000FBF8002, 55, 3458),
+    BlackMagic::new(Bitboard(0xFFF7EFDFBFFFBFFF), 0x7FF7FBFFF8203FFF, 55, 3291),
+    BlackMagic::new(Bitboard(0xFFF7FBFDFFFDFBFF), 0x8800013E8300C030, 55, 1988),
+    BlackMagic::new(Bitboard(0xFFEFF7FBFFFBF7FF), 0x0420009701806018, 55, 3445),
+    BlackMagic::new(Bitboard(0xFFDFEFF5FFF5EFFF), 0x7FFEFF7F7F01F7FD, 55, 3076),
+    BlackMagic::new(Bitboard(0xFFBFDDEBFFEBDDFF), 0x8700303010C0C006, 55, 1832),
+    BlackMagic::new(Bitboard(0xFFFDBBD7FFD7BBFF), 0xC800181810606000, 55, 255),
+    BlackMagic::new(Bitboard(0xFFFBF7AFFFAFF7FF), 0x20002038001C8010, 55, -374),
+    BlackMagic::new(Bitboard(0xFFF7EFDFFFDFEFFF), 0x087FF038000FC001, 55, 3531),
+    BlackMagic::new(Bitboard(0xFFEFDFBFFFBFDFFF), 0x00080C0C00083007, 55, -121),
+    BlackMagic::new(Bitboard(0xFFFBFDFFFDFBF7FF), 0x00000080FC82C040, 55, 3155),
+    BlackMagic::new(Bitboard(0xFFF7FBFFFBF7EFFF), 0x000000407E416020, 55, 3496),
+    BlackMagic::new(Bitboard(0xFFEFF5FFF5EFDFFF), 0x00600203F8008020, 55, 1018),
+    BlackMagic::new(Bitboard(0xFFDDEBFFEBDDBFFF), 0xD003FEFE04404080, 55, 695),
+    BlackMagic::new(Bitboard(0xFFBBD7FFD7BBFDFF), 0xA00020C018003088, 55, 2695),
+    BlackMagic::new(Bitboard(0xFFF7AFFFAFF7FBFF), 0x7FBFFE700BFFE800, 55, 1073),
+    BlackMagic::new(Bitboard(0xFFEFDFFFDFEFF7FF), 0x107FF00FE4000F90, 55, 1469),
+    BlackMagic::new(Bitboard(0xFFDFBFFFBFDFEFFF), 0x7F8FFFCFF1D007F8, 55, 3366),
+    BlackMagic::new(Bitboard(0xFFFDFFFDFBF7EFFF), 0x0000004100F88080, 55, 3242),
+    BlackMagic::new(Bitboard(0xFFFBFFFBF7EFDFFF), 0x00000020807C4040, 55, 490),
+    BlackMagic::new(Bitboard(0xFFF5FFF5EFDFBFFF), 0x00000041018700C0, 55, 825),
+    BlackMagic::new(Bitboard(0xFFEBFFEBDDBFFFFF), 0x0010000080FC4080, 55, 1241),
+    BlackMagic::new(Bitboard(0xFFD7FFD7BBFDFFFF), 0x1000003C80180030, 55, 1971),
+    BlackMagic::new(Bitboard(0xFFAFFFAFF7FBFDFF), 0xC10000DF80280050, 55, 2462),
+    BlackMagic::new(Bitboard(0xFFDFFFDFEFF7FBFF), 0xFFFFFFBFEFF80FDC, 55, 2749),
+    BlackMagic::new(Bitboard(0xFFBFFFBFDFEFF7FF), 0x000000101003F812, 55, 3542),
+    BlackMagic::new(Bitboard(0xFFFFFDFBF7EFDFFF), 0x0800001F40808200, 55, 3374),
+    BlackMagic::new(Bitboard(0xFFFFFBF7EFDFBFFF), 0x084000101F3FD208, 55, 3477),
+    BlackMagic::new(Bitboard(0xFFFFF5EFDFBFFFFF), 0x080000000F808081, 55, 140),
+    BlackMagic::new(Bitboard(0xFFFFEBDDBFFFFFFF), 0x0004000008003F80, 55, 3112),
+    BlackMagic::new(Bitboard(0xFFFFD7BBFDFFFFFF), 0x08000001001FE040, 55, 991),
+    BlackMagic::new(Bitboard(0xFFFFAFF7FBFDFFFF), 0x72DD000040900A00, 55, 3520),
+    BlackMagic::new(Bitboard(0xFFFFDFEFF7FBFDFF), 0xFFFFFEFFBFEFF81D, 55, 3767),
+    BlackMagic::new(Bitboard(0xFFFFBFDFEFF7FBFF), 0xCD8000200FEBF209, 55, 3000),
+    BlackMagic::new(Bitboard(0xFFFDFBF7EFDFBFFF), 0x100000101EC10082, 55, 1863),
+    BlackMagic::new(Bitboard(0xFFFBF7EFDFBFFFFF), 0x7FBAFFFFEFE0C02F, 55, 3703),
+    BlackMagic::new(Bitboard(0xFFF5EFDFBFFFFFFF), 0x7F83FFFFFFF07F7F, 55, 1828),
+    BlackMagic::new(Bitboard(0xFFEBDDBFFFFFFFFF), 0xFFF1FFFFFFF7FFC1, 55, 3720),
+    BlackMagic::new(Bitboard(0xFFD7BBFDFFFFFFFF), 0x0878040000FFE01F, 55, 1998),
+    BlackMagic::new(Bitboard(0xFFAFF7FBFDFFFFFF), 0x945E388000801012, 55, 2483),
+    BlackMagic::new(Bitboard(0xFFDFEFF7FBFDFFFF), 0x0840800080200FDA, 55, -238),
+    BlackMagic::new(Bitboard(0xFFBFDFEFF7FBFDFF), 0x100000C05F582008, 55, 3424),
+];
+
+pub const BLACK_ROOK_TABLE_SIZE: usize = 87758;
+
+#[rustfmt::skip]
+pub const BLACK_ROOK_MAGICS: [BlackMagic; 64] = [
+    BlackMagic::new(Bitboard(0xFFFEFEFEFEFEFE81), 0x80280013FF84FFFF, 52, 67767),
+    BlackMagic::new(Bitboard(0xFFFDFDFDFDFDFD83), 0x5FFBFEFDFEF67FFF, 52, 16662),
+    BlackMagic::new(Bitboard(0xFFFBFBFBFBFBFB85), 0xFFEFFAFFEFFDFFFF, 52, 1154),
+    BlackMagic::new(Bitboard(0xFFF7F7F7F7F7F789), 0x003000900300008A, 52, 12070),
+    BlackMagic::new(Bitboard(0xFFEFEFEFEFEFEF91), 0x0030018003500030, 52, 37405),
+    BlackMagic::new(Bitboard(0xFFDFDFDFDFDFDFA1), 0x0020012120A00020, 52, -2363),
+    BlackMagic::new(Bitboard(0xFFBFBFBFBFBFBFC1), 0x0030006000C00030, 52, 55373),
+    BlackMagic::new(Bitboard(0xFF7F7F7F7F7F7F81), 0xFFA8008DFF09FFF8, 52, 64073),
+    BlackMagic::new(Bitboard(0xFFFEFEFEFEFE81FF), 0x7FBFF7FBFBEAFFFC, 52, 67795),
+    BlackMagic::new(Bitboard(0xFFFDFDFDFDFD83FF), 0x0000140081050002, 52, 71851),
+    BlackMagic::new(Bitboard(0xFFFBFBFBFBFB85FF), 0x0000180043800048, 52, 69672),
+    BlackMagic::new(Bitboard(0xFFF7F7F7F7F789FF), 0x7FFFE800021FFFB8, 52, 78458),
+    BlackMagic::new(Bitboard(0xFFEFEFEFEFEF91FF), 0xFFFFCFFE7FCFFFAF, 52, 12454),
+    BlackMagic::new(Bitboard(0xFFDFDFDFDFDFA1FF), 0x00001800C0180060, 52, 46735),
+    BlackMagic::new(Bitboard(0xFFBFBFBFBFBFC1FF), 0x4F8018005FD00018, 52, 52931),
+    BlackMagic::new(Bitboard(0xFF7F7F7F7F7F81FF), 0x0000180030620018, 52, 80621),
+    BlackMagic::new(Bitboard(0xFFFEFEFEFE81FEFF), 0x00300018010C0003, 52, 6840),
+    BlackMagic::new(Bitboard(0xFFFDFDFDFD83FDFF), 0x0003000C0085FFFF, 52, 70849),
+    BlackMagic::new(Bitboard(0xFFFBFBFBFB85FBFF), 0xFFFDFFF7FBFEFFF7, 52, 56394),
+    BlackMagic::new(Bitboard(0xFFF7F7F7F789F7FF), 0x7FC1FFDFFC001FFF, 52, 6579),
+    BlackMagic::new(Bitboard(0xFFEFEFEFEF91EFFF), 0xFFFEFFDFFDFFDFFF, 52, 41498),
+    BlackMagic::new(Bitboard(0xFFDFDFDFDFA1DFFF), 0x7C108007BEFFF81F, 52, 50364),
+    BlackMagic::new(Bitboard(0xFFBFBFBFBFC1BFFF), 0x20408007BFE00810, 52, 83793),
+    BlackMagic::new(Bitboard(0xFF7F7F7F7F817FFF), 0x0400800558604100, 52, 19773),
+    BlackMagic::new(Bitboard(0xFFFEFEFE81FEFEFF), 0x0040200010080008, 52, 79070),
+    BlackMagic::new(Bitboard(0xFFFDFDFD83FDFDFF), 0x0010020008040004, 52, 82127),
+    BlackMagic::new(Bitboard(0xFFFBFBFB85FBFBFF), 0xFFFDFEFFF7FBFFF7, 52, 14115),
+    BlackMagic::new(Bitboard(0xFFF7F7F789F7F7FF), 0xFEBF7DFFF8FEFFF9, 52, 59446),
+    BlackMagic::new(Bitboard(0xFFEFEFEF91EFEFFF), 0xC00000FFE001FFE0, 52, 34522),
+    BlackMagic::new(Bitboard(0xFFDFDFDFA1DFDFFF), 0x4AF01F00078007C3, 52, 13388),
+    BlackMagic::new(Bitboard(0xFFBFBFBFC1BFBFFF), 0xBFFBFAFFFB683F7F, 52, 20534),
+    BlackMagic::new(Bitboard(0xFF7F7F7F817F7FFF), 0x0807F67FFA102040, 52, 51089),
+    BlackMagic::new(Bitboard(0xFFFEFE81FEFEFEFF), 0x200008E800300030, 52, 8627),
+    BlackMagic::new(Bitboard(0xFFFDFD83FDFDFDFF), 0x0000008780180018, 52, 58222),
+    BlackMagic::new(Bitboard(0xFFFBFB85FBFBFBFF), 0x0000010300180018, 52, 53834),
+    BlackMagic::new(Bitboard(0xFFF7F789F7F7F7FF), 0x4000008180180018, 52, -894),
+    BlackMagic::new(Bitboard(0xFFEFEF91EFEFEFFF), 0x008080310005FFFA, 52, 82640),
+    BlackMagic::new(Bitboard(0xFFDFDFA1DFDFDFFF), 0x4000188100060006, 52, 17610),
+    BlackMagic::new(Bitboard(0xFFBFBFC1BFBFBFFF), 0xFFFFFF7FFFBFBFFF, 52, 47760),
+    BlackMagic::new(Bitboard(0xFF7F7F817F7F7FFF), 0x0000802000200040, 52, 3398),
+    BlackMagic::new(Bitboard(0xFFFE81FEFEFEFEFF), 0x20000202EC002800, 52, 74680),
+    BlackMagic::new(Bitboard(0xFFFD83FDFDFDFDFF), 0xFFFFF9FF7CFFF3FF, 52, 4662),
+    BlackMagic::new(Bitboard(0xFFFB85FBFBFBFBFF), 0x000000404B801800, 52, 58526),
+    BlackMagic::new(Bitboard(0xFFF789F7F7F7F7FF), 0x2000002FE03FD000, 52, 41916),
+    BlackMagic::new(Bitboard(0xFFEF91EFEFEFEFFF), 0xFFFFFF6FFE7FCFFD, 52, 7494),
+    BlackMagic::new(Bitboard(0xFFDFA1DFDFDFDFFF), 0xBFF7EFFFBFC00FFF, 52, 41590),
+    BlackMagic::new(Bitboard(0xFFBFC1BFBFBFBFFF), 0x000000100800A804, 52, 15138),
+    BlackMagic::new(Bitboard(0xFF7F817F7F7F7FFF), 0x6054000A58005805, 52, 61580),
+    BlackMagic::new(Bitboard(0xFF81FEFEFEFEFEFF), 0x0829000101150028, 52, 73293),
+    BlackMagic::new(Bitboard(0xFF83FDFDFDFDFDFF), 0x00000085008A0014, 52, 68798),
+    BlackMagic::new(Bitboard(0xFF85FBFBFBFBFBFF), 0x8000002B00408028, 52, 76840),
+    BlackMagic::new(Bitboard(0xFF89F7F7F7F7F7FF), 0x4000002040790028, 52, 70197),
+    BlackMagic::new(Bitboard(0xFF91EFEFEFEFEFFF), 0x7800002010288028, 52, 77016),
+    BlackMagic::new(Bitboard(0xFFA1DFDFDFDFDFFF), 0x0000001800E08018, 52, 581),
+    BlackMagic::new(Bitboard(0xFFC1BFBFBFBFBFFF), 0xA3A80003F3A40048, 52, 16587),
+    BlackMagic::new(Bitboard(0xFF817F7F7F7F7FFF), 0x2003D80000500028, 52, 43856),
+    BlackMagic::new(Bitboard(0x81FEFEFEFEFEFEFF), 0xFFFFF37EEFEFDFBE, 52, 35816),
+    BlackMagic::new(Bitboard(0x83FDFDFDFDFDFDFF), 0x40000280090013C1, 52, 26074),
+    BlackMagic::new(Bitboard(0x85FBFBFBFBFBFBFF), 0xBF7FFEFFBFFAF71F, 52, 27338),
+    BlackMagic::new(Bitboard(0x89F7F7F7F7F7F7FF), 0xFFFDFFFF777B7D6E, 52, 35798),
+    BlackMagic::new(Bitboard(0x91EFEFEFEFEFEFFF), 0x48300007E8080C02, 52, 42491),
+    BlackMagic::new(Bitboard(0xA1DFDFDFDFDFDFFF), 0xAFE0000FFF780402, 52, 48507),
+    BlackMagic::new(Bitboard(0xC1BFBFBFBFBFBFFF), 0xEE73FFFBFFBB77FE, 52, 23576),
+    BlackMagic::new(Bitboard(0x817F7F7F7F7F7FFF), 0x0002000308482882, 52, 30679),
+];
@@ -0,0 +1,144 @@
+//! Precomputed magics, generated by `tejuino magics`. Don't edit by hand, regenerate
+//! with `cargo run --release -- magics > src/magic/constants.rs` instead.
+
+use crate::types::*;
+
+pub const BISHOP_TABLE_SIZE: usize = 5248;
+
+#[rustfmt::skip]
+pub const BISHOP_MAGICS: MagicTable = [
+    SuperMagic::new(Bitboard(0x0040201008040200), 0x40106000A1160020, 58, 0),
+    SuperMagic::new(Bitboard(0x0000402010080400), 0x0020010250810120, 59, 64),
+    SuperMagic::new(Bitboard(0x0000004020100A00), 0x2010010220280081, 59, 96),
+    SuperMagic::new(Bitboard(0x0000000040221400), 0x002806004050C040, 59, 128),
+    SuperMagic::new(Bitboard(0x0000000002442800), 0x0002021018000000, 59, 160),
+    SuperMagic::new(Bitboard(0x0000000204085000), 0x2001112010000400, 59, 192),
+    SuperMagic::new(Bitboard(0x0000020408102000), 0x0881010120218080, 59, 224),
+    SuperMagic::new(Bitboard(0x0002040810204000), 0x1030820110010500, 58, 256),
+    SuperMagic::new(Bitboard(0x0020100804020000), 0x0000120222042400, 59, 320),
+    SuperMagic::new(Bitboard(0x0040201008040000), 0x2000020404040044, 59, 352),
+    SuperMagic::new(Bitboard(0x00004020100A0000), 0x8000480094208000, 59, 384),
+    SuperMagic::new(Bitboard(0x0000004022140000), 0x0003422A02000001, 59, 416),
+    SuperMagic::new(Bitboard(0x0000000244280000), 0x000A220210100040, 59, 448),
+    SuperMagic::new(Bitboard(0x0000020408500000), 0x8004820202226000, 59, 480),
+    SuperMagic::new(Bitboard(0x0002040810200000), 0x0018234854100800, 59, 512),
+    SuperMagic::new(Bitboard(0x0004081020400000), 0x0100004042101040, 59, 544),
+    SuperMagic::new(Bitboard(0x0010080402000200), 0x0004001004082820, 59, 576),
+    SuperMagic::new(Bitboard(0x0020100804000400), 0x0010000810010048, 59, 608),
+    SuperMagic::new(Bitboard(0x004020100A000A00), 0x1014004208081300, 57, 640),
+    SuperMagic::new(Bitboard(0x0000402214001400), 0x2080818802044202, 57, 768),
+    SuperMagic::new(Bitboard(0x0000024428002800), 0x0040880C00A00100, 57, 896),
+    SuperMagic::new(Bitboard(0x0002040850005000), 0x0080400200522010, 57, 1024),
+    SuperMagic::new(Bitboard(0x0004081020002000), 0x0001000188180B04, 59, 1152),
+    SuperMagic::new(Bitboard(0x0008102040004000), 0x0080249202020204, 59, 1184),
+    SuperMagic::new(Bitboard(0x0008040200020400), 0x1004400004100410, 59, 1216),
+    SuperMagic::new(Bitboard(0x0010080400040800), 0x00013100A0022206, 59, 1248),
+    SuperMagic::new(Bitboard(0x0020100A000A1000), 0x2148500001040080, 57, 1280),
+    SuperMagic::new(Bitboard(0x0040221400142200), 0x4241080011004300, 55, 1408),
+    SuperMagic::new(Bitboard(0x0002442800284400), 0x4020848004002000, 55, 1920),
+    SuperMagic::new(Bitboard(0x0004085000500800), 0x10101380D1004100, 57, 2432),
+    SuperMagic::new(Bitboard(0x0008102000201000), 0x0008004422020284, 59, 2560),
+    SuperMagic::new(Bitboard(0x0010204000402000), 0x01010A1041008080, 59, 2592),
+    SuperMagic::new(Bitboard(0x0004020002040800), 0x0808080400082121, 59, 2624),
+    SuperMagic::new(Bitboard(0x0008040004081000), 0x0808080400082121, 59, 2656),
+    SuperMagic::new(Bitboard(0x00100A000A102000), 0x0091128200100C00, 57, 2688),
+    SuperMagic::new(Bitboard(0x0022140014224000), 0x0202200802010104, 55, 2816),
+    SuperMagic::new(Bitboard(0x0044280028440200), 0x8C0A020200440085, 55, 3328),
+    SuperMagic::new(Bitboard(0x0008500050080400), 0x01A0008080B10040, 57, 3840),
+    SuperMagic::new(Bitboard(0x0010200020100800), 0x0889520080122800, 59, 3968),
+    SuperMagic::new(Bitboard(0x0020400040201000), 0x100902022202010A, 59, 4000),
+    SuperMagic::new(Bitboard(0x0002000204081000), 0x04081A0816002000, 59, 4032),
+    SuperMagic::new(Bitboard(0x0004000408102000), 0x0000681208005000, 59, 4064),
+    SuperMagic::new(Bitboard(0x000A000A10204000), 0x8170840041008802, 57, 4096),
+    SuperMagic::new(Bitboard(0x0014001422400000), 0x0A00004200810805, 57, 4224),
+    SuperMagic::new(Bitboard(0x0028002844020000), 0x0830404408210100, 57, 4352),
+    SuperMagic::new(Bitboard(0x0050005008040200), 0x2602208106006102, 57, 4480),
+    SuperMagic::new(Bitboard(0x0020002010080400), 0x1048300680802628, 59, 4608),
+    SuperMagic::new(Bitboard(0x0040004020100800), 0x2602208106006102, 59, 4640),
+    SuperMagic::new(Bitboard(0x0000020408102000), 0x0602010120110040, 59, 4672),
+    SuperMagic::new(Bitboard(0x0000040810204000), 0x0941010801043000, 59, 4704),
+    SuperMagic::new(Bitboard(0x00000A1020400000), 0x000040440A210428, 59, 4736),
+    SuperMagic::new(Bitboard(0x0000142240000000), 0x0008240020880021, 59, 4768),
+    SuperMagic::new(Bitboard(0x0000284402000000), 0x0400002012048200, 59, 4800),
+    SuperMagic::new(Bitboard(0x0000500804020000), 0x00AC102001210220, 59, 4832),
+    SuperMagic::new(Bitboard(0x0000201008040200), 0x0220021002009900, 59, 4864),
+    SuperMagic::new(Bitboard(0x0000402010080400), 0x84440C080A013080, 59, 4896),
+    SuperMagic::new(Bitboard(0x0002040810204000), 0x0001008044200440, 58, 4928),
+    SuperMagic::new(Bitboard(0x0004081020400000), 0x0004C04410841000, 59, 4992),
+    SuperMagic::new(Bitboard(0x000A102040000000), 0x2000500104011130, 59, 5024),
+    SuperMagic::new(Bitboard(0x0014224000000000), 0x1A0C010011C20229, 59, 5056),
+    SuperMagic::new(Bitboard(0x0028440200000000), 0x0044800112202200, 59, 5088),
+    SuperMagic::new(Bitboard(0x0050080402000000), 0x0434804908100424, 59, 5120),
+    SuperMagic::new(Bitboard(0x0020100804020000), 0x0300404822C08200, 59, 5152),
+    SuperMagic::new(Bitboard(0x0040201008040200), 0x48081010008A2A80, 58, 5184),
+];
+
+pub const ROOK_TABLE_SIZE: usize = 102400;
+
+#[rustfmt::skip]
+pub const ROOK_MAGICS: MagicTable = [
+    SuperMagic::new(Bitboard(0x000101010101017E), 0x0A80004000801220, 52, 0),
+    SuperMagic::new(Bitboard(0x000202020202027C), 0x8040004010002008, 53, 4096),
+    SuperMagic::new(Bitboard(0x000404040404047A), 0x2080200010008008, 53, 6144),
+    SuperMagic::new(Bitboard(0x0008080808080876), 0x1100100008210004, 53, 8192),
+    SuperMagic::new(Bitboard(0x001010101010106E), 0xC200209084020008, 53, 10240),
+    SuperMagic::new(Bitboard(0x002020202020205E), 0x2100010004000208, 53, 12288),
+    SuperMagic::new(Bitboard(0x004040404040403E), 0x0400081000822421, 53, 14336),
+    SuperMagic::new(Bitboard(0x008080808080807E), 0x0200010422048844, 52, 16384),
+    SuperMagic::new(Bitboard(0x0001010101017E00), 0x0800800080400024, 53, 20480),
+    SuperMagic::new(Bitboard(0x0002020202027C00), 0x0001402000401000, 54, 22528),
+    SuperMagic::new(Bitboard(0x0004040404047A00), 0x3000801000802001, 54, 23552),
+    SuperMagic::new(Bitboard(0x0008080808087600), 0x4400800800100083, 54, 24576),
+    SuperMagic::new(Bitboard(0x0010101010106E00), 0x0904802402480080, 54, 25600),
+    SuperMagic::new(Bitboard(0x0020202020205E00), 0x4040800400020080, 54, 26624),
+    SuperMagic::new(Bitboard(0x0040404040403E00), 0x0018808042000100, 54, 27648),
+    SuperMagic::new(Bitboard(0x0080808080807E00), 0x4040800080004100, 53, 28672),
+    SuperMagic::new(Bitboard(0x00010101017E0100), 0x0040048001458024, 53, 30720),
+    SuperMagic::new(Bitboard(0x00020202027C0200), 0x00A0004000205000, 54, 32768),
+    SuperMagic::new(Bitboard(0x00040404047A0400), 0x3100808010002000, 54, 33792),
+    SuperMagic::new(Bitboard(0x0008080808760800), 0x4825010010000820, 54, 34816),
+    SuperMagic::new(Bitboard(0x00101010106E1000), 0x5004808008000401, 54, 35840),
+    SuperMagic::new(Bitboard(0x00202020205E2000), 0x2024818004000A00, 54, 36864),
+    SuperMagic::new(Bitboard(0x00404040403E4000), 0x0005808002000100, 54, 37888),
+    SuperMagic::new(Bitboard(0x00808080807E8000), 0x2100060004806104, 53, 38912),
+    SuperMagic::new(Bitboard(0x000101017E010100), 0x0080400880008421, 53, 40960),
+    SuperMagic::new(Bitboard(0x000202027C020200), 0x4062220600410280, 54, 43008),
+    SuperMagic::new(Bitboard(0x000404047A040400), 0x010A004A00108022, 54, 44032),
+    SuperMagic::new(Bitboard(0x0008080876080800), 0x0000100080080080, 54, 45056),
+    SuperMagic::new(Bitboard(0x001010106E101000), 0x0021000500080010, 54, 46080),
+    SuperMagic::new(Bitboard(0x002020205E202000), 0x0044000202001008, 54, 47104),
+    SuperMagic::new(Bitboard(0x004040403E404000), 0x0000100400080102, 54, 48128),
+    SuperMagic::new(Bitboard(0x008080807E808000), 0xC020128200040545, 53, 49152),
+    SuperMagic::new(Bitboard(0x0001017E01010100), 0x0080002000400040, 53, 51200),
+    SuperMagic::new(Bitboard(0x0002027C02020200), 0x0000804000802004, 54, 53248),
+    SuperMagic::new(Bitboard(0x0004047A04040400), 0x0000120022004080, 54, 54272),
+    SuperMagic::new(Bitboard(0x0008087608080800), 0x010A386103001001, 54, 55296),
+    SuperMagic::new(Bitboard(0x0010106E10101000), 0x9010080080800400, 54, 56320),
+    SuperMagic::new(Bitboard(0x0020205E20202000), 0x8440020080800400, 54, 57344),
+    SuperMagic::new(Bitboard(0x0040403E40404000), 0x0004228824001001, 54, 58368),
+    SuperMagic::new(Bitboard(0x0080807E80808000), 0x000000490A000084, 53, 59392),
+    SuperMagic::new(Bitboard(0x00017E0101010100), 0x0080002000504000, 53, 61440),
+    SuperMagic::new(Bitboard(0x00027C0202020200), 0x200020005000C000, 54, 63488),
+    SuperMagic::new(Bitboard(0x00047A0404040400), 0x0012088020420010, 54, 64512),
+    SuperMagic::new(Bitboard(0x0008760808080800), 0x0010010080080800, 54, 65536),
+    SuperMagic::new(Bitboard(0x00106E1010101000), 0x0085001008010004, 54, 66560),
+    SuperMagic::new(Bitboard(0x00205E2020202000), 0x0002000204008080, 54, 67584),
+    SuperMagic::new(Bitboard(0x00403E4040404000), 0x0040413002040008, 54, 68608),
+    SuperMagic::new(Bitboard(0x00807E8080808000), 0x0000304081020004, 53, 69632),
+    SuperMagic::new(Bitboard(0x007E010101010100), 0x0080204000800080, 53, 71680),
+    SuperMagic::new(Bitboard(0x007C020202020200), 0x3008804000290100, 54, 73728),
+    SuperMagic::new(Bitboard(0x007A040404040400), 0x1010100080200080, 54, 74752),
+    SuperMagic::new(Bitboard(0x0076080808080800), 0x2008100208028080, 54, 75776),
+    SuperMagic::new(Bitboard(0x006E101010101000), 0x5000850800910100, 54, 76800),
+    SuperMagic::new(Bitboard(0x005E202020202000), 0x8402019004680200, 54, 77824),
+    SuperMagic::new(Bitboard(0x003E404040404000), 0x0120911028020400, 54, 78848),
+    SuperMagic::new(Bitboard(0x007E808080808000), 0x0000008044010200, 53, 79872),
+    SuperMagic::new(Bitboard(0x7E01010101010100), 0x0020850200244012, 52, 81920),
+    SuperMagic::new(Bitboard(0x7C02020202020200), 0x0020850200244012, 53, 86016),
+    SuperMagic::new(Bitboard(0x7A04040404040400), 0x0000102001040841, 53, 88064),
+    SuperMagic::new(Bitboard(0x7608080808080800), 0x140900040A100021, 53, 90112),
+    SuperMagic::new(Bitboard(0x6E10101010101000), 0x000200282410A102, 53, 92160),
+    SuperMagic::new(Bitboard(0x5E20202020202000), 0x000200282410A102, 53, 94208),
+    SuperMagic::new(Bitboard(0x3E40404040404000), 0x000200282410A102, 53, 96256),
+    SuperMagic::new(Bitboard(0x7E80808080808000), 0x4048240043802106, 52, 98304),
+];
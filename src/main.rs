@@ -0,0 +1,420 @@
+//! # Tejuino command line
+//!
+//! Without arguments, the engine speaks UCI on stdin and stdout, which is how GUIs and match
+//! runners start it. Tools built on top of the engine library are subcommands:
+//!
+//! - `epd <file> [--depth N] [--time MS]`: run an EPD test suite and report solved positions.
+//! - `book <out.bin> <games.pgn>... [options]`: build a Polyglot opening book from PGN files.
+//! - `bench [depth]`: search a fixed set of positions and print the total nodes, the signature
+//!   of the search, and the speed. Refactors and speedups must leave the node count unchanged.
+//! - `datagen <out.bin> [options]`: play self-play games at fixed nodes per move and write the
+//!   quiet positions, with their scores and the game results, as packed training records.
+//! - `tune <positions>... [options]`: Texel tune the evaluation weights on labeled positions
+//!   (text, a FEN and a result per line, or datagen `.bin` files), and write them as a weights
+//!   file or back into `src/eval.rs`.
+//! - `tunables [--json]`: list the tunable search parameters for OpenBench SPSA, or as a
+//!   weather-factory config.
+//! - `spsa [options]`: tune the search parameters with local SPSA mini-matches, printing the
+//!   values every iteration.
+//! - `magics [options]`: search for magics and print them as Rust source, to regenerate
+//!   `src/magic/constants.rs` (or `src/magic/black_constants.rs` with `--black`). Seeds, attempt
+//!   limits and reduced shifts can be set, and seeds explored. Reports go to stderr.
+
+use std::env;
+use std::fs;
+use std::io::BufReader;
+use std::process::exit;
+use std::time::{Duration, Instant};
+
+use tejuino::bench::{run_bench, BENCH_DEPTH, BENCH_FENS};
+use tejuino::book::Book;
+use tejuino::book::builder::{BookBuilder, BuildOptions};
+use tejuino::datagen::{generate, DataReader, DataWriter, DatagenOptions};
+use tejuino::epd::{run_suite, Epd};
+use tejuino::magic::{magics_source, search_magics, seed_cost, SearchOptions};
+use tejuino::magic::black::{black_magics_source, generate_black_magics, published_black_magics, verify};
+use tejuino::magic::constants::{BISHOP_TABLE_SIZE, ROOK_TABLE_SIZE};
+use tejuino::pgn::PgnReader;
+use tejuino::search::Limits;
+use tejuino::spsa::{spsa, to_params, SpsaOptions};
+use tejuino::tunables::{openbench_list, weather_factory_json, TUNABLES};
+use tejuino::tune::{fit_k, parse_labeled, parse_weights, replace_eval_source, tune, weights_text, TuneEntry, TuneOptions};
+use tejuino::types::{MagicPiece, RNG_SEEDS};
+use tejuino::util::PRNG;
+
+const USAGE: &str = "usage: tejuino [<command> [options]]
+
+without a command, run the UCI protocol on stdin and stdout
+
+commands:
+    epd <file> [--depth N] [--time MS]    run an EPD test suite
+    book <out.bin> <games.pgn>...         build a Polyglot book from PGN files
+        [--depth PLIES] [--min-games N] [--unweighted] [--color white|black]
+    bench [depth]                         search the bench positions and print nodes and nps
+    datagen <out.bin>                     play self-play games and write training records
+        [--games N] [--threads N] [--nodes N] [--random-plies N] [--book FILE] [--seed N]
+    tune <positions>...                   tune the evaluation on labeled positions (text or .bin)
+        [--epochs N] [--lr F] [--k F] [--init FILE] [--weights FILE] [--source src/eval.rs]
+    tunables [--json]                     list the search parameters (OpenBench or weather-factory)
+    spsa                                  tune the search parameters with SPSA mini-matches
+        [--iterations N] [--pairs N] [--nodes N] [--threads N] [--seed N]
+    magics                                print magics as Rust source (src/magic/constants.rs)
+        [--seed N | --seeds N,N,...] [--attempts N] [--reduce BITS] [--explore N] [--report]
+        [--black [--published | --candidates N] [--fixed-shift] [--orders N]]
+                                          black magics instead (src/magic/black_constants.rs)";
+
+/// Get the value following `flag`, e.g. `--depth 5`.
+fn flag<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|a| a == flag)
+        .and_then(|i| args.get(i + 1))
+        .map(|s| s.as_str())
+}
+
+fn parse_flag<T: std::str::FromStr>(args: &[String], name: &str) -> Result<Option<T>, String> {
+    flag(args, name)
+        .map(|v| v.parse().map_err(|_| format!("Invalid value for {}: {}", name, v)))
+        .transpose()
+}
+
+fn epd(args: &[String]) -> Result<(), String> {
+    let path = args.first().ok_or(USAGE)?;
+    let mut limits = Limits {
+        depth: parse_flag(args, "--depth")?,
+        time: parse_flag(args, "--time")?.map(Duration::from_millis),
+        nodes: None,
+    };
+    if limits.depth.is_none() && limits.time.is_none() {
+        limits.time = Some(Duration::from_secs(1));
+    }
+
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let positions = text
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(Epd::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let report = run_suite(&positions, &limits)?;
+    let solvable = positions.iter().filter(|e| e.op("bm").is_some() || e.op("am").is_some());
+    for (epd, result) in solvable.zip(&report.results) {
+        println!(
+            "{:<16} {:<8} {}",
+            result.id.as_deref().unwrap_or("?"),
+            result.found_san(epd),
+            if result.solved { "solved" } else { "failed" }
+        );
+    }
+    println!("solved {} / {}", report.solved, report.solved + report.failed);
+    Ok(())
+}
+
+fn book(args: &[String]) -> Result<(), String> {
+    let out = args.first().ok_or(USAGE)?;
+    let defaults = BuildOptions::default();
+    let color = flag(args, "--color");
+    if let Some(c) = color.filter(|c| !["white", "black"].contains(c)) {
+        return Err(format!("Invalid value for --color: {}", c));
+    }
+    let options = BuildOptions {
+        max_ply: parse_flag(args, "--depth")?.unwrap_or(defaults.max_ply),
+        min_games: parse_flag(args, "--min-games")?.unwrap_or(defaults.min_games),
+        result_weighted: !args.iter().any(|a| a == "--unweighted"),
+        white: color != Some("black"),
+        black: color != Some("white"),
+    };
+
+    // every argument that isn't a flag or a flag's value is an input file
+    let mut inputs = vec![];
+    let mut rest = args[1..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--depth" | "--min-games" | "--color" => {
+                rest.next();
+            }
+            "--unweighted" => {}
+            _ => inputs.push(arg),
+        }
+    }
+    if inputs.is_empty() {
+        return Err(USAGE.to_string());
+    }
+
+    let mut builder = BookBuilder::new(options);
+    let mut skipped = 0;
+    for path in inputs {
+        let file = fs::File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        for game in PgnReader::new(BufReader::new(file)) {
+            if let Err(e) = game.and_then(|g| builder.add_game(&g)) {
+                eprintln!("{}: skipping game: {}", path, e);
+                skipped += 1;
+            }
+        }
+    }
+
+    let book = builder.build();
+    fs::write(out, book.to_bytes()).map_err(|e| format!("{}: {}", out, e))?;
+    println!("{} games ({} skipped), {} entries written to {}", builder.games(), skipped, book.len(), out);
+    Ok(())
+}
+
+fn bench(args: &[String]) -> Result<(), String> {
+    let depth = match args.first() {
+        Some(depth) => depth.parse().map_err(|_| format!("Invalid depth: {}", depth))?,
+        None => BENCH_DEPTH,
+    };
+    let report = run_bench(depth);
+    for (i, (fen, nodes)) in BENCH_FENS.iter().zip(&report.nodes).enumerate() {
+        eprintln!("position {:>2} {:>10} nodes  {}", i + 1, nodes, fen);
+    }
+    eprintln!("depth {}, {} positions in {:.2?}", depth, BENCH_FENS.len(), report.time);
+    println!("{} nodes {} nps", report.total_nodes(), report.nps());
+    Ok(())
+}
+
+fn datagen(args: &[String]) -> Result<(), String> {
+    let path = args.first().ok_or(USAGE)?;
+    let defaults = DatagenOptions::default();
+    let options = DatagenOptions {
+        games: parse_flag(args, "--games")?.unwrap_or(defaults.games),
+        threads: parse_flag(args, "--threads")?.unwrap_or(defaults.threads),
+        nodes: parse_flag(args, "--nodes")?.unwrap_or(defaults.nodes),
+        random_plies: parse_flag(args, "--random-plies")?.unwrap_or(defaults.random_plies),
+        seed: parse_flag(args, "--seed")?.unwrap_or(defaults.seed),
+        ..defaults
+    };
+    let book = flag(args, "--book").map(Book::open).transpose()?;
+
+    let mut writer = DataWriter::create(path)?;
+    let start = Instant::now();
+    let stats = generate(&options, book.as_ref(), &mut writer, |stats| {
+        if stats.games % 10 == 0 || stats.games == options.games {
+            eprintln!(
+                "{:>6} games {:>9} positions  +{} ={} -{}  {:.0} positions/s",
+                stats.games,
+                stats.positions,
+                stats.white_wins,
+                stats.draws,
+                stats.black_wins,
+                stats.positions as f64 / start.elapsed().as_secs_f64().max(1e-9)
+            );
+        }
+    })?;
+    println!("{} positions from {} games written to {}", stats.positions, stats.games, path);
+    Ok(())
+}
+
+fn tune_eval(args: &[String]) -> Result<(), String> {
+    let mut inputs = vec![];
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--epochs" | "--lr" | "--k" | "--init" | "--weights" | "--source" => {
+                args_iter.next();
+            }
+            _ => inputs.push(arg),
+        }
+    }
+    if inputs.is_empty() {
+        return Err(USAGE.to_string());
+    }
+
+    let start = Instant::now();
+    let mut entries = vec![];
+    for path in inputs {
+        if path.ends_with(".bin") {
+            for record in DataReader::open(path)? {
+                let record = record?;
+                entries.push(TuneEntry::new(&record.board, record.result.score()));
+            }
+        } else {
+            let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+            for line in text.lines().filter(|l| !l.trim().is_empty() && !l.starts_with('#')) {
+                let (board, result) = parse_labeled(line)?;
+                entries.push(TuneEntry::new(&board, result));
+            }
+        }
+    }
+    eprintln!("{} positions loaded in {:.2?}", entries.len(), start.elapsed());
+
+    let initial = match flag(args, "--init") {
+        Some(path) => parse_weights(&fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?)?,
+        None => tejuino::eval::params(),
+    };
+    let mut params: Vec<f64> = initial.iter().map(|w| *w as f64).collect();
+    let k = match parse_flag(args, "--k")? {
+        Some(k) => k,
+        None => fit_k(&entries, &params),
+    };
+    eprintln!("K = {:.6}", k);
+
+    let options = TuneOptions {
+        epochs: parse_flag(args, "--epochs")?.unwrap_or(TuneOptions::default().epochs),
+        learning_rate: parse_flag(args, "--lr")?.unwrap_or(TuneOptions::default().learning_rate),
+        ..Default::default()
+    };
+    tune(&entries, &mut params, k, &options, |epoch, error| {
+        if epoch % 50 == 0 || epoch == 1 {
+            eprintln!("epoch {:>5}  error {:.8}  {:.2?}", epoch, error, start.elapsed());
+        }
+    });
+
+    let tuned: Vec<i32> = params.iter().map(|w| w.round() as i32).collect();
+    if let Some(path) = flag(args, "--source") {
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        fs::write(path, replace_eval_source(&source, &tuned)?).map_err(|e| format!("{}: {}", path, e))?;
+        eprintln!("weights written to {}", path);
+    }
+    match flag(args, "--weights") {
+        Some(path) => fs::write(path, weights_text(&tuned)).map_err(|e| format!("{}: {}", path, e))?,
+        None if flag(args, "--source").is_none() => print!("{}", weights_text(&tuned)),
+        None => {}
+    }
+    Ok(())
+}
+
+fn tunables(args: &[String]) -> Result<(), String> {
+    if args.iter().any(|a| a == "--json") {
+        print!("{}", weather_factory_json());
+    } else {
+        print!("{}", openbench_list());
+    }
+    Ok(())
+}
+
+fn spsa_tune(args: &[String]) -> Result<(), String> {
+    let defaults = SpsaOptions::default();
+    let options = SpsaOptions {
+        iterations: parse_flag(args, "--iterations")?.unwrap_or(defaults.iterations),
+        pairs: parse_flag(args, "--pairs")?.unwrap_or(defaults.pairs),
+        nodes: parse_flag(args, "--nodes")?.unwrap_or(defaults.nodes),
+        threads: parse_flag(args, "--threads")?.unwrap_or(defaults.threads),
+        seed: parse_flag(args, "--seed")?.unwrap_or(defaults.seed),
+        ..defaults
+    };
+    let start = Instant::now();
+    let values = spsa(&options, |k, score, values| {
+        let values: Vec<String> = TUNABLES.iter().zip(values).map(|(t, v)| format!("{}={:.2}", t.name, v)).collect();
+        eprintln!(
+            "iteration {:>5}  +{} -{} ={}  {:.2?}  {}",
+            k, score.wins, score.losses, score.draws, start.elapsed(), values.join(" ")
+        );
+    });
+    for (t, v) in TUNABLES.iter().zip(to_params(&values).values()) {
+        println!("setoption name {} value {}", t.name, v);
+    }
+    Ok(())
+}
+
+fn magics(args: &[String]) -> Result<(), String> {
+    if args.iter().any(|a| a == "--black") {
+        return black_magics(args);
+    }
+
+    let mut options = SearchOptions {
+        max_attempts: parse_flag(args, "--attempts")?,
+        reduce: parse_flag(args, "--reduce")?.unwrap_or(0),
+        ..Default::default()
+    };
+    let seed: Option<u64> = parse_flag(args, "--seed")?;
+    if let Some(seed) = seed {
+        options.seeds = [seed; 8];
+    }
+    if let Some(seeds) = flag(args, "--seeds") {
+        let seeds: Vec<u64> = seeds
+            .split(',')
+            .map(|s| s.trim().parse().map_err(|_| format!("Invalid seed: {}", s)))
+            .collect::<Result<_, _>>()?;
+        options.seeds = seeds.try_into().map_err(|_| "--seeds needs one seed per rank (8)".to_string())?;
+    }
+
+    // look for seeds that find the magics of a rank in fewer attempts
+    if let Some(tries) = parse_flag::<usize>(args, "--explore")? {
+        let mut rng = PRNG::new(seed.unwrap_or(RNG_SEEDS[0]));
+        for rank in 0..8 {
+            let mut current = options.seeds[rank];
+            let mut best = seed_cost(rank, current, &options, None)
+                .ok_or(format!("The seed of rank {} finds no magics within the attempts allowed", rank + 1))?;
+            let start = best;
+            for _ in 0..tries {
+                let candidate = rng.next() >> 48;
+                if let Some(cost) = seed_cost(rank, candidate, &options, Some(best)).filter(|c| *c < best) {
+                    (current, best) = (candidate, cost);
+                }
+            }
+            options.seeds[rank] = current;
+            eprintln!("rank {}: seed {} needs {} attempts ({} before)", rank + 1, current, best, start);
+        }
+        eprintln!("seeds: {:?}", options.seeds);
+    }
+
+    let mut results = vec![];
+    for piece in [MagicPiece::Bishop, MagicPiece::Rook] {
+        let start = Instant::now();
+        let (magics, reports) = search_magics(&piece, &options)?;
+        if args.iter().any(|a| a == "--report") {
+            for r in &reports {
+                eprintln!("{:?} {:<3} {:>2} bits {:>8} attempts {:>10.2?}", piece, r.square.algebraic(), r.bits, r.attempts, r.time);
+            }
+        }
+        eprintln!(
+            "{:?}: {} entries ({} KiB), {} attempts in {:.2?}",
+            piece,
+            magics.table_size(),
+            magics.table_size() * 8 / 1024,
+            reports.iter().map(|r| r.attempts).sum::<u64>(),
+            start.elapsed(),
+        );
+        results.push(magics);
+    }
+    print!("{}", magics_source(&results[0], &results[1], &args.join(" ")));
+    Ok(())
+}
+
+fn black_magics(args: &[String]) -> Result<(), String> {
+    let candidates = parse_flag(args, "--candidates")?.unwrap_or(20);
+    let orders = parse_flag(args, "--orders")?.unwrap_or(5000);
+    let fixed_shift = args.iter().any(|a| a == "--fixed-shift");
+    let published = args.iter().any(|a| a == "--published");
+    let mut rng = PRNG::new(RNG_SEEDS[0]);
+    let mut results = vec![];
+    for (piece, plain_size) in [(MagicPiece::Bishop, BISHOP_TABLE_SIZE), (MagicPiece::Rook, ROOK_TABLE_SIZE)] {
+        let black = if published {
+            published_black_magics(&piece, orders, &mut rng)?
+        } else {
+            generate_black_magics(&piece, fixed_shift, candidates, orders, &mut rng)
+        };
+        verify(&piece, black.magics(), black.table_size())?;
+        eprintln!(
+            "{:?}: {} entries ({} KiB), plain magics {} entries ({} KiB), all collisions constructive",
+            piece,
+            black.table_size(),
+            black.table_size() * 8 / 1024,
+            plain_size,
+            plain_size * 8 / 1024,
+        );
+        results.push(black);
+    }
+    print!("{}", black_magics_source(&results[0], &results[1], &args.join(" ")));
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(|s| s.as_str()) {
+        Some("epd") => epd(&args[1..]),
+        Some("book") => book(&args[1..]),
+        Some("bench") => bench(&args[1..]),
+        Some("datagen") => datagen(&args[1..]),
+        Some("tune") => tune_eval(&args[1..]),
+        Some("tunables") => tunables(&args[1..]),
+        Some("spsa") => spsa_tune(&args[1..]),
+        Some("magics") => magics(&args[1..]),
+        None => tejuino::uci::run(),
+        _ => Err(USAGE.to_string()),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        exit(1);
+    }
+}
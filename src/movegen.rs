@@ -1,20 +1,19 @@
 use crate::types::*;
-use crate::PREGEN;
-
-const ROOK_DIRECTIONS: &[Direction] = &[Direction::Up, Direction::Right, Direction::Down, Direction::Left];
-const BISHOP_DIRECTIONS: &[Direction] = &[Direction::UpRight, Direction::UpLeft, Direction::DownRight, Direction::DownLeft];
+use crate::pregen;
+use crate::pregen::ATTACKS;
+use crate::fill::{BISHOP_DIRECTIONS, ROOK_DIRECTIONS};
 
 pub fn sliding_attack(ptype: PieceType, sq: Square, occupied: Bitboard) -> Bitboard {
     let mut attacks: Bitboard = Bitboard(0);
     let directions = match ptype {
-        PieceType::Rook => ROOK_DIRECTIONS,
-        PieceType::Bishop => BISHOP_DIRECTIONS,
+        PieceType::Rook => &ROOK_DIRECTIONS,
+        PieceType::Bishop => &BISHOP_DIRECTIONS,
         _ => return Bitboard(0),
     };
     for direction in directions {
-        let mut to = sq;
-        while to.safe_step(*direction as isize) {
-            to += *direction as isize;
+        let mut from = sq;
+        while let Some(to) = from.shift(*direction) {
+            from = to;
             attacks |= Bitboard::square(to);
             if !(Bitboard::square(to) & occupied).empty() {
                 break;
@@ -27,44 +26,143 @@ pub fn sliding_attack(ptype: PieceType, sq: Square, occupied: Bitboard) -> Bitbo
 #[inline]
 pub fn get_moves(piece: Piece, sq: Square, occupied: Bitboard) -> Bitboard {
     match piece.ptype() {
-        PieceType::Knight => PREGEN.attacks.knight[sq],
-        PieceType::King => PREGEN.attacks.king[sq],
-        PieceType::Rook => PREGEN.rook_magics.get(sq)[occupied],
-        PieceType::Bishop => PREGEN.bishop_magics.get(sq)[occupied],
-        PieceType::Queen => PREGEN.rook_magics.get(sq)[occupied] | PREGEN.bishop_magics.get(sq)[occupied],
+        PieceType::Knight => ATTACKS.knight[sq],
+        PieceType::King => ATTACKS.king[sq],
+        PieceType::Rook => pregen().rook.attacks(sq, occupied),
+        PieceType::Bishop => pregen().bishop.attacks(sq, occupied),
+        PieceType::Queen => {
+            let sliders = pregen();
+            sliders.rook.attacks(sq, occupied) | sliders.bishop.attacks(sq, occupied)
+        }
         _ => Bitboard(0)
     }
 }
 
-pub fn get_pawn_moves(sq: Square, ctx: Board) -> Vec<Move> {
+const PROMOTIONS: &[PieceType] = &[PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight];
+
+fn push_pawn_move(moves: &mut Vec<Move>, from: Square, to: Square) {
+    if matches!(to.rank(), Rank::Rank1 | Rank::Rank8) {
+        for ptype in PROMOTIONS {
+            moves.push(Move::new(from, to, MoveType::Promotion(*ptype)));
+        }
+    } else {
+        moves.push(Move::new(from, to, MoveType::Quiet));
+    }
+}
+
+pub fn get_pawn_moves(sq: Square, ctx: &Board) -> Vec<Move> {
     let mut moves: Vec<Move> = vec![];
     let us = ctx.side_to_move;
     let them = !us;
-    let start_rank = match us {
-        Color::White => Bitboard::RANK_2,
-        Color::Black => Bitboard::RANK_7,
+    let forward = match us {
+        Color::White => Direction::Up,
+        Color::Black => Direction::Down,
     };
 
-    let double_pawns = ctx.by_piece(Piece::Pawn(us)) & start_rank;
-    let single_pawns = ctx.by_piece(Piece::Pawn(us)) & !start_rank;
+    if let Some(single) = sq.step(forward as isize) {
+        if ctx.at(single) == Piece::None {
+            push_pawn_move(&mut moves, sq, single);
+            if let Some(double) = single.shift(forward).filter(|_| sq.relative_rank(us) == Rank::Rank2) {
+                if ctx.at(double) == Piece::None {
+                    moves.push(Move::new(sq, double, MoveType::Quiet));
+                }
+            }
+        }
+    }
+
+    let attacks = ATTACKS.pawn[us as usize][sq];
+    for to in (attacks & ctx.by_color(them)).iter() {
+        push_pawn_move(&mut moves, sq, to);
+    }
+    if let Some(ep) = ctx.en_passant {
+        if attacks.contains(ep) {
+            moves.push(Move::new(sq, ep, MoveType::EnPassant));
+        }
+    }
 
-    let single_moves = single_pawns.shift(Direction::Up, 1) & !ctx.pieces();
-    let double_moves = single_pawns.shift(Direction::Up, 2) & !ctx.pieces();
+    moves
+}
 
+fn get_castling_moves(ctx: &Board) -> Vec<Move> {
+    let mut moves = vec![];
+    let us = ctx.side_to_move;
+    let king = ctx.king_square(us);
+    if ctx.is_attacked(king, !us) {
+        return moves;
+    }
+    for rights in [CastlingRights::kingside(us), CastlingRights::queenside(us)] {
+        if !ctx.castling.has(rights) {
+            continue;
+        }
+        let rook = ctx.castling_rooks[rights.index()];
+        if ctx.at(rook) != Piece::Rook(us) {
+            continue;
+        }
+        // In Chess960 the king and rook can start anywhere on the back rank, so every square
+        // either of them crosses must be empty (apart from themselves), and the king can't
+        // pass through an attacked square. Its destination is checked by the legality filter.
+        let (king_to, rook_to) = castling_squares(king, rook);
+        let occupied = ctx.pieces() & !Bitboard::squares(&[king, rook]);
+        let path = Bitboard::between(king, king_to) | Bitboard::between(rook, rook_to) | Bitboard::squares(&[king_to, rook_to]);
+        let attacked = Bitboard::between(king, king_to).iter().any(|sq| ctx.is_attacked(sq, !us));
+        if (path & occupied).empty() && !attacked {
+            moves.push(Move::new(king, rook, MoveType::Castle));
+        }
+    }
     moves
 }
 
+
+/// Generate all pseudolegal moves: moves that follow the piece movement rules,
+/// but might leave the king in check.
 pub fn pseudolegal_moves(board: &Board) -> Vec<Move> {
     let mut moves = vec![];
     for sq in board.colors[board.side_to_move as usize].iter() {
         let piece = board.at(sq);
+        if piece.ptype() == PieceType::Pawn {
+            moves.extend(get_pawn_moves(sq, board));
+            continue;
+        }
         let attacks = get_moves(piece, sq, board.pieces[PieceType::All as usize])
             & !board.colors[board.side_to_move as usize];
         for to in attacks.iter() {
             moves.push(Move::new(sq, to, MoveType::Quiet));
         }
     }
+    moves.extend(get_castling_moves(board));
+    moves
+}
+
+/// Generate all legal moves for the side to move.
+pub fn legal_moves(board: &Board) -> Vec<Move> {
+    let us = board.side_to_move;
+    pseudolegal_moves(board)
+        .into_iter()
+        .filter(|m| {
+            let mut after = board.clone();
+            after.make_move(*m);
+            !after.is_attacked(after.king_square(us), !us)
+        })
+        .collect()
+}
+
+/// Count the leaf nodes of the legal move tree up to `depth`. Used to validate move generation.
+pub fn perft(board: &Board, depth: usize) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = legal_moves(board);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
     moves
+        .into_iter()
+        .map(|m| {
+            let mut after = board.clone();
+            after.make_move(m);
+            perft(&after, depth - 1)
+        })
+        .sum()
 }
 
 #[cfg(test)]
@@ -80,4 +178,69 @@ mod tests {
             println!("{}", m);
         }
     }
+
+    #[test]
+    fn test_perft() {
+        let board = Board::from(crate::BOARD_START_FEN);
+        assert_eq!(perft(&board, 1), 20);
+        assert_eq!(perft(&board, 2), 400);
+        assert_eq!(perft(&board, 3), 8902);
+
+        // "kiwipete", exercises castling, en passant and promotions
+        let board = Board::from("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        assert_eq!(perft(&board, 1), 48);
+        assert_eq!(perft(&board, 2), 2039);
+        assert_eq!(perft(&board, 3), 97862);
+
+        let board = Board::from("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1");
+        assert_eq!(perft(&board, 4), 43238);
+    }
+
+    #[test]
+    fn test_chess960() {
+        assert_eq!(Board::chess960(518).fen(), crate::BOARD_START_FEN);
+        assert_eq!(Board::chess960(0).fen(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1");
+        let fens: std::collections::HashSet<String> = (0..960).map(|i| Board::chess960(i).fen()).collect();
+        assert_eq!(fens.len(), 960);
+        for index in 0..960 {
+            let board = Board::chess960(index);
+            let king = board.king_square(Color::White);
+            assert!(board.castling_rooks[1] < king && king < board.castling_rooks[0]);
+        }
+
+        // Shredder-FEN castling is written back as X-FEN, using file letters only for inner rooks
+        let board = Board::from("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9");
+        assert_eq!(board.fen(), "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9");
+        let inner = "4k3/8/8/8/8/8/8/R1R1K2R w KC - 0 1";
+        assert_eq!(Board::from(inner).fen(), inner);
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w K - 0 1").is_err());
+
+        for (fen, nodes) in [
+            ("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", [21, 528, 12189]),
+            ("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9", [21, 807, 18002]),
+            ("b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9", [20, 479, 10471]),
+            ("qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9", [22, 593, 13440]),
+        ] {
+            let board = Board::from(fen);
+            for (depth, nodes) in nodes.into_iter().enumerate() {
+                assert_eq!(perft(&board, depth + 1), nodes, "{} at depth {}", fen, depth + 1);
+            }
+        }
+
+        // castling is king-takes-rook internally, and only written that way under UCI_Chess960
+        let board = Board::from("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        let castle = board.parse_uci("e1g1", false).unwrap();
+        assert_eq!(castle, Move::new(Square::E1, Square::H1, MoveType::Castle));
+        assert_eq!(castle.uci(true), "e1h1");
+        assert_eq!(board.parse_uci("e1a1", true).unwrap().uci(false), "e1c1");
+        let mut after = board.clone();
+        after.make_move(castle);
+        assert_eq!(after.fen(), "r3k2r/8/8/8/8/8/8/R4RK1 b kq - 1 1");
+
+        // the king doesn't move, only the rook does
+        let board = Board::from("1r4k1/8/8/8/8/8/8/1R4KR w Hb - 0 1");
+        let mut after = board.clone();
+        after.make_move(board.parse_uci("g1h1", true).unwrap());
+        assert_eq!(after.fen(), "1r4k1/8/8/8/8/8/8/1R3RK1 b q - 1 1");
+    }
 }
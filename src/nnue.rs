@@ -0,0 +1,447 @@
+//! # NNUE Evaluation
+//!
+//! An efficiently updatable neural network: a `(768 -> N) x 2 -> 1` perspective network. Each
+//! side has its own accumulator, the hidden layer of `N` values, computed from the 768 piece
+//! features (color, piece type and square) as seen from that side, so that the board is always
+//! looked at from below. The output layer reads the side to move's accumulator followed by the
+//! other side's, through a clipped ReLU (`CReLU`) or a squared clipped ReLU (`SCReLU`).
+//!
+//! Moves only change a handful of features, so accumulators aren't recomputed at every node:
+//! the [`AccumulatorStack`] keeps one per ply, and each move copies its parent's and adds or
+//! subtracts the weights of the features it changes ([`Delta`]). Unmaking a move just drops
+//! back to the parent's.
+//!
+//! The inner loops run over contiguous `i16` slices, with SSE4.1 or AVX2 kernels when the CPU
+//! has them and plain loops the compiler can vectorise everywhere else, all giving the exact
+//! same results (see [`simd`]).
+//!
+//! ## Network file format
+//!
+//! Everything is little-endian, with no padding between the weight arrays:
+//!
+//! | Offset | Type               | Contents                                             |
+//! |--------|--------------------|------------------------------------------------------|
+//! | 0      | `[u8; 4]`          | Magic, `TJNN`                                        |
+//! | 4      | `u32`              | Version, `1`                                         |
+//! | 8      | `u32`              | Hidden size `N`                                      |
+//! | 12     | `u8`               | Activation: `0` for CReLU, `1` for SCReLU            |
+//! | 13     | `[u8; 3]`          | Reserved, zero                                       |
+//! | 16     | `[[i16; N]; 768]`  | Feature weights, the `N` weights of each feature     |
+//! |        | `[i16; N]`         | Feature biases                                       |
+//! |        | `[i16; 2N]`        | Output weights, side to move's half first            |
+//! |        | `i16`              | Output bias                                          |
+//!
+//! Feature `i` is `theirs * 384 + (piece type - 1) * 64 + square`, with `theirs` 0 for the pieces
+//! of the perspective's side and 1 for the other's, piece types from pawn (1) to king (6), and
+//! squares flipped vertically for black's perspective. Weights are quantized: the feature layer
+//! by [`QA`], the output weights by [`QB`] and the output bias by `QA * QB`. The output is
+//! scaled by [`SCALE`] into centipawns.
+//!
+//! ### Links
+//! - [NNUE](https://www.chessprogramming.org/NNUE)
+//! - [bullet](https://github.com/jw1912/bullet), a trainer for networks of this shape
+
+use std::fs;
+
+use crate::types::*;
+
+pub mod simd;
+
+pub use simd::Kernel;
+
+/// Quantization of the feature layer, and the upper bound of the activation.
+pub const QA: i32 = 255;
+/// Quantization of the output weights.
+pub const QB: i32 = 64;
+/// Output scale, from the network's units into centipawns.
+pub const SCALE: i32 = 400;
+
+const MAGIC: &[u8; 4] = b"TJNN";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 16;
+/// Number of input features: 2 colors, 6 piece types and 64 squares.
+pub const FEATURES: usize = 768;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activation {
+    /// `clamp(x, 0, QA)`
+    CReLU,
+    /// `clamp(x, 0, QA)^2`
+    SCReLU,
+}
+
+/// The weights of a network. See the module documentation for the layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Network {
+    pub hidden: usize,
+    pub activation: Activation,
+    /// `FEATURES * hidden` weights, the `hidden` weights of each feature one after the other.
+    pub feature_weights: Vec<i16>,
+    pub feature_bias: Vec<i16>,
+    /// `2 * hidden` weights, for the side to move's accumulator and then the other side's.
+    pub output_weights: Vec<i16>,
+    pub output_bias: i16,
+    /// The kernels running the inner loops, always supported by the CPU.
+    kernel: Kernel,
+}
+
+/// The hidden layer of the network, as seen by each side, indexed by [`Color`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Accumulator {
+    pub values: [Vec<i16>; 2],
+}
+
+/// The pieces a move takes off the board and puts on it, at most two of each (castling).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Delta {
+    pub removed: [Option<(Piece, Square)>; 2],
+    pub added: [Option<(Piece, Square)>; 2],
+}
+
+/// One accumulator per ply of the search, each one computed from its parent's.
+#[derive(Debug, Clone)]
+pub struct AccumulatorStack {
+    accumulators: Vec<Accumulator>,
+    ply: usize,
+}
+
+/// Index of the feature for `piece` on `sq`, as seen by `perspective`.
+#[inline]
+pub fn feature(perspective: Color, piece: Piece, sq: Square) -> usize {
+    let theirs = piece.color() != Some(perspective);
+    let sq = sq.relative(perspective);
+    theirs as usize * 384 + (piece.ptype() as usize - PieceType::Pawn as usize) * 64 + sq as usize
+}
+
+fn read_i16s(bytes: &[u8]) -> Vec<i16> {
+    bytes.chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]])).collect()
+}
+
+impl Network {
+    /// A network of the given size with every weight set to zero.
+    pub fn new(hidden: usize, activation: Activation) -> Network {
+        Network {
+            hidden,
+            activation,
+            feature_weights: vec![0; FEATURES * hidden],
+            feature_bias: vec![0; hidden],
+            output_weights: vec![0; 2 * hidden],
+            output_bias: 0,
+            kernel: Kernel::detect(),
+        }
+    }
+
+    /// Use the given kernels instead of the fastest ones available. Panics if the CPU doesn't
+    /// support them.
+    pub fn with_kernel(mut self, kernel: Kernel) -> Network {
+        assert!(kernel.available(), "The CPU doesn't support {:?}", kernel);
+        self.kernel = kernel;
+        self
+    }
+
+    pub fn kernel(&self) -> Kernel {
+        self.kernel
+    }
+
+    /// Read a network in the format described in the module documentation.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Network, String> {
+        if bytes.len() < HEADER_SIZE || &bytes[0..4] != MAGIC {
+            return Err("Not a network file".to_string());
+        }
+        let u32_at = |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let version = u32_at(4);
+        if version != VERSION {
+            return Err(format!("Unsupported network version: {}", version));
+        }
+        let hidden = u32_at(8) as usize;
+        let activation = match bytes[12] {
+            0 => Activation::CReLU,
+            1 => Activation::SCReLU,
+            other => return Err(format!("Invalid activation: {}", other)),
+        };
+
+        let sizes = [FEATURES * hidden, hidden, 2 * hidden, 1];
+        let expected = HEADER_SIZE + 2 * sizes.iter().sum::<usize>();
+        if hidden == 0 || bytes.len() != expected {
+            return Err(format!("Network file should be {} bytes for a hidden size of {}, found {}", expected, hidden, bytes.len()));
+        }
+        let mut rest = &bytes[HEADER_SIZE..];
+        let mut next = |size: usize| {
+            let (weights, tail) = rest.split_at(2 * size);
+            rest = tail;
+            read_i16s(weights)
+        };
+        Ok(Network {
+            hidden,
+            activation,
+            feature_weights: next(sizes[0]),
+            feature_bias: next(sizes[1]),
+            output_weights: next(sizes[2]),
+            output_bias: next(sizes[3])[0],
+            kernel: Kernel::detect(),
+        })
+    }
+
+    /// Load a network file, see [`Network::from_bytes`].
+    pub fn load(path: &str) -> Result<Network, String> {
+        let bytes = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        Network::from_bytes(&bytes).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Write the network in the format read by [`Network::from_bytes`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(VERSION.to_le_bytes());
+        bytes.extend((self.hidden as u32).to_le_bytes());
+        bytes.extend([self.activation as u8, 0, 0, 0]);
+        for weights in [&self.feature_weights, &self.feature_bias, &self.output_weights] {
+            bytes.extend(weights.iter().flat_map(|w| w.to_le_bytes()));
+        }
+        bytes.extend(self.output_bias.to_le_bytes());
+        bytes
+    }
+
+    /// The weights of feature `index`.
+    #[inline]
+    fn weights(&self, index: usize) -> &[i16] {
+        &self.feature_weights[index * self.hidden..(index + 1) * self.hidden]
+    }
+
+    /// Sum of the activated accumulator times its output weights.
+    #[inline]
+    fn output(&self, values: &[i16], weights: &[i16]) -> i64 {
+        match self.activation {
+            Activation::CReLU => self.kernel.crelu_dot(values, weights) as i64,
+            // in 64 bits, since QA^2 times a weight can already be close to the 32 bit limit
+            Activation::SCReLU => self.kernel.screlu_dot(values, weights),
+        }
+    }
+
+    /// Evaluate from an up to date accumulator, in centipawns for the side to move `stm`.
+    pub fn evaluate(&self, acc: &Accumulator, stm: Color) -> i32 {
+        let (ours, theirs) = self.output_weights.split_at(self.hidden);
+        let mut sum = self.output(&acc.values[stm as usize], ours) + self.output(&acc.values[!stm as usize], theirs);
+        if self.activation == Activation::SCReLU {
+            // back to a single QA, like the CReLU sum
+            sum /= QA as i64;
+        }
+        ((sum + self.output_bias as i64) * SCALE as i64 / (QA * QB) as i64) as i32
+    }
+
+    /// Evaluate a position from scratch, without any accumulator to start from.
+    pub fn evaluate_board(&self, board: &Board) -> i32 {
+        self.evaluate(&Accumulator::new(self, board), board.side_to_move)
+    }
+}
+
+impl Accumulator {
+    /// Compute the accumulator of `board` from scratch.
+    pub fn new(net: &Network, board: &Board) -> Accumulator {
+        let mut acc = Accumulator {
+            values: [net.feature_bias.clone(), net.feature_bias.clone()],
+        };
+        acc.refresh(net, board);
+        acc
+    }
+
+    /// Recompute the accumulator of `board` from scratch, in place.
+    pub fn refresh(&mut self, net: &Network, board: &Board) {
+        for color in [Color::White, Color::Black] {
+            let values = &mut self.values[color as usize];
+            values.copy_from_slice(&net.feature_bias);
+            for sq in board.pieces().iter() {
+                net.kernel.add(values, net.weights(feature(color, board.at(sq), sq)));
+            }
+        }
+    }
+
+    /// Set to `parent` updated with the features changed by `delta`.
+    pub fn update(&mut self, net: &Network, parent: &Accumulator, delta: &Delta) {
+        for color in [Color::White, Color::Black] {
+            let values = &mut self.values[color as usize];
+            values.copy_from_slice(&parent.values[color as usize]);
+            for (piece, sq) in delta.removed.iter().flatten() {
+                net.kernel.sub(values, net.weights(feature(color, *piece, *sq)));
+            }
+            for (piece, sq) in delta.added.iter().flatten() {
+                net.kernel.add(values, net.weights(feature(color, *piece, *sq)));
+            }
+        }
+    }
+}
+
+impl Delta {
+    /// The features changed by playing `m` on `board`.
+    pub fn new(board: &Board, m: Move) -> Delta {
+        let piece = board.at(m.from);
+        let captured = Some((board.at(m.to), m.to)).filter(|(p, _)| *p != Piece::None);
+        match m.move_type {
+            MoveType::Quiet => Delta {
+                removed: [Some((piece, m.from)), captured],
+                added: [Some((piece, m.to)), None],
+            },
+            MoveType::Promotion(ptype) => Delta {
+                removed: [Some((piece, m.from)), captured],
+                added: [Some((Piece::new(ptype, board.side_to_move), m.to)), None],
+            },
+            MoveType::EnPassant => Delta {
+                removed: [
+                    Some((piece, m.from)),
+                    Some((Piece::Pawn(!board.side_to_move), Square::new(m.to.file(), m.from.rank()))),
+                ],
+                added: [Some((piece, m.to)), None],
+            },
+            MoveType::Castle => {
+                // the king takes its own rook, and both land on their castling squares
+                let (king_to, rook_to) = castling_squares(m.from, m.to);
+                let rook = board.at(m.to);
+                Delta {
+                    removed: [Some((piece, m.from)), Some((rook, m.to))],
+                    added: [Some((piece, king_to)), Some((rook, rook_to))],
+                }
+            }
+        }
+    }
+}
+
+impl AccumulatorStack {
+    /// A stack holding the accumulator of `board` at ply 0.
+    pub fn new(net: &Network, board: &Board) -> AccumulatorStack {
+        AccumulatorStack {
+            accumulators: vec![Accumulator::new(net, board)],
+            ply: 0,
+        }
+    }
+
+    /// Start over from `board` at ply 0, keeping the memory of the deeper plies.
+    pub fn reset(&mut self, net: &Network, board: &Board) {
+        self.accumulators[0].refresh(net, board);
+        self.ply = 0;
+    }
+
+    /// Make `m`, played on `board`, the move to the next ply.
+    pub fn push(&mut self, net: &Network, board: &Board, m: Move) {
+        if self.ply + 1 == self.accumulators.len() {
+            self.accumulators.push(self.accumulators[self.ply].clone());
+        }
+        let (parents, children) = self.accumulators.split_at_mut(self.ply + 1);
+        children[0].update(net, &parents[self.ply], &Delta::new(board, m));
+        self.ply += 1;
+    }
+
+    /// Pass the turn, for a null move: nothing moves, so the accumulator is copied as it is.
+    pub fn push_null(&mut self) {
+        if self.ply + 1 == self.accumulators.len() {
+            self.accumulators.push(self.accumulators[self.ply].clone());
+        }
+        let (parents, children) = self.accumulators.split_at_mut(self.ply + 1);
+        for (child, parent) in children[0].values.iter_mut().zip(&parents[self.ply].values) {
+            child.copy_from_slice(parent);
+        }
+        self.ply += 1;
+    }
+
+    /// Unmake the last move pushed.
+    pub fn pop(&mut self) {
+        self.ply = self.ply.checked_sub(1).expect("There should be a move to unmake");
+    }
+
+    /// The accumulator of the current ply.
+    pub fn current(&self) -> &Accumulator {
+        &self.accumulators[self.ply]
+    }
+
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nnue::*;
+    use crate::movegen::legal_moves;
+    use crate::util::PRNG;
+
+    fn random_network(hidden: usize, activation: Activation) -> Network {
+        let mut rng = PRNG::new(0x891750FBADEA5);
+        let mut small = |range: u64| (rng.next() % (2 * range + 1)) as i16 - range as i16;
+        let mut net = Network::new(hidden, activation);
+        net.feature_weights.iter_mut().for_each(|w| *w = small(20));
+        net.feature_bias.iter_mut().for_each(|w| *w = small(100));
+        net.output_weights.iter_mut().for_each(|w| *w = small(64));
+        net.output_bias = small(1000);
+        net
+    }
+
+    fn walk(net: &Network, stack: &mut AccumulatorStack, board: &Board, depth: usize) {
+        assert_eq!(stack.current(), &Accumulator::new(net, board));
+        if depth == 0 {
+            return;
+        }
+        stack.push_null();
+        assert_eq!(stack.current(), &Accumulator::new(net, board));
+        stack.pop();
+        for m in legal_moves(board) {
+            let mut after = board.clone();
+            after.make_move(m);
+            stack.push(net, board, m);
+            walk(net, stack, &after, depth - 1);
+            stack.pop();
+        }
+    }
+
+    #[test]
+    fn test_nnue() {
+        let net = random_network(16, Activation::SCReLU);
+        let bytes = net.to_bytes();
+        assert_eq!(bytes.len(), 16 + 2 * (768 * 16 + 16 + 32 + 1));
+        assert_eq!(Network::from_bytes(&bytes), Ok(net.clone()));
+        assert!(Network::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Network::from_bytes(b"NOPE").is_err());
+
+        // incremental updates match a full refresh, through captures, promotions, en passant
+        // and castling (including Chess960 castling, where the king or rook may not move)
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "1r2k1r1/8/8/8/8/8/8/1R2K1R1 w GBgb - 0 1",
+        ] {
+            let board = Board::from(fen);
+            let mut stack = AccumulatorStack::new(&net, &board);
+            walk(&net, &mut stack, &board, 2);
+            assert_eq!(stack.ply(), 0);
+        }
+
+        // every kernel evaluates exactly the same, including with large weights that clip and wrap
+        for activation in [Activation::CReLU, Activation::SCReLU] {
+            let mut net = random_network(40, activation);
+            let mut rng = PRNG::new(7);
+            net.feature_bias.iter_mut().for_each(|w| *w = (rng.next() % 600) as i16 - 300);
+            net.output_weights.iter_mut().for_each(|w| *w = rng.next() as i16);
+            let board = Board::from("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+            let scalar = net.clone().with_kernel(Kernel::Scalar);
+            for kernel in Kernel::ALL.into_iter().filter(|k| k.available()) {
+                let net = net.clone().with_kernel(kernel);
+                let mut stack = AccumulatorStack::new(&net, &board);
+                let mut reference = AccumulatorStack::new(&scalar, &board);
+                for m in legal_moves(&board) {
+                    stack.push(&net, &board, m);
+                    reference.push(&scalar, &board, m);
+                    assert_eq!(stack.current(), reference.current());
+                    assert_eq!(net.evaluate(stack.current(), Color::Black), scalar.evaluate(reference.current(), Color::Black));
+                    stack.pop();
+                    reference.pop();
+                }
+            }
+        }
+
+        // each side sees the position the same way from its own side of the board
+        for activation in [Activation::CReLU, Activation::SCReLU] {
+            let net = random_network(16, activation);
+            let white = Board::from("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+            let black = Board::from("rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq - 2 3");
+            assert_eq!(net.evaluate_board(&white), net.evaluate_board(&black));
+        }
+    }
+}
@@ -0,0 +1,336 @@
+//! # NNUE Kernels
+//!
+//! The inner loops of the network: adding or subtracting a feature's weights to an accumulator,
+//! and the dot product of an activated accumulator with the output weights. Each one comes in a
+//! portable scalar version, and in SSE4.1 and AVX2 versions for x86-64, picked at runtime by
+//! [`Kernel::detect`] based on what the CPU supports.
+//!
+//! Every kernel gives bit-identical results. Accumulator values wrap on overflow and the CReLU
+//! sum is taken modulo 2^32, which doesn't depend on the order of the additions, while each
+//! SCReLU term is computed exactly in 32 bits and summed in 64 bits, so there's no rounding
+//! anywhere for the order to affect.
+//!
+//! ### Links
+//! - [SIMD and SWAR Techniques](https://www.chessprogramming.org/SIMD_and_SWAR_Techniques)
+//! - [Intel Intrinsics Guide](https://www.intel.com/content/www/us/en/docs/intrinsics-guide/index.html)
+
+use crate::nnue::QA;
+
+/// The instruction set used by the network's inner loops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kernel {
+    Scalar,
+    Sse41,
+    Avx2,
+}
+
+impl Kernel {
+    pub const ALL: [Kernel; 3] = [Kernel::Scalar, Kernel::Sse41, Kernel::Avx2];
+
+    /// The fastest kernel the CPU running this supports.
+    pub fn detect() -> Kernel {
+        [Kernel::Avx2, Kernel::Sse41]
+            .into_iter()
+            .find(|k| k.available())
+            .unwrap_or(Kernel::Scalar)
+    }
+
+    /// Checks if the CPU running this supports the kernel.
+    pub fn available(&self) -> bool {
+        match self {
+            Kernel::Scalar => true,
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Sse41 => std::arch::is_x86_feature_detected!("sse4.1"),
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx2 => std::arch::is_x86_feature_detected!("avx2"),
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => false,
+        }
+    }
+
+    /// Add `weights` to `values`, element by element.
+    #[inline]
+    pub(crate) fn add(&self, values: &mut [i16], weights: &[i16]) {
+        debug_assert!(self.available());
+        match self {
+            // SAFETY: networks only ever hold kernels the CPU supports, see `Network::with_kernel`
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx2 => unsafe { avx2::add(values, weights) },
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Sse41 => unsafe { sse41::add(values, weights) },
+            _ => scalar::add(values, weights),
+        }
+    }
+
+    /// Subtract `weights` from `values`, element by element.
+    #[inline]
+    pub(crate) fn sub(&self, values: &mut [i16], weights: &[i16]) {
+        debug_assert!(self.available());
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx2 => unsafe { avx2::sub(values, weights) },
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Sse41 => unsafe { sse41::sub(values, weights) },
+            _ => scalar::sub(values, weights),
+        }
+    }
+
+    /// Sum of `clamp(value, 0, QA) * weight`, modulo 2^32.
+    #[inline]
+    pub(crate) fn crelu_dot(&self, values: &[i16], weights: &[i16]) -> i32 {
+        debug_assert!(self.available());
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx2 => unsafe { avx2::crelu_dot(values, weights) },
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Sse41 => unsafe { sse41::crelu_dot(values, weights) },
+            _ => scalar::crelu_dot(values, weights),
+        }
+    }
+
+    /// Sum of `clamp(value, 0, QA)^2 * weight`.
+    #[inline]
+    pub(crate) fn screlu_dot(&self, values: &[i16], weights: &[i16]) -> i64 {
+        debug_assert!(self.available());
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx2 => unsafe { avx2::screlu_dot(values, weights) },
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Sse41 => unsafe { sse41::screlu_dot(values, weights) },
+            _ => scalar::screlu_dot(values, weights),
+        }
+    }
+}
+
+/// Plain loops, which the compiler is free to vectorise for whatever the target supports. The
+/// vector kernels use them for the elements left over after the last full vector.
+mod scalar {
+    use super::QA;
+
+    #[inline]
+    pub fn add(values: &mut [i16], weights: &[i16]) {
+        for (v, w) in values.iter_mut().zip(weights) {
+            *v = v.wrapping_add(*w);
+        }
+    }
+
+    #[inline]
+    pub fn sub(values: &mut [i16], weights: &[i16]) {
+        for (v, w) in values.iter_mut().zip(weights) {
+            *v = v.wrapping_sub(*w);
+        }
+    }
+
+    #[inline]
+    pub fn crelu_dot(values: &[i16], weights: &[i16]) -> i32 {
+        values
+            .iter()
+            .zip(weights)
+            .fold(0i32, |sum, (v, w)| sum.wrapping_add((*v as i32).clamp(0, QA) * *w as i32))
+    }
+
+    #[inline]
+    pub fn screlu_dot(values: &[i16], weights: &[i16]) -> i64 {
+        values
+            .iter()
+            .zip(weights)
+            .map(|(v, w)| {
+                let v = (*v as i32).clamp(0, QA);
+                // at most 255^2 * 2^15, which still fits in 32 bits
+                (v * v * *w as i32) as i64
+            })
+            .sum()
+    }
+}
+
+/// 16 values at a time. Only called once AVX2 support has been checked for.
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    use super::{scalar, QA};
+
+    const LANES: usize = 16;
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn load(slice: &[i16], i: usize) -> __m256i {
+        _mm256_loadu_si256(slice.as_ptr().add(i) as *const __m256i)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn clamp(v: __m256i) -> __m256i {
+        _mm256_min_epi16(_mm256_max_epi16(v, _mm256_setzero_si256()), _mm256_set1_epi16(QA as i16))
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn add(values: &mut [i16], weights: &[i16]) {
+        let n = values.len().min(weights.len()) / LANES * LANES;
+        for i in (0..n).step_by(LANES) {
+            let sum = _mm256_add_epi16(load(values, i), load(weights, i));
+            _mm256_storeu_si256(values.as_mut_ptr().add(i) as *mut __m256i, sum);
+        }
+        scalar::add(&mut values[n..], &weights[n..]);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn sub(values: &mut [i16], weights: &[i16]) {
+        let n = values.len().min(weights.len()) / LANES * LANES;
+        for i in (0..n).step_by(LANES) {
+            let diff = _mm256_sub_epi16(load(values, i), load(weights, i));
+            _mm256_storeu_si256(values.as_mut_ptr().add(i) as *mut __m256i, diff);
+        }
+        scalar::sub(&mut values[n..], &weights[n..]);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn crelu_dot(values: &[i16], weights: &[i16]) -> i32 {
+        let n = values.len().min(weights.len()) / LANES * LANES;
+        let mut sum = _mm256_setzero_si256();
+        for i in (0..n).step_by(LANES) {
+            // pairs of products, each at most 2 * 255 * 2^15, summed into 32 bit lanes
+            sum = _mm256_add_epi32(sum, _mm256_madd_epi16(clamp(load(values, i)), load(weights, i)));
+        }
+        let mut lanes = [0i32; 8];
+        _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, sum);
+        lanes.iter().fold(scalar::crelu_dot(&values[n..], &weights[n..]), |a, b| a.wrapping_add(*b))
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn screlu_dot(values: &[i16], weights: &[i16]) -> i64 {
+        let n = values.len().min(weights.len()) / LANES * LANES;
+        let zero = _mm256_setzero_si256();
+        let mut sum = _mm256_setzero_si256();
+        for i in (0..n).step_by(LANES) {
+            let (v, w) = (clamp(load(values, i)), load(weights, i));
+            // the full 32 bit products v * w, from their low and high halves
+            let (low, high) = (_mm256_mullo_epi16(v, w), _mm256_mulhi_epi16(v, w));
+            let vw = [_mm256_unpacklo_epi16(low, high), _mm256_unpackhi_epi16(low, high)];
+            // and v widened to 32 bits, in the same order
+            let v = [_mm256_unpacklo_epi16(v, zero), _mm256_unpackhi_epi16(v, zero)];
+            for (vw, v) in vw.into_iter().zip(v) {
+                let terms = _mm256_mullo_epi32(vw, v);
+                sum = _mm256_add_epi64(sum, _mm256_cvtepi32_epi64(_mm256_castsi256_si128(terms)));
+                sum = _mm256_add_epi64(sum, _mm256_cvtepi32_epi64(_mm256_extracti128_si256::<1>(terms)));
+            }
+        }
+        let mut lanes = [0i64; 4];
+        _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, sum);
+        lanes.iter().sum::<i64>() + scalar::screlu_dot(&values[n..], &weights[n..])
+    }
+}
+
+/// 8 values at a time. Only called once SSE4.1 support has been checked for.
+#[cfg(target_arch = "x86_64")]
+mod sse41 {
+    use std::arch::x86_64::*;
+
+    use super::{scalar, QA};
+
+    const LANES: usize = 8;
+
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn load(slice: &[i16], i: usize) -> __m128i {
+        _mm_loadu_si128(slice.as_ptr().add(i) as *const __m128i)
+    }
+
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn clamp(v: __m128i) -> __m128i {
+        _mm_min_epi16(_mm_max_epi16(v, _mm_setzero_si128()), _mm_set1_epi16(QA as i16))
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    pub unsafe fn add(values: &mut [i16], weights: &[i16]) {
+        let n = values.len().min(weights.len()) / LANES * LANES;
+        for i in (0..n).step_by(LANES) {
+            let sum = _mm_add_epi16(load(values, i), load(weights, i));
+            _mm_storeu_si128(values.as_mut_ptr().add(i) as *mut __m128i, sum);
+        }
+        scalar::add(&mut values[n..], &weights[n..]);
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    pub unsafe fn sub(values: &mut [i16], weights: &[i16]) {
+        let n = values.len().min(weights.len()) / LANES * LANES;
+        for i in (0..n).step_by(LANES) {
+            let diff = _mm_sub_epi16(load(values, i), load(weights, i));
+            _mm_storeu_si128(values.as_mut_ptr().add(i) as *mut __m128i, diff);
+        }
+        scalar::sub(&mut values[n..], &weights[n..]);
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    pub unsafe fn crelu_dot(values: &[i16], weights: &[i16]) -> i32 {
+        let n = values.len().min(weights.len()) / LANES * LANES;
+        let mut sum = _mm_setzero_si128();
+        for i in (0..n).step_by(LANES) {
+            sum = _mm_add_epi32(sum, _mm_madd_epi16(clamp(load(values, i)), load(weights, i)));
+        }
+        let mut lanes = [0i32; 4];
+        _mm_storeu_si128(lanes.as_mut_ptr() as *mut __m128i, sum);
+        lanes.iter().fold(scalar::crelu_dot(&values[n..], &weights[n..]), |a, b| a.wrapping_add(*b))
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    pub unsafe fn screlu_dot(values: &[i16], weights: &[i16]) -> i64 {
+        let n = values.len().min(weights.len()) / LANES * LANES;
+        let zero = _mm_setzero_si128();
+        let mut sum = _mm_setzero_si128();
+        for i in (0..n).step_by(LANES) {
+            let (v, w) = (clamp(load(values, i)), load(weights, i));
+            let (low, high) = (_mm_mullo_epi16(v, w), _mm_mulhi_epi16(v, w));
+            let vw = [_mm_unpacklo_epi16(low, high), _mm_unpackhi_epi16(low, high)];
+            let v = [_mm_unpacklo_epi16(v, zero), _mm_unpackhi_epi16(v, zero)];
+            for (vw, v) in vw.into_iter().zip(v) {
+                let terms = _mm_mullo_epi32(vw, v);
+                sum = _mm_add_epi64(sum, _mm_cvtepi32_epi64(terms));
+                sum = _mm_add_epi64(sum, _mm_cvtepi32_epi64(_mm_srli_si128::<8>(terms)));
+            }
+        }
+        let mut lanes = [0i64; 2];
+        _mm_storeu_si128(lanes.as_mut_ptr() as *mut __m128i, sum);
+        lanes.iter().sum::<i64>() + scalar::screlu_dot(&values[n..], &weights[n..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nnue::simd::*;
+    use crate::util::PRNG;
+
+    #[test]
+    fn test_kernels() {
+        let kernels: Vec<Kernel> = Kernel::ALL.into_iter().filter(|k| k.available()).collect();
+        let mut rng = PRNG::new(0x891750FBADEA5);
+        // lengths with and without a scalar tail, and values over the whole range, so that
+        // accumulators wrap and activations clip
+        for len in [0, 7, 8, 16, 24, 31, 64, 250, 1024] {
+            for _ in 0..20 {
+                let values: Vec<i16> = (0..len).map(|_| rng.next() as i16).collect();
+                let weights: Vec<i16> = (0..len).map(|_| rng.next() as i16).collect();
+                let small: Vec<i16> = values.iter().map(|v| v % 300).collect();
+
+                let mut expected = (values.clone(), values.clone());
+                scalar::add(&mut expected.0, &weights);
+                scalar::sub(&mut expected.1, &weights);
+                for kernel in &kernels {
+                    let mut added = values.clone();
+                    kernel.add(&mut added, &weights);
+                    assert_eq!(added, expected.0, "{:?}", kernel);
+                    let mut subtracted = values.clone();
+                    kernel.sub(&mut subtracted, &weights);
+                    assert_eq!(subtracted, expected.1, "{:?}", kernel);
+                    for values in [&values, &small] {
+                        assert_eq!(kernel.crelu_dot(values, &weights), scalar::crelu_dot(values, &weights), "{:?}", kernel);
+                        assert_eq!(kernel.screlu_dot(values, &weights), scalar::screlu_dot(values, &weights), "{:?}", kernel);
+                    }
+                }
+            }
+        }
+        assert_eq!(scalar::screlu_dot(&[300, -5, 2], &[i16::MIN, 7, 3]), -255 * 255 * 32768 + 12);
+        assert!(Kernel::detect().available());
+    }
+}
@@ -0,0 +1,158 @@
+//! # PEXT Bitboards
+//!
+//! An alternative to magic bitboards for CPUs with the BMI2 instruction set. The `pext`
+//! instruction (parallel bits extract) gathers the bits of the blockers selected by the mask
+//! into the low bits of the result, which is a perfect index: no magic number, no shift and
+//! no collisions, so each square's table is exactly `2^popcount(mask)` entries.
+//!
+//! This backend is used instead of magics when the crate is built with the `pext` feature for a
+//! CPU with BMI2, e.g. with `RUSTFLAGS="-C target-cpu=native"`; otherwise the build script warns
+//! that the feature is ignored, and the next backend is used. The instruction is picked at
+//! compile time, so that it's inlined into every lookup: checking for it at runtime would put a
+//! branch and a call on the hottest path of move generation. Without BMI2, [`Software`] does the
+//! same in a (slow) loop, which is only really useful for testing.
+//!
+//! ### Links
+//! - [BMI2](https://www.chessprogramming.org/BMI2#PEXTBitboards)
+
+use std::marker::PhantomData;
+
+use crate::types::*;
+use crate::movegen::sliding_attack;
+
+/// A way to compute `pext`, each its own [`PextBitboard`] type so that lookups don't branch.
+pub trait Extract {
+    fn pext(value: u64, mask: u64) -> u64;
+}
+
+/// The software version of `pext`, for any CPU.
+pub struct Software;
+
+/// The `pext` instruction, only there when building for a CPU with BMI2.
+#[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+pub struct Bmi2;
+
+/// The fastest `pext` this build has.
+#[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+pub type Native = Bmi2;
+#[cfg(not(all(target_arch = "x86_64", target_feature = "bmi2")))]
+pub type Native = Software;
+
+pub struct PextBitboard<E: Extract = Native> {
+    masks: [Bitboard; 64],
+    offsets: [usize; 64],
+    table: Vec<Bitboard>,
+    extract: PhantomData<E>,
+}
+
+/// Software version of `pext`: the bits of `value` selected by `mask`, packed into the low bits.
+#[inline]
+pub fn pext(value: u64, mut mask: u64) -> u64 {
+    let mut result = 0;
+    let mut bit = 1;
+    while mask != 0 {
+        if value & mask & mask.wrapping_neg() != 0 {
+            result |= bit;
+        }
+        mask &= mask - 1;
+        bit <<= 1;
+    }
+    result
+}
+
+impl Extract for Software {
+    #[inline]
+    fn pext(value: u64, mask: u64) -> u64 {
+        pext(value, mask)
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+impl Extract for Bmi2 {
+    #[inline]
+    fn pext(value: u64, mask: u64) -> u64 {
+        // SAFETY: this is only compiled for CPUs with BMI2
+        #[allow(unused_unsafe)]
+        unsafe {
+            std::arch::x86_64::_pext_u64(value, mask)
+        }
+    }
+}
+
+impl<E: Extract> PextBitboard<E> {
+    /// Build the attack tables for a piece.
+    pub fn new(piece: &MagicPiece) -> PextBitboard<E> {
+        let mut pext = PextBitboard {
+            masks: [Bitboard(0); 64],
+            offsets: [0; 64],
+            table: vec![],
+            extract: PhantomData,
+        };
+        for sq in Square::all() {
+            let mask = sliding_attack(piece.ptype(), sq, Bitboard(0)) & !Bitboard::edges(sq);
+            pext.masks[sq as usize] = mask;
+            pext.offsets[sq as usize] = pext.table.len();
+            pext.table.resize(pext.table.len() + (1 << mask.count()), Bitboard(0));
+            for subset in mask.subsets() {
+                let index = pext.index(sq, subset);
+                pext.table[index] = sliding_attack(piece.ptype(), sq, subset);
+            }
+        }
+        pext
+    }
+
+    /// Number of entries in the shared attack table.
+    pub fn table_size(&self) -> usize {
+        self.table.len()
+    }
+
+    #[inline]
+    fn index(&self, sq: Square, occupied: Bitboard) -> usize {
+        self.offsets[sq as usize] + E::pext(occupied.0, self.masks[sq as usize].0) as usize
+    }
+}
+
+impl<E: Extract> SliderAttacks for PextBitboard<E> {
+    #[inline]
+    fn attacks(&self, sq: Square, occupied: Bitboard) -> Bitboard {
+        self.table[self.index(sq, occupied)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pext::*;
+    use crate::magic::load_magics;
+    use crate::magic::constants::*;
+
+    /// Checks the attacks for every square and every subset of its blockers.
+    fn assert_matches_sliding_attack(piece: &MagicPiece, backend: &impl SliderAttacks) {
+        for sq in Square::all() {
+            let mask = sliding_attack(piece.ptype(), sq, Bitboard(0)) & !Bitboard::edges(sq);
+            for subset in mask.subsets() {
+                assert_eq!(backend.attacks(sq, subset), sliding_attack(piece.ptype(), sq, subset));
+            }
+        }
+    }
+
+    #[test]
+    fn test_pext() {
+        assert_eq!(pext(0b1011_0110, 0b1111_0000), 0b1011);
+        assert_eq!(pext(0b1011_0110, 0b0101_0101), 0b0110);
+
+        for piece in [MagicPiece::Bishop, MagicPiece::Rook] {
+            assert_matches_sliding_attack(&piece, &PextBitboard::<Software>::new(&piece));
+            #[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+            assert_matches_sliding_attack(&piece, &PextBitboard::<Bmi2>::new(&piece));
+        }
+        // no wasted entries, unlike magics
+        assert_eq!(PextBitboard::<Native>::new(&MagicPiece::Bishop).table_size(), BISHOP_TABLE_SIZE);
+        assert_eq!(PextBitboard::<Native>::new(&MagicPiece::Rook).table_size(), ROOK_TABLE_SIZE);
+
+        let magics = load_magics(&MagicPiece::Rook, &ROOK_MAGICS, ROOK_TABLE_SIZE);
+        assert_matches_sliding_attack(&MagicPiece::Rook, &magics);
+        assert_eq!(cfg!(sliders = "pext"), cfg!(all(feature = "pext", target_arch = "x86_64", target_feature = "bmi2")));
+        assert_matches_sliding_attack(&MagicPiece::Bishop, &crate::pregen().bishop);
+        assert_matches_sliding_attack(&MagicPiece::Rook, &crate::pregen().rook);
+    }
+}
@@ -0,0 +1,601 @@
+//! # PGN
+//!
+//! Reading and writing games in Portable Game Notation, the standard format for storing chess games.
+//!
+//! A PGN file is a sequence of games. Each game starts with a list of tag pairs
+//! (`[Event "Casual game"]`), followed by the movetext: the moves in SAN, optionally annotated with
+//! NAGs (`$1`, or `!`/`?` suffixes), `{comments}` and `(variations)`, and terminated by the result.
+//!
+//! [`PgnReader`] streams games out of any [`BufRead`], one at a time, so arbitrarily large files
+//! can be processed. A [`Game`] can be replayed onto a [`Board`], and written back out in export
+//! format (seven tag roster first, movetext wrapped to 80 columns) through its `Display` impl.
+//!
+//! ### Links
+//! - [PGN Standard](https://www.thechessdrum.net/PGN_Reference.txt)
+
+use std::fmt::Display;
+use std::io::BufRead;
+
+use crate::types::*;
+use crate::san::{parse_san, to_san};
+use crate::BOARD_START_FEN;
+
+/// Tags every game must have, in the order they are exported.
+pub const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+/// Maximum width of the exported movetext.
+const COLUMNS: usize = 80;
+
+/// A single game: tags, the mainline with its annotations, and the result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Game {
+    pub tags: Vec<(String, String)>,
+    /// A comment before the first move.
+    pub comment: Option<String>,
+    pub moves: Vec<Node>,
+    /// The game termination marker: `1-0`, `0-1`, `1/2-1/2` or `*`.
+    pub result: String,
+}
+
+/// A move in the game tree, along with its annotations.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Node {
+    pub san: String,
+    pub nags: Vec<u8>,
+    /// A comment after the move.
+    pub comment: Option<String>,
+    /// Alternatives to this move, played from the position before it.
+    pub variations: Vec<Variation>,
+}
+
+/// A sequence of moves branching off from the main line.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Variation {
+    /// A comment before the first move of the variation.
+    pub comment: Option<String>,
+    pub moves: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    TagOpen,
+    TagClose,
+    Str(String),
+    Symbol(String),
+    Nag(u8),
+    Comment(String),
+    VariationOpen,
+    VariationClose,
+    Result(String),
+}
+
+struct Lexer<R: BufRead> {
+    reader: R,
+    line: Vec<char>,
+    pos: usize,
+    peeked: Option<Token>,
+}
+
+/// Streams games from a PGN source.
+///
+/// # Example
+/// ```
+/// use tejuino::pgn::PgnReader;
+///
+/// let pgn = "[White \"A\"]\n[Black \"B\"]\n\n1. e4 e5 2. Nf3 {main line} (2. f4 exf4) Nc6 1-0\n";
+/// let games: Vec<_> = PgnReader::new(pgn.as_bytes()).collect::<Result<_, _>>().unwrap();
+/// assert_eq!(games.len(), 1);
+/// assert_eq!(games[0].moves.len(), 4);
+/// assert_eq!(games[0].moves[2].variations[0].moves[0].san, "f4");
+/// ```
+pub struct PgnReader<R: BufRead> {
+    lexer: Lexer<R>,
+}
+
+impl Game {
+    pub fn new() -> Game {
+        Game {
+            tags: vec![],
+            comment: None,
+            moves: vec![],
+            result: "*".to_string(),
+        }
+    }
+
+    /// Parse a single game from a string.
+    pub fn parse(pgn: &str) -> Result<Game, String> {
+        PgnReader::new(pgn.as_bytes())
+            .next()
+            .unwrap_or(Err("No game found".to_string()))
+    }
+
+    /// Build a game record from a starting position and the moves played from it.
+    pub fn from_moves(start: &Board, moves: &[Move]) -> Game {
+        let mut game = Game::new();
+        let fen = start.fen();
+        if fen != BOARD_START_FEN {
+            game.set_tag("SetUp", "1");
+            game.set_tag("FEN", &fen);
+        }
+        let mut board = start.clone();
+        for m in moves {
+            game.moves.push(Node {
+                san: to_san(&board, *m),
+                ..Default::default()
+            });
+            board.make_move(*m);
+        }
+        game
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// The position the game starts from, taken from the `FEN` tag if present.
+    pub fn start_board(&self) -> Result<Board, String> {
+        Board::from_fen(self.tag("FEN").unwrap_or(BOARD_START_FEN))
+    }
+
+    /// Resolve the SAN moves of the main line.
+    pub fn mainline(&self) -> Result<Vec<Move>, String> {
+        let mut board = self.start_board()?;
+        let mut moves = vec![];
+        for node in &self.moves {
+            let m = parse_san(&board, &node.san)?;
+            board.make_move(m);
+            moves.push(m);
+        }
+        Ok(moves)
+    }
+
+    /// Replay the whole game, checking every move (including variations) is legal,
+    /// and return the final position of the main line.
+    pub fn replay(&self) -> Result<Board, String> {
+        let mut board = self.start_board()?;
+        replay_line(&mut board, &self.moves)?;
+        Ok(board)
+    }
+}
+
+fn replay_line(board: &mut Board, nodes: &[Node]) -> Result<(), String> {
+    for node in nodes {
+        for variation in &node.variations {
+            replay_line(&mut board.clone(), &variation.moves)?;
+        }
+        let m = parse_san(board, &node.san)?;
+        board.make_move(m);
+    }
+    Ok(())
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new()
+    }
+}
+
+impl<R: BufRead> Lexer<R> {
+    fn new(reader: R) -> Lexer<R> {
+        Lexer {
+            reader,
+            line: vec![],
+            pos: 0,
+            peeked: None,
+        }
+    }
+
+    /// Load the next line, skipping `%` escape lines. Returns false at the end of the input.
+    fn next_line(&mut self) -> Result<bool, String> {
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+                return Ok(false);
+            }
+            if line.starts_with('%') {
+                continue;
+            }
+            self.line = line.chars().collect();
+            self.pos = 0;
+            return Ok(true);
+        }
+    }
+
+    fn peek_char(&mut self) -> Result<Option<char>, String> {
+        while self.pos >= self.line.len() {
+            if !self.next_line()? {
+                return Ok(None);
+            }
+        }
+        Ok(Some(self.line[self.pos]))
+    }
+
+    fn next_char(&mut self) -> Result<Option<char>, String> {
+        let c = self.peek_char()?;
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn peek(&mut self) -> Result<Option<&Token>, String> {
+        if self.peeked.is_none() {
+            self.peeked = self.lex()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    fn next(&mut self) -> Result<Option<Token>, String> {
+        match self.peeked.take() {
+            Some(token) => Ok(Some(token)),
+            None => self.lex(),
+        }
+    }
+
+    fn lex(&mut self) -> Result<Option<Token>, String> {
+        loop {
+            let c = match self.next_char()? {
+                Some(c) => c,
+                None => return Ok(None),
+            };
+            let token = match c {
+                c if c.is_whitespace() || c == '.' => continue,
+                '[' => Token::TagOpen,
+                ']' => Token::TagClose,
+                '(' => Token::VariationOpen,
+                ')' => Token::VariationClose,
+                '*' => Token::Result("*".to_string()),
+                ';' => {
+                    let comment: String = self.line[self.pos..].iter().collect();
+                    self.pos = self.line.len();
+                    Token::Comment(comment.trim().to_string())
+                }
+                '{' => {
+                    let mut comment = String::new();
+                    loop {
+                        match self.next_char()? {
+                            Some('}') => break,
+                            Some(c) => comment.push(c),
+                            None => return Err("Unterminated comment".to_string()),
+                        }
+                    }
+                    Token::Comment(comment.split_whitespace().collect::<Vec<_>>().join(" "))
+                }
+                '"' => {
+                    let mut s = String::new();
+                    loop {
+                        match self.next_char()? {
+                            Some('"') => break,
+                            Some('\\') => match self.next_char()? {
+                                Some(c) => s.push(c),
+                                None => return Err("Unterminated string".to_string()),
+                            },
+                            Some('\n') | None => return Err("Unterminated string".to_string()),
+                            Some(c) => s.push(c),
+                        }
+                    }
+                    Token::Str(s)
+                }
+                '$' => {
+                    let mut nag = String::new();
+                    while let Some(c) = self.peek_char()?.filter(|c| c.is_ascii_digit()) {
+                        nag.push(c);
+                        self.pos += 1;
+                    }
+                    Token::Nag(nag.parse().map_err(|_| format!("Invalid NAG: ${}", nag))?)
+                }
+                '!' | '?' => {
+                    let mut suffix = c.to_string();
+                    while let Some(c) = self.peek_char()?.filter(|c| *c == '!' || *c == '?') {
+                        suffix.push(c);
+                        self.pos += 1;
+                    }
+                    match suffix.as_str() {
+                        "!" => Token::Nag(1),
+                        "?" => Token::Nag(2),
+                        "!!" => Token::Nag(3),
+                        "??" => Token::Nag(4),
+                        "!?" => Token::Nag(5),
+                        "?!" => Token::Nag(6),
+                        _ => return Err(format!("Invalid move suffix: {}", suffix)),
+                    }
+                }
+                c if c.is_ascii_alphanumeric() => {
+                    let mut symbol = c.to_string();
+                    while let Some(c) = self.peek_char()?.filter(|c| c.is_ascii_alphanumeric() || "_+#=:-/".contains(*c)) {
+                        symbol.push(c);
+                        self.pos += 1;
+                    }
+                    match symbol.as_str() {
+                        "1-0" | "0-1" | "1/2-1/2" => Token::Result(symbol),
+                        // move numbers carry no information
+                        s if s.chars().all(|c| c.is_ascii_digit()) => continue,
+                        _ => Token::Symbol(symbol),
+                    }
+                }
+                c => return Err(format!("Unexpected character in PGN: {:?}", c)),
+            };
+            return Ok(Some(token));
+        }
+    }
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> PgnReader<R> {
+        PgnReader {
+            lexer: Lexer::new(reader),
+        }
+    }
+
+    fn read_game(&mut self) -> Result<Option<Game>, String> {
+        let mut game = Game::new();
+        let mut found = false;
+
+        while self.lexer.peek()? == Some(&Token::TagOpen) {
+            self.lexer.next()?;
+            found = true;
+            let (name, value) = match (self.lexer.next()?, self.lexer.next()?, self.lexer.next()?) {
+                (Some(Token::Symbol(name)), Some(Token::Str(value)), Some(Token::TagClose)) => (name, value),
+                _ => return Err("Malformed tag pair".to_string()),
+            };
+            game.tags.push((name, value));
+        }
+
+        // The bottom of the stack is the main line. Each open variation is pushed on top of it,
+        // and attached to the last move of the line below when closed.
+        let mut lines = vec![Variation::default()];
+        loop {
+            let token = match self.lexer.peek()? {
+                // a new game starts without the previous one being terminated
+                Some(Token::TagOpen) | None => break,
+                Some(_) => self.lexer.next()?.unwrap(),
+            };
+            found = true;
+            let line = lines.last_mut().unwrap();
+            match token {
+                Token::Symbol(san) => line.moves.push(Node {
+                    san,
+                    ..Default::default()
+                }),
+                Token::Nag(nag) => match line.moves.last_mut() {
+                    Some(node) => node.nags.push(nag),
+                    None => return Err(format!("NAG ${} before any move", nag)),
+                },
+                Token::Comment(comment) => {
+                    let target = match line.moves.last_mut() {
+                        Some(node) => &mut node.comment,
+                        None => &mut line.comment,
+                    };
+                    *target = Some(match target.take() {
+                        Some(previous) => format!("{} {}", previous, comment),
+                        None => comment,
+                    });
+                }
+                Token::VariationOpen => {
+                    if line.moves.is_empty() {
+                        return Err("Variation before any move".to_string());
+                    }
+                    lines.push(Variation::default());
+                }
+                Token::VariationClose => {
+                    if lines.len() < 2 {
+                        return Err("Unmatched closing parenthesis".to_string());
+                    }
+                    let variation = lines.pop().unwrap();
+                    lines.last_mut().unwrap().moves.last_mut().unwrap().variations.push(variation);
+                }
+                Token::Result(result) => {
+                    if lines.len() > 1 {
+                        return Err("Game ended inside a variation".to_string());
+                    }
+                    game.result = result;
+                    break;
+                }
+                Token::TagOpen | Token::TagClose | Token::Str(_) => {
+                    return Err(format!("Unexpected token in movetext: {:?}", token));
+                }
+            }
+        }
+
+        if !found {
+            return Ok(None);
+        }
+        if lines.len() > 1 {
+            return Err("Unterminated variation".to_string());
+        }
+        let mainline = lines.pop().unwrap();
+        game.comment = mainline.comment;
+        game.moves = mainline.moves;
+        Ok(Some(game))
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<Game, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_game().transpose()
+    }
+}
+
+fn push_comment(tokens: &mut Vec<String>, comment: &str) {
+    let words: Vec<&str> = comment.split_whitespace().collect();
+    if words.is_empty() {
+        tokens.push("{}".to_string());
+        return;
+    }
+    for (i, word) in words.iter().enumerate() {
+        let mut token = word.to_string();
+        if i == 0 {
+            token.insert(0, '{');
+        }
+        if i == words.len() - 1 {
+            token.push('}');
+        }
+        tokens.push(token);
+    }
+}
+
+/// Turn a line into movetext tokens. `ply` counts half-moves from the start of the game,
+/// starting at `2 * fullmove_number` for white.
+fn movetext_tokens(tokens: &mut Vec<String>, comment: &Option<String>, nodes: &[Node], mut ply: u32) {
+    if let Some(comment) = comment {
+        push_comment(tokens, comment);
+    }
+    let mut need_number = true;
+    for node in nodes {
+        if ply.is_multiple_of(2) {
+            tokens.push(format!("{}.", ply / 2));
+        } else if need_number {
+            tokens.push(format!("{}...", ply / 2));
+        }
+        need_number = false;
+
+        tokens.push(node.san.clone());
+        for nag in &node.nags {
+            tokens.push(format!("${}", nag));
+        }
+        if let Some(comment) = &node.comment {
+            push_comment(tokens, comment);
+            need_number = true;
+        }
+        for variation in &node.variations {
+            let mut inner = vec![];
+            movetext_tokens(&mut inner, &variation.comment, &variation.moves, ply);
+            if inner.is_empty() {
+                continue;
+            }
+            inner[0].insert(0, '(');
+            inner.last_mut().unwrap().push(')');
+            tokens.extend(inner);
+            need_number = true;
+        }
+        ply += 1;
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Display for Game {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for name in SEVEN_TAG_ROSTER {
+            let value = match name {
+                "Result" => self.result.as_str(),
+                _ => self.tag(name).unwrap_or(match name {
+                    "Date" => "????.??.??",
+                    _ => "?",
+                }),
+            };
+            writeln!(f, "[{} \"{}\"]", name, escape(value))?;
+        }
+        for (name, value) in &self.tags {
+            if !SEVEN_TAG_ROSTER.contains(&name.as_str()) {
+                writeln!(f, "[{} \"{}\"]", name, escape(value))?;
+            }
+        }
+        writeln!(f)?;
+
+        let start = self.start_board().unwrap_or_default();
+        let ply = start.fullmove_number * 2 + start.side_to_move as u32;
+        let mut tokens = vec![];
+        movetext_tokens(&mut tokens, &self.comment, &self.moves, ply);
+        tokens.push(self.result.clone());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.chars().count() + 1 + token.chars().count() > COLUMNS {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line += &token;
+        }
+        writeln!(f, "{}", line)?;
+        writeln!(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pgn::*;
+
+    const PGN: &str = r#"[Event "F/S Return Match"]
+[Site "Belgrade, Serbia JUG"]
+[Date "1992.11.04"]
+[Round "29"]
+[White "Fischer, Robert J."]
+[Black "Spassky, Boris V."]
+[Result "1/2-1/2"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 {This opening is called the Ruy Lopez.}
+4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O 9. h3 Nb8 10. d4 Nbd7
+11. c4 c6 12. cxb5 axb5 13. Nc3 Bb7 14. Bg5 b4 15. Nb1 h6 16. Bh4 c5 17. dxe5
+Nxe4 18. Bxe7 Qxe7 19. exd6 Qf6 20. Nbd2 Nxd6 21. Nc4 Nxc4 22. Bxc4 Nb6
+23. Ne5 Rae8 24. Bxf7+ Rxf7 25. Nxf7 Rxe1+ 26. Qxe1 Kxf7 27. Qe3 Qg5 28. Qxg5
+hxg5 29. b3 Ke6 30. a3 Kd6 31. axb4 cxb4 32. Ra5 Nd5 33. f3 Bc8 34. Kf2 Bf5
+35. Ra7 g6 36. Ra6+ Kc5 37. Ke1 Nf4 38. g3 Nxh3 39. Kd2 Kb5 40. Rd6 Kc5 41. Ra6
+Nf2 42. g4 Bd3 43. Re6 1/2-1/2
+
+% escaped line
+[Event "Second"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 b - - 0 12"]
+
+{Start} 12... Kd7 13. e4!? (13. e3 $2 Ke6 ; rest of line comment
+(13... Kd6)) Ke6 *
+"#;
+
+    #[test]
+    fn test_pgn_read() {
+        let games: Vec<Game> = PgnReader::new(PGN.as_bytes()).collect::<Result<_, _>>().unwrap();
+        assert_eq!(games.len(), 2);
+
+        let fischer = &games[0];
+        assert_eq!(fischer.tag("White"), Some("Fischer, Robert J."));
+        assert_eq!(fischer.result, "1/2-1/2");
+        assert_eq!(fischer.moves.len(), 85);
+        assert_eq!(fischer.moves[5].comment.as_deref(), Some("This opening is called the Ruy Lopez."));
+        let board = fischer.replay().unwrap();
+        assert_eq!(board.fen(), "8/8/4R1p1/2k3p1/1p4P1/1P1b1P2/3K1n2/8 b - - 2 43");
+
+        let second = &games[1];
+        assert_eq!(second.comment.as_deref(), Some("Start"));
+        assert_eq!(second.moves[1].nags, vec![5]);
+        let variation = &second.moves[1].variations[0];
+        assert_eq!(variation.moves[0].nags, vec![2]);
+        assert_eq!(variation.moves[1].comment.as_deref(), Some("rest of line comment"));
+        assert_eq!(variation.moves[1].variations[0].moves[0].san, "Kd6");
+        assert_eq!(second.replay().unwrap().at(Square::E6), Piece::King(Color::Black));
+    }
+
+    #[test]
+    fn test_pgn_write() {
+        let games: Vec<Game> = PgnReader::new(PGN.as_bytes()).collect::<Result<_, _>>().unwrap();
+        for game in &games {
+            let written = game.to_string();
+            assert!(written.lines().all(|line| line.chars().count() <= COLUMNS));
+            let reread = Game::parse(&written).unwrap();
+            assert_eq!(reread.moves, game.moves);
+            assert_eq!(reread.comment, game.comment);
+            assert_eq!(reread.to_string(), written);
+        }
+
+        let written = games[1].to_string();
+        assert!(written.starts_with("[Event \"Second\"]\n[Site \"?\"]\n[Date \"????.??.??\"]"));
+        assert!(written.replace('\n', " ").contains("{Start} 12... Kd7 13. e4 $5 (13. e3 $2 Ke6 {rest of line comment} (13... Kd6)) 13... Ke6 *"));
+
+        let board = Board::from(crate::BOARD_START_FEN);
+        let moves = games[0].mainline().unwrap();
+        let rebuilt = Game::from_moves(&board, &moves);
+        assert_eq!(rebuilt.moves.iter().map(|n| &n.san).collect::<Vec<_>>(),
+                   games[0].moves.iter().map(|n| &n.san).collect::<Vec<_>>());
+    }
+}
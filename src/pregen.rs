@@ -1,17 +1,44 @@
 use std::ops::{Index, IndexMut};
 
 use crate::types::*;
-use crate::magic::*;
+#[cfg(sliders = "magic")]
+use crate::magic::{load_magics, constants::*};
+#[cfg(sliders = "black-magic")]
+use crate::magic::{black::load_black_magics, black_constants::*};
 
 type IndexableBitboardList = [Bitboard; 64];
 
+/// Attacks of every piece on an empty board, computed at compile time.
+pub static ATTACKS: PseudoAttacks = PseudoAttacks::new();
+/// Squares strictly between two squares on the same rank, file or diagonal, empty otherwise.
+pub static BETWEEN: [IndexableBitboardList; 64] = LINES.0;
+/// The whole rank, file or diagonal through two squares, empty if they aren't aligned.
+pub static LINE: [IndexableBitboardList; 64] = LINES.1;
+
+const LINES: ([IndexableBitboardList; 64], [IndexableBitboardList; 64]) = lines();
+
+/// The tables that can't be built at compile time: the sliding attacks, whose backend is
+/// selected by the crate features. See [`crate::pregen()`] and [`crate::init`].
 pub struct Pregen {
-    pub attacks: PseudoAttacks,
-    pub bishop_magics: MagicBitboard,
-    pub rook_magics: MagicBitboard,
+    pub bishop: Sliders,
+    pub rook: Sliders,
 }
 
+/// The sliding attack backend in use, picked by the build script: PEXT bitboards with the
+/// `pext` feature, when building for a CPU with BMI2, table free fills with the `kogge-stone`
+/// feature, black magics with the `black-magic` feature, and plain magics otherwise.
+#[cfg(sliders = "pext")]
+pub type Sliders = crate::pext::PextBitboard;
+#[cfg(sliders = "kogge-stone")]
+pub type Sliders = crate::fill::KoggeStone;
+#[cfg(sliders = "black-magic")]
+pub type Sliders = BlackMagicBitboard;
+#[cfg(sliders = "magic")]
+pub type Sliders = MagicBitboard;
+
 pub struct PseudoAttacks {
+    /// Pawn captures, indexed by the color of the pawn.
+    pub pawn: [IndexableBitboardList; 2],
     pub knight: IndexableBitboardList,
     pub bishop: IndexableBitboardList,
     pub rook: IndexableBitboardList,
@@ -21,48 +48,136 @@ pub struct PseudoAttacks {
 
 impl Pregen {
     pub fn init() -> Pregen {
-        let bishop = generate_magics(&MagicPiece::Bishop);
-        let rook = generate_magics(&MagicPiece::Rook);
         Pregen {
-            attacks: PseudoAttacks::init(&bishop, &rook),
-            bishop_magics: bishop,
-            rook_magics: rook,
+            bishop: sliders(&MagicPiece::Bishop),
+            rook: sliders(&MagicPiece::Rook),
         }
     }
 }
 
+#[cfg(sliders = "pext")]
+fn sliders(piece: &MagicPiece) -> Sliders {
+    Sliders::new(piece)
+}
+
+#[cfg(sliders = "kogge-stone")]
+fn sliders(piece: &MagicPiece) -> Sliders {
+    crate::fill::KoggeStone(*piece)
+}
+
+#[cfg(sliders = "black-magic")]
+fn sliders(piece: &MagicPiece) -> Sliders {
+    match piece {
+        MagicPiece::Bishop => load_black_magics(piece, &BLACK_BISHOP_MAGICS, BLACK_BISHOP_TABLE_SIZE),
+        MagicPiece::Rook => load_black_magics(piece, &BLACK_ROOK_MAGICS, BLACK_ROOK_TABLE_SIZE),
+    }
+}
+
+#[cfg(sliders = "magic")]
+fn sliders(piece: &MagicPiece) -> Sliders {
+    match piece {
+        MagicPiece::Bishop => load_magics(piece, &BISHOP_MAGICS, BISHOP_TABLE_SIZE),
+        MagicPiece::Rook => load_magics(piece, &ROOK_MAGICS, ROOK_TABLE_SIZE),
+    }
+}
+
+// (file, rank) steps, with each direction next to its opposite
+const KNIGHT_STEPS: [(i32, i32); 8] = [(1, 2), (-1, -2), (2, 1), (-2, -1), (2, -1), (-2, 1), (1, -2), (-1, 2)];
+const KING_STEPS: [(i32, i32); 8] = [(0, 1), (0, -1), (1, 0), (-1, 0), (1, 1), (-1, -1), (1, -1), (-1, 1)];
+const WHITE_PAWN_STEPS: [(i32, i32); 2] = [(-1, 1), (1, 1)];
+const BLACK_PAWN_STEPS: [(i32, i32); 2] = [(-1, -1), (1, -1)];
+
+/// The square `step` away from `sq`, or `None` if it's off the board.
+const fn step(sq: usize, step: (i32, i32)) -> Option<usize> {
+    let (file, rank) = ((sq % 8) as i32 + step.0, (sq / 8) as i32 + step.1);
+    if 0 <= file && file < 8 && 0 <= rank && rank < 8 {
+        Some((rank * 8 + file) as usize)
+    } else {
+        None
+    }
+}
+
+/// Squares reached from `sq` with a single step of each of `steps`.
+const fn leaper(sq: usize, steps: &[(i32, i32)]) -> Bitboard {
+    let mut attacks = 0;
+    let mut i = 0;
+    while i < steps.len() {
+        if let Some(to) = step(sq, steps[i]) {
+            attacks |= 1 << to;
+        }
+        i += 1;
+    }
+    Bitboard(attacks)
+}
+
+/// Squares reached from `sq` repeating `dir` up to the edge of the board, on an empty board.
+const fn ray(sq: usize, dir: (i32, i32)) -> u64 {
+    let mut attacks = 0;
+    let mut from = sq;
+    while let Some(to) = step(from, dir) {
+        attacks |= 1 << to;
+        from = to;
+    }
+    attacks
+}
+
+/// Squares reached from `sq` sliding in each of `dirs`, on an empty board.
+const fn slider(sq: usize, dirs: &[(i32, i32)]) -> Bitboard {
+    let mut attacks = 0;
+    let mut i = 0;
+    while i < dirs.len() {
+        attacks |= ray(sq, dirs[i]);
+        i += 1;
+    }
+    Bitboard(attacks)
+}
+
+/// Both the between and line tables: walking each ray out of `a`, every square `b` on it is
+/// preceded by the squares between them, and lies on that ray plus its opposite one.
+const fn lines() -> ([IndexableBitboardList; 64], [IndexableBitboardList; 64]) {
+    let mut between = [[Bitboard(0); 64]; 64];
+    let mut line = [[Bitboard(0); 64]; 64];
+    let mut a = 0;
+    while a < 64 {
+        let mut d = 0;
+        while d < KING_STEPS.len() {
+            let full = ray(a, KING_STEPS[d]) | ray(a, KING_STEPS[d ^ 1]) | (1 << a);
+            let mut walked = 0;
+            let mut from = a;
+            while let Some(b) = step(from, KING_STEPS[d]) {
+                between[a][b] = Bitboard(walked);
+                line[a][b] = Bitboard(full);
+                walked |= 1 << b;
+                from = b;
+            }
+            d += 1;
+        }
+        a += 1;
+    }
+    (between, line)
+}
+
 impl PseudoAttacks {
     const fn new() -> PseudoAttacks {
-        PseudoAttacks {
+        let mut attacks = PseudoAttacks {
+            pawn: [[Bitboard(0); 64]; 2],
             knight: [Bitboard(0); 64],
             bishop: [Bitboard(0); 64],
             rook: [Bitboard(0); 64],
             queen: [Bitboard(0); 64],
             king: [Bitboard(0); 64],
-        }
-    }
-
-    pub fn init(bishop_magics:& MagicBitboard, rook_magics: &MagicBitboard) -> PseudoAttacks {
-        let mut attacks = PseudoAttacks::new();
-        for sq in Square::all() {
-            // calculate knight attacks
-            for step in [-17, -15, -10, -6, 6, 10, 15, 17] {
-                if !sq.safe_step(step) {
-                    continue;
-                };
-                attacks.knight[sq + step] |= Bitboard::square(sq);
-            }
-            // calculate king attacks
-            for step in [-9, -8, -7, -1, 1, 7, 8, 9] {
-                if !sq.safe_step(step) {
-                    continue;
-                };
-                attacks.king[sq + step] |= Bitboard::square(sq);
-            }
-
-            attacks.bishop[sq] = bishop_magics.get(sq)[Bitboard(0)];
-            attacks.rook[sq] = rook_magics.get(sq)[Bitboard(0)];
-            attacks.queen[sq] = attacks.bishop[sq] | attacks.rook[sq];
+        };
+        let mut sq = 0;
+        while sq < 64 {
+            attacks.pawn[Color::White as usize][sq] = leaper(sq, &WHITE_PAWN_STEPS);
+            attacks.pawn[Color::Black as usize][sq] = leaper(sq, &BLACK_PAWN_STEPS);
+            attacks.knight[sq] = leaper(sq, &KNIGHT_STEPS);
+            attacks.king[sq] = leaper(sq, &KING_STEPS);
+            // the king steps are the rook directions followed by the bishop ones
+            attacks.rook[sq] = slider(sq, KING_STEPS.split_at(4).0);
+            attacks.bishop[sq] = slider(sq, KING_STEPS.split_at(4).1);
+            attacks.queen[sq] = Bitboard(attacks.rook[sq].0 | attacks.bishop[sq].0);
+            sq += 1;
         }
         attacks
     }
@@ -0,0 +1,179 @@
+//! # Standard Algebraic Notation
+//!
+//! SAN is the move notation humans (and PGN files) use: `e4`, `Nxf3+`, `exd8=Q#`, `O-O`.
+//! Unlike UCI notation, a SAN move only makes sense in the context of a position, as it only
+//! mentions the destination square and just enough of the origin to make it unambiguous.
+
+use crate::types::*;
+use crate::movegen::legal_moves;
+
+fn piece_letter(ptype: PieceType) -> Option<char> {
+    match ptype {
+        PieceType::Knight => Some('N'),
+        PieceType::Bishop => Some('B'),
+        PieceType::Rook => Some('R'),
+        PieceType::Queen => Some('Q'),
+        PieceType::King => Some('K'),
+        _ => None,
+    }
+}
+
+fn letter_piece(c: char) -> Option<PieceType> {
+    match c {
+        'N' => Some(PieceType::Knight),
+        'B' => Some(PieceType::Bishop),
+        'R' => Some(PieceType::Rook),
+        'Q' => Some(PieceType::Queen),
+        'K' => Some(PieceType::King),
+        _ => None,
+    }
+}
+
+/// Format a legal move in SAN, including the check (`+`) or checkmate (`#`) suffix.
+pub fn to_san(board: &Board, m: Move) -> String {
+    let mut san = String::new();
+    let piece = board.at(m.from);
+
+    if m.move_type == MoveType::Castle {
+        san += if m.to.file() > m.from.file() { "O-O" } else { "O-O-O" };
+    } else {
+        let capture = board.at(m.to) != Piece::None || m.move_type == MoveType::EnPassant;
+        match piece_letter(piece.ptype()) {
+            Some(letter) => {
+                san.push(letter);
+                // other pieces of the same kind that could also move to the target square
+                let others: Vec<Move> = legal_moves(board)
+                    .into_iter()
+                    .filter(|o| o.to == m.to && o.from != m.from && board.at(o.from) == piece)
+                    .collect();
+                if !others.is_empty() {
+                    let name = m.from.algebraic();
+                    if others.iter().all(|o| o.from.file() != m.from.file()) {
+                        san += &name[..1];
+                    } else if others.iter().all(|o| o.from.rank() != m.from.rank()) {
+                        san += &name[1..];
+                    } else {
+                        san += &name;
+                    }
+                }
+            }
+            None => {
+                if capture {
+                    san += &m.from.algebraic()[..1];
+                }
+            }
+        }
+        if capture {
+            san.push('x');
+        }
+        san += &m.to.algebraic();
+        if let MoveType::Promotion(ptype) = m.move_type {
+            san.push('=');
+            san.push(piece_letter(ptype).expect("Promotion piece should have a letter"));
+        }
+    }
+
+    let mut after = board.clone();
+    after.make_move(m);
+    if after.in_check() {
+        san.push(if legal_moves(&after).is_empty() { '#' } else { '+' });
+    }
+    san
+}
+
+/// Parse a SAN move in the context of `board`, returning the matching legal move.
+///
+/// The parser is lenient: check/mate markers and annotation suffixes are ignored,
+/// castling may be written with zeroes, and the `=` before a promotion piece is optional.
+pub fn parse_san(board: &Board, san: &str) -> Result<Move, String> {
+    let trimmed = san.trim_end_matches(['+', '#', '!', '?']);
+    let moves = legal_moves(board);
+
+    if matches!(trimmed, "O-O" | "0-0" | "O-O-O" | "0-0-0") {
+        let kingside = trimmed.len() == 3;
+        return moves
+            .into_iter()
+            .find(|m| m.move_type == MoveType::Castle && (m.to.file() > m.from.file()) == kingside)
+            .ok_or(format!("Illegal castling move: {}", san));
+    }
+
+    let mut chars: Vec<char> = trimmed.chars().collect();
+
+    let ptype = match chars.first().copied().and_then(letter_piece) {
+        Some(ptype) => {
+            chars.remove(0);
+            ptype
+        }
+        None => PieceType::Pawn,
+    };
+
+    let mut promotion = None;
+    if let Some(ptype) = chars.last().copied().and_then(letter_piece) {
+        chars.pop();
+        if chars.last() == Some(&'=') {
+            chars.pop();
+        }
+        promotion = Some(ptype);
+    }
+
+    if chars.len() < 2 {
+        return Err(format!("Invalid SAN move: {}", san));
+    }
+    let target: String = chars[chars.len() - 2..].iter().collect();
+    let to: Square = target.parse().map_err(|_| format!("Invalid SAN move: {}", san))?;
+
+    // whatever is left is disambiguation, possibly followed by a capture marker
+    let mut from_file = None;
+    let mut from_rank = None;
+    for c in chars[..chars.len() - 2].iter().filter(|c| **c != 'x' && **c != '-') {
+        if let Ok(file) = File::try_from(*c) {
+            from_file = Some(file);
+        } else if let Ok(rank) = Rank::try_from(*c) {
+            from_rank = Some(rank);
+        } else {
+            return Err(format!("Invalid SAN move: {}", san));
+        }
+    }
+
+    let mut candidates = moves.into_iter().filter(|m| {
+        m.to == to
+            && m.move_type != MoveType::Castle
+            && board.at(m.from).ptype() == ptype
+            && from_file.is_none_or(|f| m.from.file() == f)
+            && from_rank.is_none_or(|r| m.from.rank() == r)
+            && match m.move_type {
+                MoveType::Promotion(p) => promotion == Some(p),
+                _ => promotion.is_none(),
+            }
+    });
+
+    match (candidates.next(), candidates.next()) {
+        (Some(m), None) => Ok(m),
+        (None, _) => Err(format!("Illegal SAN move: {}", san)),
+        (Some(_), Some(_)) => Err(format!("Ambiguous SAN move: {}", san)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::san::*;
+
+    #[test]
+    fn test_san_roundtrip() {
+        let board = Board::from("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        for m in crate::movegen::legal_moves(&board) {
+            let san = to_san(&board, m);
+            assert_eq!(parse_san(&board, &san), Ok(m), "{}", san);
+        }
+
+        let board = Board::from("4k3/1P6/8/8/8/8/8/R3K2R w KQ - 0 1");
+        assert_eq!(to_san(&board, parse_san(&board, "O-O-O").unwrap()), "O-O-O");
+        assert_eq!(to_san(&board, parse_san(&board, "b8Q").unwrap()), "b8=Q+");
+        assert_eq!(to_san(&board, parse_san(&board, "Ra8").unwrap()), "Ra8+");
+
+        let board = Board::from("6k1/5ppp/8/8/8/8/8/N1N1R1K1 w - - 0 1");
+        assert!(parse_san(&board, "Nb3").is_err());
+        assert_eq!(to_san(&board, parse_san(&board, "Nab3").unwrap()), "Nab3");
+        assert_eq!(to_san(&board, parse_san(&board, "Re8").unwrap()), "Re8#");
+    }
+}
@@ -0,0 +1,552 @@
+//! # Search
+//!
+//! An iterative deepening principal variation search (negamax alpha-beta, with null windows
+//! after the first move) and a quiescence search at the leaves, so that the static evaluation is
+//! only ever taken in quiet positions.
+//!
+//! Each iteration searches an aspiration window around the previous score. Away from the
+//! principal variation, positions far above beta are cut with reverse futility pruning and null
+//! move pruning, and late quiet moves are searched to a reduced depth first. Quiet moves are
+//! ordered by their history. All the constants involved are in [`SearchParams`], and can be
+//! changed with [`Search::with_params`].
+//!
+//! Positions are evaluated with the hand-crafted evaluation, unless a network is given with
+//! [`Search::with_network`]. Its accumulators are then kept up to date move by move.
+//!
+//! ### Links
+//! - [Alpha-Beta](https://www.chessprogramming.org/Alpha-Beta)
+//! - [Quiescence Search](https://www.chessprogramming.org/Quiescence_Search)
+//! - [Principal Variation Search](https://www.chessprogramming.org/Principal_Variation_Search)
+//! - [Null Move Pruning](https://www.chessprogramming.org/Null_Move_Pruning)
+//! - [Late Move Reductions](https://www.chessprogramming.org/Late_Move_Reductions)
+//! - [History Heuristic](https://www.chessprogramming.org/History_Heuristic)
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::types::*;
+use crate::eval::{evaluate, PIECE_VALUES};
+use crate::movegen::legal_moves;
+use crate::nnue::{AccumulatorStack, Network};
+use crate::tunables::SearchParams;
+
+/// Score of a checkmate at the root. Mates further away score closer to zero.
+pub const MATE: i32 = 30000;
+const INFINITY: i32 = 32000;
+const MAX_PLY: usize = 128;
+/// History scores stay within plus or minus this.
+const HISTORY_MAX: i32 = 16384;
+
+/// When the search should stop. Unset limits are ignored; with none set, the search runs to `MAX_PLY`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    pub depth: Option<u32>,
+    pub time: Option<Duration>,
+    pub nodes: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    /// Score in centipawns from the point of view of the side to move.
+    pub score: i32,
+    /// The last fully searched depth.
+    pub depth: u32,
+    pub nodes: u64,
+    pub pv: Vec<Move>,
+}
+
+pub struct Search {
+    limits: Limits,
+    start: Instant,
+    nodes: u64,
+    stopped: bool,
+    /// Set from outside the search to stop it, e.g. by the UCI `stop` command.
+    stop: Arc<AtomicBool>,
+    /// The principal variation found at each ply.
+    pv: Vec<Vec<Move>>,
+    /// The network evaluating positions, if any, and its accumulators.
+    nnue: Option<(Arc<Network>, AccumulatorStack)>,
+    params: SearchParams,
+    /// Late move reductions, by depth and move number.
+    reductions: Vec<[i32; 64]>,
+    /// How often each quiet move, by side, origin and destination, has caused a cutoff.
+    history: Box<[[[i32; 64]; 64]; 2]>,
+    /// Whether each move played on the way to the current position was a null move.
+    null_moves: Vec<bool>,
+}
+
+impl Limits {
+    pub fn depth(depth: u32) -> Limits {
+        Limits {
+            depth: Some(depth),
+            ..Default::default()
+        }
+    }
+
+    pub fn time(time: Duration) -> Limits {
+        Limits {
+            time: Some(time),
+            ..Default::default()
+        }
+    }
+
+    pub fn nodes(nodes: u64) -> Limits {
+        Limits {
+            nodes: Some(nodes),
+            ..Default::default()
+        }
+    }
+}
+
+/// Search `board` within the given limits and return the best move found.
+pub fn search(board: &Board, limits: &Limits) -> SearchResult {
+    Search::new(*limits).run(board)
+}
+
+/// The quiet position the quiescence search of `board` gets its score from, at the end of its
+/// principal variation, so that its static evaluation is the quiescence score. Used for tuning
+/// the evaluation on quiet positions only.
+pub fn resolve(board: &Board) -> Board {
+    let mut search = Search::new(Limits::default());
+    search.quiescence(board, 0, -INFINITY, INFINITY);
+    let mut resolved = board.clone();
+    for m in &search.pv[0] {
+        resolved.make_move(*m);
+    }
+    resolved
+}
+
+/// Move a history score towards `bonus`, by less the closer it already is to the limit.
+#[inline]
+fn update_history(entry: &mut i32, bonus: i32) {
+    *entry += bonus - *entry * bonus.abs() / HISTORY_MAX;
+}
+
+/// Checks if a score is a forced mate, for either side.
+#[inline]
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE - MAX_PLY as i32
+}
+
+/// Checks if the move captures something or promotes.
+#[inline]
+pub(crate) fn is_tactical(board: &Board, m: Move) -> bool {
+    match m.move_type {
+        MoveType::Quiet => board.at(m.to) != Piece::None,
+        // castling moves land on the king's own rook
+        MoveType::Castle => false,
+        MoveType::EnPassant | MoveType::Promotion(_) => true,
+    }
+}
+
+/// Checks if `color` has pieces other than pawns and its king, without which null moves are
+/// unsafe (zugzwang is common).
+#[inline]
+fn has_non_pawn_material(board: &Board, color: Color) -> bool {
+    let pawns_and_king = board.by_piece_type(PieceType::Pawn) | board.by_piece_type(PieceType::King);
+    board.by_color(color) & !pawns_and_king != Bitboard(0)
+}
+
+/// Order moves by most valuable victim / least valuable attacker, with quiet moves last, by
+/// their history.
+fn order_moves(board: &Board, moves: &mut [Move], first: Option<Move>, history: &[[i32; 64]; 64]) {
+    moves.sort_by_cached_key(|m| {
+        if Some(*m) == first {
+            return i32::MIN;
+        }
+        let victim = match m.move_type {
+            MoveType::EnPassant => PIECE_VALUES[PieceType::Pawn as usize],
+            MoveType::Castle => 0,
+            _ => PIECE_VALUES[board.at(m.to).ptype() as usize],
+        };
+        let promotion = match m.move_type {
+            MoveType::Promotion(ptype) => PIECE_VALUES[ptype as usize],
+            _ => 0,
+        };
+        let attacker = PIECE_VALUES[board.at(m.from).ptype() as usize] / 100;
+        if victim + promotion > 0 {
+            -(victim + promotion) * 10 + attacker
+        } else {
+            2 * HISTORY_MAX - history[m.from as usize][m.to as usize]
+        }
+    });
+}
+
+impl Search {
+    pub fn new(limits: Limits) -> Search {
+        Search {
+            limits,
+            start: Instant::now(),
+            nodes: 0,
+            stopped: false,
+            stop: Arc::new(AtomicBool::new(false)),
+            pv: vec![vec![]; MAX_PLY],
+            nnue: None,
+            params: SearchParams::default(),
+            reductions: vec![],
+            history: Box::new([[[0; 64]; 64]; 2]),
+            null_moves: vec![],
+        }
+        .with_params(SearchParams::default())
+    }
+
+    /// Search with the given parameters instead of the defaults.
+    pub fn with_params(mut self, params: SearchParams) -> Search {
+        let (base, divisor) = (params.lmr_base as f64 / 100.0, params.lmr_divisor as f64 / 100.0);
+        self.reductions = (0..64)
+            .map(|depth| {
+                std::array::from_fn(|moves| match (depth, moves) {
+                    (0, _) | (_, 0) => 0,
+                    _ => (base + (depth as f64).ln() * (moves as f64).ln() / divisor) as i32,
+                })
+            })
+            .collect();
+        self.params = params;
+        self
+    }
+
+    /// Stop the search as soon as `stop` is set, keeping the best move of the last full iteration.
+    pub fn with_stop(mut self, stop: Arc<AtomicBool>) -> Search {
+        self.stop = stop;
+        self
+    }
+
+    /// Evaluate positions with `net` instead of the hand-crafted evaluation.
+    pub fn with_network(mut self, net: Arc<Network>) -> Search {
+        let stack = AccumulatorStack::new(&net, &Board::new());
+        self.nnue = Some((net, stack));
+        self
+    }
+
+    pub fn run(&mut self, board: &Board) -> SearchResult {
+        self.start = Instant::now();
+        self.nodes = 0;
+        self.stopped = false;
+        *self.history = [[[0; 64]; 64]; 2];
+        self.null_moves.clear();
+        if let Some((net, stack)) = &mut self.nnue {
+            stack.reset(net, board);
+        }
+
+        let mut result = SearchResult {
+            best_move: legal_moves(board).first().copied(),
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            pv: vec![],
+        };
+        let max_depth = self.limits.depth.unwrap_or(MAX_PLY as u32 - 1).min(MAX_PLY as u32 - 1);
+
+        for depth in 1..=max_depth {
+            let score = self.aspiration(board, depth as i32, result.score);
+            if self.stopped {
+                break;
+            }
+            let pv = self.pv[0].clone();
+            result.best_move = pv.first().copied().or(result.best_move);
+            result.score = score;
+            result.depth = depth;
+            result.pv = pv;
+            // no point in searching deeper once a forced mate is found
+            if score.abs() >= MATE - depth as i32 {
+                break;
+            }
+        }
+        result.nodes = self.nodes;
+        result
+    }
+
+    /// Search the root in a window around the previous iteration's score, widening it on the
+    /// side the score falls out of until it lands inside.
+    fn aspiration(&mut self, board: &Board, depth: i32, previous: i32) -> i32 {
+        let mut delta = self.params.aspiration_delta;
+        let (mut alpha, mut beta) = if depth >= 4 {
+            ((previous - delta).max(-INFINITY), (previous + delta).min(INFINITY))
+        } else {
+            (-INFINITY, INFINITY)
+        };
+        loop {
+            let score = self.negamax(board, depth, 0, alpha, beta);
+            if self.stopped {
+                return score;
+            }
+            if score <= alpha {
+                alpha = (score - delta).max(-INFINITY);
+            } else if score >= beta {
+                beta = (score + delta).min(INFINITY);
+            } else {
+                return score;
+            }
+            delta *= 2;
+        }
+    }
+
+    #[inline]
+    fn should_stop(&mut self) -> bool {
+        if self.nodes & 1023 == 0 {
+            let out_of_time = self.limits.time.is_some_and(|t| self.start.elapsed() >= t);
+            let out_of_nodes = self.limits.nodes.is_some_and(|n| self.nodes >= n);
+            self.stopped |= out_of_time || out_of_nodes || self.stop.load(Ordering::Relaxed);
+        }
+        self.stopped
+    }
+
+    fn negamax(&mut self, board: &Board, depth: i32, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        // at the root, the best move of the previous iteration is searched first
+        let previous_best = self.pv[ply].first().copied().filter(|_| ply == 0);
+        self.pv[ply].clear();
+        if depth <= 0 || ply >= MAX_PLY - 1 {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
+        if ply > 0 && board.halfmove_clock >= 100 {
+            return 0;
+        }
+
+        let mut moves = legal_moves(board);
+        let in_check = board.in_check();
+        if moves.is_empty() {
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+
+        let pv_node = beta - alpha > 1;
+        if ply > 0 && !pv_node && !in_check && !is_mate_score(beta) {
+            let eval = self.evaluate(board);
+            // reverse futility pruning: far enough above beta that nothing will bring it back
+            if depth <= self.params.rfp_depth && eval - self.params.rfp_margin * depth >= beta {
+                return eval;
+            }
+            // null move pruning: if passing still fails high, a real move would too
+            let after_null = self.null_moves.last() == Some(&true);
+            if depth >= self.params.nmp_min_depth && eval >= beta && !after_null
+                && has_non_pawn_material(board, board.side_to_move)
+            {
+                let reduction = self.params.nmp_base + depth / self.params.nmp_divisor;
+                let after = self.make_null_move(board);
+                let score = -self.negamax(&after, depth - 1 - reduction, ply + 1, -beta, -beta + 1);
+                self.unmake_move();
+                if self.stopped {
+                    return 0;
+                }
+                if score >= beta {
+                    return if is_mate_score(score) { beta } else { score };
+                }
+            }
+        }
+
+        let side = board.side_to_move as usize;
+        order_moves(board, &mut moves, previous_best, &self.history[side]);
+
+        let mut best = -INFINITY;
+        let mut quiets: Vec<Move> = vec![];
+        for (i, m) in moves.into_iter().enumerate() {
+            let quiet = !is_tactical(board, m);
+            let after = self.make_move(board, m);
+            let score = if i == 0 {
+                -self.negamax(&after, depth - 1, ply + 1, -beta, -alpha)
+            } else {
+                let reduction = if depth >= 3 && quiet && i >= self.params.lmr_min_moves as usize
+                    && !in_check && !after.in_check()
+                {
+                    self.reductions[depth.min(63) as usize][(i + 1).min(63)].clamp(0, depth - 2)
+                } else {
+                    0
+                };
+                let mut score = -self.negamax(&after, depth - 1 - reduction, ply + 1, -alpha - 1, -alpha);
+                if score > alpha && reduction > 0 {
+                    score = -self.negamax(&after, depth - 1, ply + 1, -alpha - 1, -alpha);
+                }
+                if score > alpha && score < beta {
+                    score = -self.negamax(&after, depth - 1, ply + 1, -beta, -alpha);
+                }
+                score
+            };
+            self.unmake_move();
+            if self.stopped {
+                return 0;
+            }
+            if score > best {
+                best = score;
+                if score > alpha {
+                    alpha = score;
+                    self.update_pv(ply, m);
+                }
+                if score >= beta {
+                    if quiet {
+                        let bonus = (self.params.history_bonus * depth * depth).min(HISTORY_MAX);
+                        let history = &mut self.history[side];
+                        update_history(&mut history[m.from as usize][m.to as usize], bonus);
+                        for q in &quiets {
+                            update_history(&mut history[q.from as usize][q.to as usize], -bonus);
+                        }
+                    }
+                    break;
+                }
+            }
+            if quiet {
+                quiets.push(m);
+            }
+        }
+        best
+    }
+
+    fn quiescence(&mut self, board: &Board, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.pv[ply].clear();
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
+
+        let stand_pat = self.evaluate(board);
+        if stand_pat >= beta || ply >= MAX_PLY - 1 {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let mut moves: Vec<Move> = legal_moves(board).into_iter().filter(|m| is_tactical(board, *m)).collect();
+        order_moves(board, &mut moves, None, &self.history[board.side_to_move as usize]);
+
+        let mut best = stand_pat;
+        for m in moves {
+            let after = self.make_move(board, m);
+            let score = -self.quiescence(&after, ply + 1, -beta, -alpha);
+            self.unmake_move();
+            if self.stopped {
+                return 0;
+            }
+            if score > best {
+                best = score;
+                if score > alpha {
+                    alpha = score;
+                    self.update_pv(ply, m);
+                }
+                if score >= beta {
+                    break;
+                }
+            }
+        }
+        best
+    }
+
+    /// Play `m` on a copy of `board`, keeping the accumulators in step.
+    #[inline]
+    fn make_move(&mut self, board: &Board, m: Move) -> Board {
+        if let Some((net, stack)) = &mut self.nnue {
+            stack.push(net, board, m);
+        }
+        self.null_moves.push(false);
+        let mut after = board.clone();
+        after.make_move(m);
+        after
+    }
+
+    /// Pass the turn on a copy of `board`.
+    #[inline]
+    fn make_null_move(&mut self, board: &Board) -> Board {
+        if let Some((_, stack)) = &mut self.nnue {
+            stack.push_null();
+        }
+        self.null_moves.push(true);
+        let mut after = board.clone();
+        after.side_to_move = !after.side_to_move;
+        after.en_passant = None;
+        after.halfmove_clock += 1;
+        after
+    }
+
+    #[inline]
+    fn unmake_move(&mut self) {
+        if let Some((_, stack)) = &mut self.nnue {
+            stack.pop();
+        }
+        self.null_moves.pop();
+    }
+
+    #[inline]
+    fn evaluate(&self, board: &Board) -> i32 {
+        match &self.nnue {
+            Some((net, stack)) => net.evaluate(stack.current(), board.side_to_move),
+            None => evaluate(board),
+        }
+    }
+
+    fn update_pv(&mut self, ply: usize, m: Move) {
+        let child = std::mem::take(&mut self.pv[ply + 1]);
+        self.pv[ply].clear();
+        self.pv[ply].push(m);
+        self.pv[ply].extend(&child);
+        self.pv[ply + 1] = child;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::search::*;
+
+    #[test]
+    fn test_search_mate() {
+        // back rank mate in one
+        let board = Board::from("6k1/5ppp/8/8/8/8/8/4R1K1 w - - 0 1");
+        let result = search(&board, &Limits::depth(3));
+        assert_eq!(result.best_move, Some(Move::new(Square::E1, Square::E8, MoveType::Quiet)));
+        assert_eq!(result.score, MATE - 1);
+
+        // free queen
+        let board = Board::from("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1");
+        let result = search(&board, &Limits::depth(2));
+        assert_eq!(result.best_move, Some(Move::new(Square::D2, Square::D5, MoveType::Quiet)));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let white_eval = |board: &Board, score: i32| match board.side_to_move {
+            Color::White => score,
+            Color::Black => -score,
+        };
+        for fen in [
+            crate::BOARD_START_FEN,
+            "4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2",
+        ] {
+            let board = Board::from(fen);
+            let score = Search::new(Limits::default()).quiescence(&board, 0, -INFINITY, INFINITY);
+            let resolved = resolve(&board);
+            assert_eq!(white_eval(&resolved, evaluate(&resolved)), white_eval(&board, score), "{}", fen);
+        }
+        // nothing to capture
+        assert_eq!(resolve(&Board::from(crate::BOARD_START_FEN)).fen(), crate::BOARD_START_FEN);
+        // the hanging queen is taken
+        assert_eq!(resolve(&Board::from("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1")).by_piece_type(PieceType::Queen), Bitboard(0));
+    }
+
+    #[test]
+    fn test_search_nnue() {
+        use crate::nnue::{Activation, Network};
+
+        // a network that only counts material: each accumulator is 128 plus its side's material
+        // minus the other's, and the output is their difference
+        let mut net = Network::new(1, Activation::CReLU);
+        net.feature_bias[0] = 128;
+        for (ptype, value) in [(0, 1), (1, 3), (2, 3), (3, 5), (4, 9)] {
+            for sq in 0..64 {
+                net.feature_weights[ptype * 64 + sq] = value;
+                net.feature_weights[384 + ptype * 64 + sq] = -value;
+            }
+        }
+        net.output_weights = vec![100, -100];
+        let board = Board::from("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1");
+        let net = Arc::new(net);
+        assert_eq!(net.evaluate_board(&board), -4 * 200 * 400 / (255 * 64));
+
+        let mut search = Search::new(Limits::depth(3)).with_network(net.clone());
+        let result = search.run(&board);
+        assert_eq!(result.best_move, Some(Move::new(Square::D2, Square::D5, MoveType::Quiet)));
+        assert_eq!(result.score, 5 * 200 * 400 / (255 * 64));
+    }
+}
@@ -0,0 +1,209 @@
+//! # SPSA Tuning
+//!
+//! A local driver to tune the [search parameters](crate::tunables) by simultaneous perturbation
+//! stochastic approximation. Every iteration, all the parameters are nudged at once, each up or
+//! down at random, into two opposite versions of the engine, which then play a mini-match at a
+//! fixed number of nodes per move. The parameters move towards the version that scored better.
+//!
+//! The schedule follows OpenBench's, so that runs can be compared with its workloads: with `N`
+//! iterations, each parameter's perturbation shrinks to its `step` (`c_end`) and its learning
+//! rate to [`R_END`] by the last one.
+//!
+//! Games are played in-process, between two [`Search`]es with different parameters, so no
+//! engine binary is needed. Each opening, random moves from the start position, is played twice
+//! with the colors swapped.
+//!
+//! ### Links
+//! - [SPSA](https://www.jhuapl.edu/SPSA/)
+//! - [OpenBench SPSA](https://github.com/AndyGrant/OpenBench/wiki/SPSA-Tuning-Workloads)
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::types::*;
+use crate::datagen::{game_over, random_opening, GameResult};
+use crate::search::{Limits, Search};
+use crate::tunables::{SearchParams, R_END, TUNABLES};
+use crate::util::PRNG;
+
+#[derive(Debug, Clone)]
+pub struct SpsaOptions {
+    pub iterations: usize,
+    /// Game pairs per mini-match.
+    pub pairs: usize,
+    pub threads: usize,
+    /// Nodes searched for every move.
+    pub nodes: u64,
+    /// Random moves from the start position, for each opening.
+    pub random_plies: usize,
+    /// Games still going after this many plies are drawn.
+    pub max_plies: usize,
+    pub seed: u64,
+    /// Decay exponent of the learning rate.
+    pub alpha: f64,
+    /// Decay exponent of the perturbation.
+    pub gamma: f64,
+}
+
+/// The score of a mini-match, for the version perturbed upwards.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchScore {
+    pub wins: usize,
+    pub losses: usize,
+    pub draws: usize,
+}
+
+impl Default for SpsaOptions {
+    fn default() -> SpsaOptions {
+        SpsaOptions {
+            iterations: 1000,
+            pairs: 8,
+            threads: 1,
+            nodes: 2000,
+            random_plies: 8,
+            max_plies: 300,
+            seed: 1,
+            alpha: 0.602,
+            gamma: 0.101,
+        }
+    }
+}
+
+/// Round the parameter values and clamp them to their ranges.
+pub fn to_params(values: &[f64]) -> SearchParams {
+    let mut params = SearchParams::default();
+    for (t, v) in TUNABLES.iter().zip(values) {
+        params.set(t.name, (v.round() as i32).clamp(t.min, t.max)).unwrap();
+    }
+    params
+}
+
+/// Play a game from `opening`, searching `nodes` per move with each side's parameters.
+pub fn play_game(white: &SearchParams, black: &SearchParams, opening: &Board, nodes: u64, max_plies: usize) -> GameResult {
+    let mut board = opening.clone();
+    let mut seen = HashMap::new();
+    for _ in 0..max_plies {
+        if let Some(result) = game_over(&board, &mut seen) {
+            return result;
+        }
+        let params = match board.side_to_move {
+            Color::White => white,
+            Color::Black => black,
+        };
+        let found = Search::new(Limits::nodes(nodes)).with_params(params.clone()).run(&board);
+        board.make_move(found.best_move.expect("There should be a legal move"));
+    }
+    GameResult::Draw
+}
+
+/// Play `options.pairs` game pairs between `plus` and `minus`, from openings drawn with `rng`.
+pub fn mini_match(plus: &SearchParams, minus: &SearchParams, options: &SpsaOptions, rng: &mut PRNG) -> MatchScore {
+    let openings: Vec<Board> = (0..options.pairs)
+        .map(|_| loop {
+            if let Some(board) = random_opening(None, options.random_plies, rng) {
+                break board;
+            }
+        })
+        .collect();
+
+    let next = AtomicUsize::new(0);
+    let score = Mutex::new(MatchScore::default());
+    std::thread::scope(|scope| {
+        for _ in 0..options.threads.max(1) {
+            scope.spawn(|| loop {
+                let pair = next.fetch_add(1, Ordering::Relaxed);
+                let Some(opening) = openings.get(pair) else { break };
+                let first = play_game(plus, minus, opening, options.nodes, options.max_plies);
+                let second = play_game(minus, plus, opening, options.nodes, options.max_plies);
+                let mut score = score.lock().unwrap();
+                for (result, plus_color) in [(first, Color::White), (second, Color::Black)] {
+                    match result {
+                        GameResult::Draw => score.draws += 1,
+                        r if r == GameResult::win(plus_color) => score.wins += 1,
+                        _ => score.losses += 1,
+                    }
+                }
+            });
+        }
+    });
+    score.into_inner().unwrap()
+}
+
+/// Tune the search parameters, starting from their defaults. `progress` gets the iteration,
+/// the score of its mini-match and the values after its update. Returns the tuned values,
+/// unrounded (see [`to_params`]).
+pub fn spsa(options: &SpsaOptions, mut progress: impl FnMut(usize, MatchScore, &[f64])) -> Vec<f64> {
+    crate::init();
+    let n = options.iterations as f64;
+    let big_a = 0.1 * n;
+    // the perturbation and learning rate at the first iteration, so that they decay to the
+    // steps and R_END by the last one
+    let c: Vec<f64> = TUNABLES.iter().map(|t| t.step as f64 * n.powf(options.gamma)).collect();
+    let a: Vec<f64> = TUNABLES
+        .iter()
+        .map(|t| R_END * (t.step as f64).powi(2) * (big_a + n).powf(options.alpha))
+        .collect();
+
+    let mut rng = PRNG::new(options.seed.max(1));
+    let mut values: Vec<f64> = TUNABLES.iter().map(|t| t.default as f64).collect();
+    for k in 1..=options.iterations {
+        let c_k: Vec<f64> = c.iter().map(|c| c / (k as f64).powf(options.gamma)).collect();
+        let delta: Vec<f64> = (0..TUNABLES.len()).map(|_| if rng.next() & 1 == 0 { -1.0 } else { 1.0 }).collect();
+        let perturbed = |sign: f64| -> Vec<f64> {
+            values.iter().zip(&c_k).zip(&delta).map(|((v, c), d)| v + sign * c * d).collect()
+        };
+        let plus = to_params(&perturbed(1.0));
+        let minus = to_params(&perturbed(-1.0));
+
+        let score = mini_match(&plus, &minus, options, &mut rng);
+        let result = score.wins as f64 - score.losses as f64;
+        for (i, t) in TUNABLES.iter().enumerate() {
+            let a_k = a[i] / (big_a + k as f64).powf(options.alpha);
+            // r_k * c_k * result * delta, with r_k = a_k / c_k^2
+            values[i] = (values[i] + a_k / c_k[i] * result * delta[i]).clamp(t.min as f64, t.max as f64);
+        }
+        progress(k, score, &values);
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::spsa::*;
+
+    #[test]
+    fn test_spsa() {
+        let defaults = SearchParams::default();
+        let values: Vec<f64> = TUNABLES.iter().map(|t| t.default as f64 + 0.4).collect();
+        assert_eq!(to_params(&values), defaults);
+        let extreme: Vec<f64> = TUNABLES.iter().map(|t| t.max as f64 + 100.0).collect();
+        assert_eq!(to_params(&extreme).values(), TUNABLES.iter().map(|t| t.max).collect::<Vec<_>>());
+
+        // mate in one for white, whoever plays it
+        let board = Board::from("6k1/5ppp/8/8/8/8/8/4R1K1 w - - 0 1");
+        assert_eq!(play_game(&defaults, &defaults, &board, 1000, 10), GameResult::WhiteWin);
+        assert_eq!(play_game(&defaults, &defaults, &Board::from("8/8/4k3/8/8/3K4/8/8 w - - 0 1"), 1000, 10), GameResult::Draw);
+
+        let options = SpsaOptions {
+            iterations: 2,
+            pairs: 1,
+            threads: 2,
+            nodes: 200,
+            max_plies: 40,
+            ..Default::default()
+        };
+        let mut scores = vec![];
+        let tuned = spsa(&options, |k, score, _| scores.push((k, score)));
+        assert_eq!(tuned.len(), TUNABLES.len());
+        assert_eq!(scores.iter().map(|(k, _)| *k).collect::<Vec<_>>(), vec![1, 2]);
+        for (_, score) in &scores {
+            assert_eq!(score.wins + score.losses + score.draws, 2);
+        }
+        for (t, v) in TUNABLES.iter().zip(&tuned) {
+            assert!((t.min as f64..=t.max as f64).contains(v));
+        }
+        // the same seed tunes the same way
+        assert_eq!(spsa(&options, |_, _, _| {}), tuned);
+    }
+}
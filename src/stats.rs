@@ -0,0 +1,288 @@
+//! # Match Statistics
+//!
+//! Whether a change gains strength, from the results of a match: the Elo difference with its
+//! 95% confidence interval, the likelihood of superiority, and a sequential probability ratio
+//! test that can stop the match as soon as the answer is clear.
+//!
+//! Results are counted either per game ([`Wdl`], the trinomial model), or per color-reversed
+//! pair of games from the same opening ([`Pentanomial`], the pair scoring 0, ½, 1, 1½ or 2).
+//! Pairs are the better choice whenever the games come in pairs: the two games of a pair are
+//! correlated through their opening, which the trinomial model ignores, overestimating the
+//! variance.
+//!
+//! The SPRT is the GSPRT used by cutechess-cli, fishtest and OpenBench: it tests the logistic
+//! Elo difference `elo0` (H0) against `elo1` (H1), accepting one of them once the log
+//! likelihood ratio leaves `[ln(β / (1 - α)), ln((1 - β) / α)]`, with `α` and `β` the false
+//! positive and false negative rates.
+//!
+//! ### Links
+//! - [Match Statistics](https://www.chessprogramming.org/Match_Statistics)
+//! - [SPRT](https://www.chessprogramming.org/Sequential_Probability_Ratio_Test)
+//! - [GSPRT](https://hardy.uhasselt.be/Fishtest/support_MLE_multinomial.pdf)
+
+/// The standard normal quantile of a two-sided 95% confidence interval.
+pub const Z_95: f64 = 1.959963984540054;
+
+/// Game results, from one side's point of view.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Wdl {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+}
+
+/// Counts of game pairs by their score, in half points: `[0, ½, 1, 1½, 2]` points.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Pentanomial(pub [usize; 5]);
+
+/// An Elo difference with its 95% confidence interval.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EloEstimate {
+    pub elo: f64,
+    /// Half width of the confidence interval.
+    pub error: f64,
+    /// Likelihood of superiority: the probability that the difference is positive.
+    pub los: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SprtConfig {
+    pub elo0: f64,
+    pub elo1: f64,
+    /// False positive rate: accepting H1 when H0 holds.
+    pub alpha: f64,
+    /// False negative rate: accepting H0 when H1 holds.
+    pub beta: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hypothesis {
+    H0,
+    H1,
+}
+
+/// The state of an SPRT.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SprtReport {
+    /// Log likelihood ratio of H1 against H0.
+    pub llr: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+/// The Elo difference giving an expected `score`, between 0 and 1.
+pub fn elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// The expected score for an Elo difference.
+pub fn score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// The error function, to a precision of about 1e-7.
+pub fn erf(x: f64) -> f64 {
+    // erfc's Chebyshev fit, from Numerical Recipes
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = [
+        -1.26551223, 1.00002368, 0.37409196, 0.09678418, -0.18628806,
+        0.27886807, -1.13520398, 1.48851587, -0.82215223, 0.17087277,
+    ];
+    let sum = poly.iter().rev().fold(0.0, |acc, c| c + t * acc);
+    let erfc = t * (-z * z + sum).exp();
+    if x >= 0.0 { 1.0 - erfc } else { erfc - 1.0 }
+}
+
+/// The standard normal cumulative distribution function.
+pub fn phi(x: f64) -> f64 {
+    0.5 * (1.0 + erf(x / std::f64::consts::SQRT_2))
+}
+
+/// The number of samples, their mean and their variance, for a distribution of `scores` seen
+/// `counts` times each.
+fn moments(scores: &[f64], counts: &[usize]) -> (f64, f64, f64) {
+    let n = counts.iter().sum::<usize>() as f64;
+    let mean = scores.iter().zip(counts).map(|(s, c)| s * *c as f64).sum::<f64>() / n;
+    let variance = scores.iter().zip(counts).map(|(s, c)| (s - mean).powi(2) * *c as f64).sum::<f64>() / n;
+    (n, mean, variance)
+}
+
+/// The Elo difference and its interval, from samples scoring `mean` on average.
+fn estimate(n: f64, mean: f64, variance: f64, los: f64) -> EloEstimate {
+    let margin = Z_95 * (variance / n).sqrt();
+    EloEstimate {
+        elo: elo(mean),
+        error: (elo((mean + margin).min(1.0)) - elo((mean - margin).max(0.0))) / 2.0,
+        los,
+    }
+}
+
+/// The GSPRT log likelihood ratio, approximating the multinomial by a normal distribution.
+fn llr(n: f64, mean: f64, variance: f64, config: &SprtConfig) -> f64 {
+    if n == 0.0 || variance <= 0.0 {
+        return 0.0;
+    }
+    let (s0, s1) = (score(config.elo0), score(config.elo1));
+    n * (s1 - s0) * (2.0 * mean - s0 - s1) / (2.0 * variance)
+}
+
+impl Wdl {
+    /// Count a game scoring `points` half points: 0 for a loss, 1 for a draw, 2 for a win.
+    pub fn add(&mut self, points: usize) {
+        match points {
+            0 => self.losses += 1,
+            1 => self.draws += 1,
+            _ => self.wins += 1,
+        }
+    }
+
+    pub fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    pub fn points(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 / 2.0
+    }
+
+    pub fn draw_ratio(&self) -> f64 {
+        self.draws as f64 / self.games() as f64
+    }
+
+    fn moments(&self) -> (f64, f64, f64) {
+        moments(&[0.0, 0.5, 1.0], &[self.losses, self.draws, self.wins])
+    }
+
+    /// The Elo difference. The LOS only depends on the decisive games.
+    ///
+    /// # Example
+    /// ```
+    /// use tejuino::stats::Wdl;
+    ///
+    /// let estimate = Wdl { wins: 60, draws: 100, losses: 40 }.elo();
+    /// assert_eq!(format!("{:.1} +/- {:.1}", estimate.elo, estimate.error), "34.9 +/- 34.2");
+    /// assert!((estimate.los - 0.977).abs() < 1e-3);
+    /// ```
+    pub fn elo(&self) -> EloEstimate {
+        let (n, mean, variance) = self.moments();
+        let decisive = (self.wins + self.losses) as f64;
+        let los = phi((self.wins as f64 - self.losses as f64) / decisive.sqrt());
+        estimate(n, mean, variance, if decisive > 0.0 { los } else { 0.5 })
+    }
+
+    pub fn sprt(&self, config: &SprtConfig) -> SprtReport {
+        let (n, mean, variance) = self.moments();
+        SprtReport::new(llr(n, mean, variance, config), config)
+    }
+}
+
+impl Pentanomial {
+    /// Count a pair scoring `points` half points, from 0 to 4.
+    pub fn add(&mut self, points: usize) {
+        self.0[points.min(4)] += 1;
+    }
+
+    pub fn pairs(&self) -> usize {
+        self.0.iter().sum()
+    }
+
+    fn moments(&self) -> (f64, f64, f64) {
+        moments(&[0.0, 0.25, 0.5, 0.75, 1.0], &self.0)
+    }
+
+    /// The Elo difference, from the pairs' scores.
+    pub fn elo(&self) -> EloEstimate {
+        let (n, mean, variance) = self.moments();
+        let deviation = (variance / n).sqrt();
+        let los = if deviation > 0.0 { phi((mean - 0.5) / deviation) } else { 0.5 };
+        estimate(n, mean, variance, los)
+    }
+
+    pub fn sprt(&self, config: &SprtConfig) -> SprtReport {
+        let (n, mean, variance) = self.moments();
+        SprtReport::new(llr(n, mean, variance, config), config)
+    }
+}
+
+impl Default for SprtConfig {
+    fn default() -> SprtConfig {
+        SprtConfig { elo0: 0.0, elo1: 5.0, alpha: 0.05, beta: 0.05 }
+    }
+}
+
+impl SprtReport {
+    fn new(llr: f64, config: &SprtConfig) -> SprtReport {
+        SprtReport {
+            llr,
+            lower: (config.beta / (1.0 - config.alpha)).ln(),
+            upper: ((1.0 - config.beta) / config.alpha).ln(),
+        }
+    }
+
+    /// The hypothesis accepted, once the LLR is out of bounds.
+    pub fn decision(&self) -> Option<Hypothesis> {
+        if self.llr >= self.upper {
+            Some(Hypothesis::H1)
+        } else if self.llr <= self.lower {
+            Some(Hypothesis::H0)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stats::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn test_elo() {
+        for e in [-400.0, -35.0, 0.0, 10.0, 191.0] {
+            assert!(close(elo(score(e)), e));
+        }
+        assert!(close(score(0.0), 0.5) && close(elo(0.75), 190.8485));
+        assert!(close(erf(0.0), 0.0) && close(erf(1.0), 0.8427008) && close(erf(-2.0), -0.9953223));
+        assert!(close(phi(2.0), 0.9772499));
+
+        let wdl = Wdl { wins: 60, draws: 100, losses: 40 };
+        let estimate = wdl.elo();
+        assert!(close(estimate.elo, 34.86007) && close(estimate.error, 34.15927) && close(estimate.los, 0.9772499));
+        assert_eq!((wdl.games(), wdl.points(), wdl.draw_ratio()), (200, 110.0, 0.5));
+        let even = Wdl { wins: 10, draws: 10, losses: 10 }.elo();
+        assert!(close(even.elo, 0.0) && close(even.los, 0.5));
+        let mut counted = Wdl::default();
+        for points in [2, 1, 1, 0] {
+            counted.add(points);
+        }
+        assert_eq!(counted, Wdl { wins: 1, draws: 2, losses: 1 });
+
+        let pentanomial = Pentanomial([5, 20, 50, 30, 10]);
+        let estimate = pentanomial.elo();
+        assert!(close(estimate.elo, 30.28829) && close(estimate.error, 30.87287) && close(estimate.los, 0.9736767));
+        assert_eq!(pentanomial.pairs(), 115);
+    }
+
+    #[test]
+    fn test_sprt() {
+        let config = SprtConfig { elo0: 0.0, elo1: 10.0, alpha: 0.05, beta: 0.05 };
+        let report = Wdl { wins: 60, draws: 100, losses: 40 }.sprt(&config);
+        assert!(close(report.llr, 1.0054917));
+        assert!(close(report.lower, -2.9444390) && close(report.upper, 2.9444390));
+        assert_eq!(report.decision(), None);
+        assert!(close(Pentanomial([5, 20, 50, 30, 10]).sprt(&config).llr, 1.0369943));
+
+        // no information yet
+        assert_eq!(Wdl::default().sprt(&config).llr, 0.0);
+        assert_eq!(Wdl { draws: 10, ..Default::default() }.sprt(&config).llr, 0.0);
+
+        // the evidence piles up as the same results repeat
+        let strong = Pentanomial([10, 100, 300, 200, 50]).sprt(&config);
+        assert_eq!(strong.decision(), Some(Hypothesis::H1));
+        let weak = Pentanomial([50, 200, 300, 100, 10]).sprt(&config);
+        assert_eq!(weak.decision(), Some(Hypothesis::H0));
+    }
+}
@@ -0,0 +1,814 @@
+//! # Engine Matches
+//!
+//! A match runner in the spirit of cutechess-cli: UCI engines are started as child processes
+//! (see [`engine`]) and play each other round robin, with our own [`Board`] as the arbiter. Every
+//! move is checked for legality, and games are ended by the rules (mate, stalemate, threefold
+//! repetition, the fifty move rule, insufficient material), by a loss on time, an illegal move
+//! or a crash, or by adjudication:
+//!
+//! - resign: a side loses once its own score has been at or below `-score` for `move_count`
+//!   moves in a row.
+//! - draw: once both sides' scores have stayed within `score` of zero for `move_count` moves
+//!   each, from move `move_number` on.
+//! - move limit: games still going after `max_moves` moves are drawn.
+//!
+//! Openings come from an EPD or PGN file, and each is played [`MatchOptions::games`] times in
+//! a row between the same two engines, alternating colors, so that a color-reversed pair sees
+//! the same position from both sides. Games run [`MatchOptions::concurrency`] at a time, each
+//! worker keeping its engine processes from game to game, and restarting those that failed.
+//!
+//! Clocks follow cutechess' time controls: `[moves/]time[+increment]` in seconds, where the
+//! time is added back every `moves` moves, a fixed time per move, nodes or depth.
+//!
+//! ### Links
+//! - [cutechess-cli](https://github.com/cutechess/cutechess)
+//! - [PGN](https://www.thechessdrum.net/PGN_Reference.txt)
+
+pub mod engine;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::BOARD_START_FEN;
+use crate::types::*;
+use crate::book::polyglot_key;
+use crate::datagen::{insufficient_material, GameResult};
+use crate::epd::Epd;
+use crate::movegen::legal_moves;
+use crate::pgn::{Game, PgnReader};
+use crate::stats::{Pentanomial, Wdl};
+use crate::util::PRNG;
+use engine::Engine;
+
+/// Limits for each move, from the cutechess `tc`, `st`, `nodes` and `depth` options.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimeControl {
+    /// Moves per period, with `time` added back at the end of each. `None` for the whole game.
+    pub moves: Option<u32>,
+    /// Time per period, `None` to play without a clock.
+    pub time: Option<Duration>,
+    pub increment: Duration,
+    /// Fixed time per move.
+    pub movetime: Option<Duration>,
+    pub nodes: Option<u64>,
+    pub depth: Option<u32>,
+}
+
+/// How to start an engine and set it up.
+#[derive(Debug, Clone, Default)]
+pub struct EngineConfig {
+    pub name: String,
+    pub command: String,
+    pub args: Vec<String>,
+    /// Working directory of the process.
+    pub dir: Option<PathBuf>,
+    /// UCI options, as name and value.
+    pub options: Vec<(String, String)>,
+    pub time_control: TimeControl,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResignAdjudication {
+    pub move_count: u32,
+    /// Centipawns.
+    pub score: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrawAdjudication {
+    pub move_number: u32,
+    pub move_count: u32,
+    /// Centipawns.
+    pub score: i32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Adjudication {
+    pub resign: Option<ResignAdjudication>,
+    pub draw: Option<DrawAdjudication>,
+    pub max_moves: Option<u32>,
+}
+
+/// A starting position and the moves played from it before the engines take over.
+#[derive(Debug, Clone)]
+pub struct Opening {
+    pub board: Board,
+    pub moves: Vec<Move>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpeningFormat {
+    Epd,
+    Pgn,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchOptions {
+    pub engines: Vec<EngineConfig>,
+    /// Openings, used in turn for each encounter. The start position if there are none.
+    pub openings: Vec<Opening>,
+    /// Pick the openings at random instead of in order.
+    pub random_order: bool,
+    pub seed: u64,
+    /// Times every engine meets every other one.
+    pub rounds: usize,
+    /// Games per encounter, all from the same opening, with the colors alternating.
+    pub games: usize,
+    /// Games played at the same time.
+    pub concurrency: usize,
+    pub adjudication: Adjudication,
+    /// Play Chess960: sets `UCI_Chess960` and sends castling moves as king takes rook.
+    pub chess960: bool,
+    /// How much an engine can overrun its clock without losing on time.
+    pub time_margin: Duration,
+    /// How long an engine gets for a move without a clock or a fixed time, e.g. with a node or
+    /// depth limit, before it loses on time.
+    pub move_timeout: Duration,
+    /// The `Event` tag of the games.
+    pub event: String,
+}
+
+/// A game on the schedule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pairing {
+    /// Numbered from 1, in the order of the schedule.
+    pub number: usize,
+    /// Numbered from 1.
+    pub round: usize,
+    /// Numbered from 1. The games of an encounter have the same engines and opening.
+    pub encounter: usize,
+    /// Numbered from 1 within the encounter.
+    pub game: usize,
+    /// Indices into [`MatchOptions::engines`].
+    pub white: usize,
+    pub black: usize,
+    /// Index into the openings.
+    pub opening: usize,
+}
+
+/// How a game between two running engines went.
+#[derive(Debug, Clone)]
+pub struct PlayedGame {
+    pub result: GameResult,
+    /// Why the game ended, e.g. `White mates` or `Black loses on time`.
+    pub reason: String,
+    /// The PGN `Termination` tag: `normal`, `adjudication`, `time forfeit`, `rules infraction`
+    /// or `abandoned`.
+    pub termination: &'static str,
+    /// Every move from the opening's position, the opening moves included.
+    pub moves: Vec<Move>,
+    /// The side whose engine crashed, timed out or played an illegal move, and should be
+    /// restarted before playing again.
+    pub failed: Option<Color>,
+}
+
+/// A finished game of a match.
+#[derive(Debug, Clone)]
+pub struct GameOutcome {
+    pub pairing: Pairing,
+    pub result: GameResult,
+    pub reason: String,
+    /// The game record, with its tags.
+    pub game: Game,
+}
+
+impl TimeControl {
+    /// Whether every move is limited somehow, so that a game can't run forever.
+    pub fn is_limited(&self) -> bool {
+        self.time.is_some() || self.movetime.is_some() || self.nodes.is_some() || self.depth.is_some()
+    }
+
+    /// Moves left in the current period, after `played` moves.
+    fn moves_to_go(&self, played: u32) -> Option<u32> {
+        self.moves.map(|moves| moves - played % moves)
+    }
+}
+
+/// Parse a cutechess `tc`: `inf`, or `[moves/]time[+increment]` with the time in seconds or as
+/// `minutes:seconds`.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use tejuino::tournament::TimeControl;
+///
+/// let tc: TimeControl = "40/1:30+0.5".parse().unwrap();
+/// assert_eq!(tc.moves, Some(40));
+/// assert_eq!(tc.time, Some(Duration::from_secs(90)));
+/// assert_eq!(tc.increment, Duration::from_millis(500));
+/// assert_eq!(tc.to_string(), "40/90+0.5");
+/// ```
+impl FromStr for TimeControl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TimeControl, String> {
+        let invalid = || format!("Invalid time control: {}", s);
+        if s == "inf" {
+            return Ok(TimeControl::default());
+        }
+        let (moves, rest) = match s.split_once('/') {
+            Some((moves, rest)) => (Some(moves.parse().ok().filter(|m| *m > 0).ok_or_else(invalid)?), rest),
+            None => (None, s),
+        };
+        let (time, increment) = rest.split_once('+').unwrap_or((rest, "0"));
+        let seconds = match time.split_once(':') {
+            Some((minutes, seconds)) => {
+                minutes.parse::<f64>().map_err(|_| invalid())? * 60.0 + seconds.parse::<f64>().map_err(|_| invalid())?
+            }
+            None => time.parse().map_err(|_| invalid())?,
+        };
+        let increment: f64 = increment.parse().map_err(|_| invalid())?;
+        if !(seconds > 0.0 && increment >= 0.0 && seconds.is_finite() && increment.is_finite()) {
+            return Err(invalid());
+        }
+        Ok(TimeControl {
+            moves,
+            time: Some(Duration::from_secs_f64(seconds)),
+            increment: Duration::from_secs_f64(increment),
+            ..Default::default()
+        })
+    }
+}
+
+/// The clock as a PGN `TimeControl` tag, `-` without one.
+impl std::fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some(time) = self.time else {
+            return write!(f, "-");
+        };
+        if let Some(moves) = self.moves {
+            write!(f, "{}/", moves)?;
+        }
+        write!(f, "{}", time.as_secs_f64())?;
+        if self.increment > Duration::ZERO {
+            write!(f, "+{}", self.increment.as_secs_f64())?;
+        }
+        Ok(())
+    }
+}
+
+impl EngineConfig {
+    /// Start the engine, in Chess960 mode if `chess960`.
+    pub fn start(&self, chess960: bool) -> Result<Engine, String> {
+        let mut options = self.options.clone();
+        if chess960 {
+            options.push(("UCI_Chess960".to_string(), "true".to_string()));
+        }
+        Engine::start(&self.command, &self.args, self.dir.as_deref(), &options)
+            .map_err(|e| format!("{}: {}", self.name, e))
+    }
+}
+
+impl Opening {
+    /// The standard start position.
+    pub fn start() -> Opening {
+        Opening {
+            board: Board::from(BOARD_START_FEN),
+            moves: vec![],
+        }
+    }
+}
+
+impl FromStr for OpeningFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OpeningFormat, String> {
+        match s {
+            "epd" => Ok(OpeningFormat::Epd),
+            "pgn" => Ok(OpeningFormat::Pgn),
+            _ => Err(format!("Invalid opening format: {}", s)),
+        }
+    }
+}
+
+impl Default for MatchOptions {
+    fn default() -> MatchOptions {
+        MatchOptions {
+            engines: vec![],
+            openings: vec![],
+            random_order: false,
+            seed: 1,
+            rounds: 1,
+            games: 2,
+            concurrency: 1,
+            adjudication: Adjudication::default(),
+            chess960: false,
+            time_margin: Duration::from_millis(50),
+            move_timeout: Duration::from_secs(60),
+            event: "?".to_string(),
+        }
+    }
+}
+
+/// Read openings: every line of an EPD file, or the mainline of every game of a PGN file, cut
+/// to its first `plies` moves if given.
+pub fn read_openings<R: BufRead>(reader: R, format: OpeningFormat, plies: Option<usize>) -> Result<Vec<Opening>, String> {
+    let mut openings = vec![];
+    match format {
+        OpeningFormat::Epd => {
+            for line in reader.lines() {
+                let line = line.map_err(|e| e.to_string())?;
+                if !line.trim().is_empty() {
+                    openings.push(Opening { board: Epd::parse(&line)?.board, moves: vec![] });
+                }
+            }
+        }
+        OpeningFormat::Pgn => {
+            for game in PgnReader::new(reader) {
+                let game = game?;
+                let mut moves = game.mainline()?;
+                moves.truncate(plies.unwrap_or(usize::MAX));
+                openings.push(Opening { board: game.start_board()?, moves });
+            }
+        }
+    }
+    Ok(openings)
+}
+
+/// Read openings from a file, see [`read_openings`].
+pub fn load_openings<P: AsRef<Path>>(path: P, format: OpeningFormat, plies: Option<usize>) -> Result<Vec<Opening>, String> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    read_openings(BufReader::new(file), format, plies).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// The games of a match: for every round, every pair of engines meets once, playing
+/// `options.games` games from the same opening.
+pub fn schedule(options: &MatchOptions, openings: usize) -> Vec<Pairing> {
+    let mut rng = PRNG::new(options.seed.max(1));
+    let mut pairings = vec![];
+    let mut encounter = 0;
+    for round in 1..=options.rounds {
+        for first in 0..options.engines.len() {
+            for second in first + 1..options.engines.len() {
+                let opening = if options.random_order {
+                    (rng.next() % openings as u64) as usize
+                } else {
+                    encounter % openings
+                };
+                encounter += 1;
+                for game in 0..options.games {
+                    let (white, black) = if game % 2 == 0 { (first, second) } else { (second, first) };
+                    pairings.push(Pairing { number: pairings.len() + 1, round, encounter, game: game + 1, white, black, opening });
+                }
+            }
+        }
+    }
+    pairings
+}
+
+/// The `position` command for `moves` played from `start`.
+pub fn position_command(start: &Board, moves: &[Move], chess960: bool) -> String {
+    let fen = start.fen();
+    let mut command = if fen == BOARD_START_FEN {
+        "position startpos".to_string()
+    } else {
+        format!("position fen {}", fen)
+    };
+    if !moves.is_empty() {
+        command += " moves";
+        for m in moves {
+            command += " ";
+            command += &m.uci(chess960);
+        }
+    }
+    command
+}
+
+/// The result of the game at `board` by the rules, with the reason, if it's over. `seen`
+/// counts how many times each position of the game has been reached, and gets `board` added.
+fn rules_result(board: &Board, seen: &mut HashMap<u64, usize>) -> Option<(GameResult, String)> {
+    if legal_moves(board).is_empty() {
+        return Some(if board.in_check() {
+            let winner = !board.side_to_move;
+            (GameResult::win(winner), format!("{:?} mates", winner))
+        } else {
+            (GameResult::Draw, "Draw by stalemate".to_string())
+        });
+    }
+    let repetitions = seen.entry(polyglot_key(board)).or_default();
+    *repetitions += 1;
+    let reason = if *repetitions >= 3 {
+        "Draw by 3-fold repetition"
+    } else if board.halfmove_clock >= 100 {
+        "Draw by fifty moves rule"
+    } else if insufficient_material(board) {
+        "Draw by insufficient mating material"
+    } else {
+        return None;
+    };
+    Some((GameResult::Draw, reason.to_string()))
+}
+
+/// The `go` command for the side to move of `board`, given each side's time control, time left
+/// and moves played.
+fn go_command(board: &Board, tcs: [&TimeControl; 2], left: [Option<Duration>; 2], played: [u32; 2]) -> String {
+    let mut command = "go".to_string();
+    for (color, prefix) in [(Color::White, 'w'), (Color::Black, 'b')] {
+        let c = color as usize;
+        if let Some(left) = left[c] {
+            command += &format!(" {}time {} {}inc {}", prefix, left.as_millis(), prefix, tcs[c].increment.as_millis());
+        }
+    }
+    let stm = board.side_to_move as usize;
+    let tc = tcs[stm];
+    if let (Some(moves_to_go), Some(_)) = (tc.moves_to_go(played[stm]), left[stm]) {
+        command += &format!(" movestogo {}", moves_to_go);
+    }
+    if let Some(movetime) = tc.movetime {
+        command += &format!(" movetime {}", movetime.as_millis());
+    }
+    if let Some(nodes) = tc.nodes {
+        command += &format!(" nodes {}", nodes);
+    }
+    if let Some(depth) = tc.depth {
+        command += &format!(" depth {}", depth);
+    }
+    command
+}
+
+/// Play a game from `opening` between two running engines, white's first. `configs` give
+/// their time controls.
+pub fn play_game(engines: [&mut Engine; 2], configs: [&EngineConfig; 2], opening: &Opening, options: &MatchOptions) -> PlayedGame {
+    let tcs = [&configs[0].time_control, &configs[1].time_control];
+    let adjudication = &options.adjudication;
+    let mut board = opening.board.clone();
+    let mut seen = HashMap::new();
+    let mut moves = vec![];
+    for m in &opening.moves {
+        rules_result(&board, &mut seen);
+        board.make_move(*m);
+        moves.push(*m);
+    }
+
+    let end = |result, reason: String, termination, moves, failed| PlayedGame { result, reason, termination, moves, failed };
+    for color in [Color::White, Color::Black] {
+        if engines[color as usize].new_game().is_err() {
+            return end(GameResult::win(!color), format!("{:?} disconnects", color), "abandoned", moves, Some(color));
+        }
+    }
+
+    let mut left = [tcs[0].time, tcs[1].time];
+    let mut played = [0; 2];
+    let mut resign_count = [0; 2];
+    let mut draw_count = 0;
+    loop {
+        if let Some((result, reason)) = rules_result(&board, &mut seen) {
+            return end(result, reason, "normal", moves, None);
+        }
+        if adjudication.max_moves.is_some_and(|max| moves.len() >= 2 * max as usize) {
+            return end(GameResult::Draw, "Draw by adjudication: move limit".to_string(), "adjudication", moves, None);
+        }
+
+        let stm = board.side_to_move;
+        let s = stm as usize;
+        let allowed = match (tcs[s].movetime, left[s]) {
+            (Some(movetime), Some(left)) => Some(movetime.min(left)),
+            (movetime, left) => movetime.or(left),
+        }
+        .map_or(options.move_timeout, |t| t + options.time_margin);
+        let position = position_command(&opening.board, &moves, options.chess960);
+        let go = go_command(&board, tcs, left, played);
+        let start = Instant::now();
+        let reply = engines[s].go(&position, &go, Some(allowed));
+        let elapsed = start.elapsed();
+
+        if elapsed > allowed {
+            return end(GameResult::win(!stm), format!("{:?} loses on time", stm), "time forfeit", moves, Some(stm));
+        }
+        let reply = match reply {
+            Ok(reply) => reply,
+            Err(_) => return end(GameResult::win(!stm), format!("{:?} disconnects", stm), "abandoned", moves, Some(stm)),
+        };
+        let Ok(m) = board.parse_uci(&reply.uci, options.chess960) else {
+            let reason = format!("{:?} makes an illegal move: {}", stm, reply.uci);
+            return end(GameResult::win(!stm), reason, "rules infraction", moves, Some(stm));
+        };
+
+        if let Some(time) = left[s] {
+            let mut next = time.saturating_sub(elapsed) + tcs[s].increment;
+            if tcs[s].moves_to_go(played[s]) == Some(1) {
+                next += tcs[s].time.unwrap_or_default();
+            }
+            left[s] = Some(next);
+        }
+        played[s] += 1;
+        board.make_move(m);
+        moves.push(m);
+
+        let score = reply.score.map(|score| score.cp());
+        if let Some(resign) = adjudication.resign {
+            resign_count[s] = match score {
+                Some(score) if score <= -resign.score => resign_count[s] + 1,
+                _ => 0,
+            };
+            if resign_count[s] >= resign.move_count {
+                let reason = format!("{:?} wins by adjudication", !stm);
+                return end(GameResult::win(!stm), reason, "adjudication", moves, None);
+            }
+        }
+        if let Some(draw) = adjudication.draw {
+            draw_count = match score {
+                Some(score) if board.fullmove_number >= draw.move_number && score.abs() <= draw.score => draw_count + 1,
+                _ => 0,
+            };
+            if draw_count >= 2 * draw.move_count {
+                return end(GameResult::Draw, "Draw by adjudication".to_string(), "adjudication", moves, None);
+            }
+        }
+    }
+}
+
+/// The game result as the PGN termination marker.
+fn result_marker(result: GameResult) -> &'static str {
+    match result {
+        GameResult::WhiteWin => "1-0",
+        GameResult::BlackWin => "0-1",
+        GameResult::Draw => "1/2-1/2",
+    }
+}
+
+/// Today's date in the PGN format, `YYYY.MM.DD`.
+fn pgn_date() -> String {
+    let days = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() / 86400) as i64;
+    // days to a civil date, from Howard Hinnant's algorithm
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+/// The game record of a played game, with its tags and the reason it ended as a final comment.
+fn game_record(pairing: &Pairing, played: &PlayedGame, opening: &Opening, options: &MatchOptions) -> Game {
+    let white = &options.engines[pairing.white];
+    let black = &options.engines[pairing.black];
+    let mut game = Game::from_moves(&opening.board, &played.moves);
+    let fen = game.tag("FEN").map(str::to_string);
+    game.tags.clear();
+    game.set_tag("Event", &options.event);
+    game.set_tag("Date", &pgn_date());
+    game.set_tag("Round", &pairing.round.to_string());
+    game.set_tag("White", &white.name);
+    game.set_tag("Black", &black.name);
+    game.set_tag("Result", result_marker(played.result));
+    if let Some(fen) = fen {
+        game.set_tag("SetUp", "1");
+        game.set_tag("FEN", &fen);
+    }
+    if options.chess960 {
+        game.set_tag("Variant", "fischerandom");
+    }
+    if white.time_control == black.time_control {
+        game.set_tag("TimeControl", &white.time_control.to_string());
+    } else {
+        game.set_tag("WhiteTimeControl", &white.time_control.to_string());
+        game.set_tag("BlackTimeControl", &black.time_control.to_string());
+    }
+    game.set_tag("PlyCount", &played.moves.len().to_string());
+    game.set_tag("Termination", played.termination);
+    game.result = result_marker(played.result).to_string();
+    match game.moves.last_mut() {
+        Some(last) => last.comment = Some(played.reason.clone()),
+        None => game.comment = Some(played.reason.clone()),
+    }
+    game
+}
+
+/// Play a game of the schedule with the engines of a worker, starting those that aren't running.
+fn play_pairing(pairing: &Pairing, running: &mut [Option<Engine>], openings: &[Opening], options: &MatchOptions) -> Result<GameOutcome, String> {
+    let (w, b) = (pairing.white, pairing.black);
+    let configs = [&options.engines[w], &options.engines[b]];
+    let mut white = match running[w].take() {
+        Some(engine) => engine,
+        None => configs[0].start(options.chess960)?,
+    };
+    let mut black = match running[b].take() {
+        Some(engine) => engine,
+        None => configs[1].start(options.chess960)?,
+    };
+    let opening = &openings[pairing.opening];
+    let played = play_game([&mut white, &mut black], configs, opening, options);
+    if played.failed != Some(Color::White) {
+        running[w] = Some(white);
+    }
+    if played.failed != Some(Color::Black) {
+        running[b] = Some(black);
+    }
+    Ok(GameOutcome {
+        pairing: *pairing,
+        result: played.result,
+        reason: played.reason.clone(),
+        game: game_record(pairing, &played, opening, options),
+    })
+}
+
+/// Play a whole match. `on_game` is called with every game as it finishes, e.g. to write it
+/// out, and stops the match early by returning `false`, once an SPRT is over for example. The
+/// games still being played are then finished, but left out. An error from `on_game`, or an
+/// engine that can't be started, stops the match too. Returns the games in the order of the
+/// schedule.
+pub fn run_match(options: &MatchOptions, mut on_game: impl FnMut(&GameOutcome) -> Result<bool, String>) -> Result<Vec<GameOutcome>, String> {
+    crate::init();
+    if options.engines.len() < 2 {
+        return Err("A match needs at least two engines".to_string());
+    }
+    if let Some(engine) = options.engines.iter().find(|e| !e.time_control.is_limited()) {
+        return Err(format!("{}: no time control, node or depth limit", engine.name));
+    }
+    let start = [Opening::start()];
+    let openings = if options.openings.is_empty() { &start[..] } else { &options.openings[..] };
+    let pairings = schedule(options, openings.len());
+
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let mut outcomes = vec![];
+    std::thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel::<Result<GameOutcome, String>>();
+        for _ in 0..options.concurrency.max(1) {
+            let sender = sender.clone();
+            let (next, stop, pairings) = (&next, &stop, &pairings);
+            scope.spawn(move || {
+                let mut running: Vec<Option<Engine>> = options.engines.iter().map(|_| None).collect();
+                while !stop.load(Ordering::Relaxed) {
+                    let Some(pairing) = pairings.get(next.fetch_add(1, Ordering::Relaxed)) else { break };
+                    if sender.send(play_pairing(pairing, &mut running, openings, options)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        let mut result = Ok(());
+        for outcome in receiver {
+            let go_on = outcome.and_then(|o| {
+                let go_on = on_game(&o);
+                outcomes.push(o);
+                go_on
+            });
+            if go_on != Ok(true) {
+                result = go_on.map(|_| ());
+                break;
+            }
+        }
+        stop.store(true, Ordering::Relaxed);
+        result
+    })?;
+    outcomes.sort_by_key(|o| o.pairing.number);
+    Ok(outcomes)
+}
+
+/// The half points `engine` scored in a game it played.
+fn points(outcome: &GameOutcome, engine: usize) -> usize {
+    let white = outcome.pairing.white == engine;
+    match outcome.result {
+        GameResult::Draw => 1,
+        GameResult::WhiteWin if white => 2,
+        GameResult::BlackWin if !white => 2,
+        _ => 0,
+    }
+}
+
+/// Every engine's wins, losses and draws over `outcomes`.
+pub fn standings(engines: usize, outcomes: &[GameOutcome]) -> Vec<Wdl> {
+    let mut standings = vec![Wdl::default(); engines];
+    for outcome in outcomes {
+        for engine in [outcome.pairing.white, outcome.pairing.black] {
+            standings[engine].add(points(outcome, engine));
+        }
+    }
+    standings
+}
+
+/// The color-reversed pairs `engine` played against `opponent`, counted by `engine`'s score:
+/// the first and second games of every encounter, the third and fourth, and so on. Pairs with
+/// a game missing are left out.
+pub fn pentanomial(outcomes: &[GameOutcome], engine: usize, opponent: usize) -> Pentanomial {
+    let mut pairs: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut pentanomial = Pentanomial::default();
+    for outcome in outcomes {
+        let pairing = &outcome.pairing;
+        if [pairing.white, pairing.black] != [engine, opponent] && [pairing.black, pairing.white] != [engine, opponent] {
+            continue;
+        }
+        let points = points(outcome, engine);
+        match pairs.remove(&(pairing.encounter, (pairing.game - 1) / 2)) {
+            Some(first) => pentanomial.add(first + points),
+            None => {
+                pairs.insert((pairing.encounter, (pairing.game - 1) / 2), points);
+            }
+        }
+    }
+    pentanomial
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tournament::*;
+
+    #[test]
+    fn test_time_control() {
+        let tc: TimeControl = "10+0.1".parse().unwrap();
+        assert_eq!((tc.moves, tc.time, tc.increment), (None, Some(Duration::from_secs(10)), Duration::from_millis(100)));
+        assert_eq!(tc.to_string(), "10+0.1");
+        assert_eq!("40/60".parse::<TimeControl>().unwrap().moves_to_go(45), Some(35));
+        assert_eq!("inf".parse::<TimeControl>().unwrap(), TimeControl::default());
+        assert!(!TimeControl::default().is_limited());
+        assert_eq!(TimeControl::default().to_string(), "-");
+        for tc in ["", "0", "-5", "0/10", "x/10", "10+", "10+-1", "1:x"] {
+            assert!(tc.parse::<TimeControl>().is_err(), "{}", tc);
+        }
+
+        let board = Board::from(BOARD_START_FEN);
+        let tcs = [&"40/60+1".parse().unwrap(), &TimeControl { nodes: Some(1000), ..Default::default() }];
+        let left = [Some(Duration::from_millis(1500)), None];
+        assert_eq!(go_command(&board, tcs, left, [39, 0]), "go wtime 1500 winc 1000 movestogo 1");
+        let board = Board::from("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+        assert_eq!(go_command(&board, tcs, left, [1, 0]), "go wtime 1500 winc 1000 nodes 1000");
+    }
+
+    #[test]
+    fn test_openings() {
+        let epd = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - id \"e4\";\n\n8/8/4k3/8/8/3K4/8/8 w - -\n";
+        let openings = read_openings(epd.as_bytes(), OpeningFormat::Epd, None).unwrap();
+        assert_eq!(openings.len(), 2);
+        assert_eq!(openings[1].board.fen(), "8/8/4k3/8/8/3K4/8/8 w - - 0 1");
+
+        let pgn = "[Event \"a\"]\n\n1. e4 e5 2. Nf3 Nc6 *\n\n[Event \"b\"]\n\n1. d4 *\n";
+        let openings = read_openings(pgn.as_bytes(), OpeningFormat::Pgn, Some(3)).unwrap();
+        assert_eq!(openings.iter().map(|o| o.moves.len()).collect::<Vec<_>>(), vec![3, 1]);
+        assert_eq!(position_command(&openings[0].board, &openings[0].moves, false), "position startpos moves e2e4 e7e5 g1f3");
+        assert_eq!(position_command(&openings[1].board, &[], false), "position startpos");
+        assert!(read_openings("1. e4 e5 2. Ke3 *".as_bytes(), OpeningFormat::Pgn, None).is_err());
+        assert_eq!("pgn".parse(), Ok(OpeningFormat::Pgn));
+        assert!("cgn".parse::<OpeningFormat>().is_err());
+    }
+
+    #[test]
+    fn test_schedule() {
+        let options = MatchOptions {
+            engines: vec![EngineConfig::default(); 3],
+            rounds: 2,
+            ..Default::default()
+        };
+        let pairings = schedule(&options, 4);
+        // three encounters a round, each a color-reversed pair from the same opening
+        assert_eq!(pairings.len(), 12);
+        for pair in pairings.chunks(2) {
+            assert_eq!((pair[0].white, pair[0].black), (pair[1].black, pair[1].white));
+            assert_eq!(pair[0].opening, pair[1].opening);
+        }
+        assert_eq!(pairings.iter().map(|p| p.opening).collect::<Vec<_>>(), vec![0, 0, 1, 1, 2, 2, 3, 3, 0, 0, 1, 1]);
+        assert_eq!(pairings.iter().map(|p| p.number).collect::<Vec<_>>(), (1..=12).collect::<Vec<_>>());
+        assert_eq!(pairings[6].round, 2);
+
+        let random = schedule(&MatchOptions { random_order: true, ..options.clone() }, 4);
+        assert!(random.iter().all(|p| p.opening < 4));
+        assert_eq!(schedule(&MatchOptions { random_order: true, ..options.clone() }, 4), random);
+
+        // the first engine wins the first pair with white and draws with black, then loses the
+        // second one twice; the third game of the last encounter is left without a pair
+        let results = [GameResult::WhiteWin, GameResult::Draw, GameResult::BlackWin, GameResult::WhiteWin, GameResult::Draw];
+        let pairings = schedule(&MatchOptions { engines: vec![EngineConfig::default(); 2], games: 3, ..options }, 1);
+        assert_eq!(pairings.iter().map(|p| (p.encounter, p.game)).collect::<Vec<_>>(), vec![(1, 1), (1, 2), (1, 3), (2, 1), (2, 2), (2, 3)]);
+        let outcomes: Vec<GameOutcome> = [0, 1, 3, 4, 5]
+            .iter()
+            .zip(results)
+            .map(|(&i, result)| GameOutcome { pairing: pairings[i], result, reason: String::new(), game: Game::new() })
+            .collect();
+        assert_eq!(pentanomial(&outcomes, 0, 1), Pentanomial([1, 0, 0, 1, 0]));
+        assert_eq!(pentanomial(&outcomes, 1, 0), Pentanomial([0, 1, 0, 0, 1]));
+        assert_eq!(pentanomial(&outcomes, 0, 2), Pentanomial::default());
+        let table = standings(2, &outcomes);
+        assert_eq!(table[0], Wdl { wins: 1, draws: 2, losses: 2 });
+        assert_eq!(table[1], Wdl { wins: 2, draws: 2, losses: 1 });
+    }
+
+    #[test]
+    fn test_rules_result() {
+        let mut seen = HashMap::new();
+        let mate = Board::from("6k1/5ppp/8/8/8/8/8/4R1K1 w - - 0 1");
+        let mut board = mate.clone();
+        board.make_move(board.parse_uci("e1e8", false).unwrap());
+        assert_eq!(rules_result(&board, &mut seen), Some((GameResult::WhiteWin, "White mates".to_string())));
+        assert_eq!(rules_result(&mate, &mut seen), None);
+        assert_eq!(rules_result(&mate, &mut seen), None);
+        assert_eq!(rules_result(&mate, &mut seen).unwrap().1, "Draw by 3-fold repetition");
+        let bare = Board::from("8/8/4k3/8/8/3K4/8/8 w - - 0 1");
+        assert_eq!(rules_result(&bare, &mut seen).unwrap().1, "Draw by insufficient mating material");
+        let date = pgn_date();
+        assert_eq!((date.len(), &date[4..5], &date[7..8]), (10, ".", "."));
+    }
+}
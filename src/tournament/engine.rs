@@ -0,0 +1,195 @@
+//! # UCI Engine Processes
+//!
+//! An engine executable run as a child process and driven over UCI, for the
+//! [match runner](crate::tournament). Its output is read on a thread of its own, so that every
+//! wait can time out: an engine that hangs or overruns its clock doesn't hang the match.
+//!
+//! Dropping an [`Engine`] sends `quit` and kills the process if it doesn't exit on its own.
+//!
+//! ### Links
+//! - [UCI](https://backscattering.de/chess/uci/)
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// How long an engine gets to answer `uci` and `isready`.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long an engine gets to exit after `quit`, before it's killed.
+const QUIT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// A score reported in an `info` line, from the point of view of the side to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    Cp(i32),
+    /// Mate in that many moves, negative when getting mated.
+    Mate(i32),
+}
+
+/// The reply to a `go` command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineMove {
+    /// The `bestmove`, in UCI notation.
+    pub uci: String,
+    /// The score of the last `info` line that had one.
+    pub score: Option<Score>,
+}
+
+/// A running engine process.
+pub struct Engine {
+    name: Option<String>,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+}
+
+impl Score {
+    /// The score in centipawns, with mates as large as they get, to compare with thresholds.
+    pub fn cp(&self) -> i32 {
+        match *self {
+            Score::Cp(cp) => cp,
+            Score::Mate(moves) if moves > 0 => 100_000 - moves,
+            Score::Mate(moves) => -100_000 - moves,
+        }
+    }
+
+    /// Parse the score of an `info` line, if it has one.
+    ///
+    /// # Example
+    /// ```
+    /// use tejuino::tournament::engine::Score;
+    ///
+    /// assert_eq!(Score::parse_info("info depth 5 score cp -31 nodes 900 pv e7e5"), Some(Score::Cp(-31)));
+    /// assert_eq!(Score::parse_info("info depth 9 score mate 3 lowerbound"), Some(Score::Mate(3)));
+    /// assert_eq!(Score::parse_info("info string score cp 10"), None);
+    /// ```
+    pub fn parse_info(line: &str) -> Option<Score> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.first() != Some(&"info") || tokens.get(1) == Some(&"string") {
+            return None;
+        }
+        let at = tokens.iter().position(|t| *t == "score")?;
+        let value = tokens.get(at + 2)?.parse().ok()?;
+        match tokens.get(at + 1) {
+            Some(&"cp") => Some(Score::Cp(value)),
+            Some(&"mate") => Some(Score::Mate(value)),
+            _ => None,
+        }
+    }
+}
+
+impl Engine {
+    /// Start `command` with `args` in `dir`, do the UCI handshake and set `options`.
+    pub fn start(command: &str, args: &[String], dir: Option<&Path>, options: &[(String, String)]) -> Result<Engine, String> {
+        let mut process = Command::new(command);
+        process.args(args).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::null());
+        if let Some(dir) = dir {
+            process.current_dir(dir);
+        }
+        let mut child = process.spawn().map_err(|e| format!("{}: {}", command, e))?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = Engine { name: None, child, stdin, lines };
+        engine.send("uci")?;
+        for line in engine.wait_for("uciok", Some(HANDSHAKE_TIMEOUT))? {
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = Some(name.trim().to_string());
+            }
+        }
+        for (name, value) in options {
+            engine.send(&format!("setoption name {} value {}", name, value))?;
+        }
+        engine.ready()?;
+        Ok(engine)
+    }
+
+    /// The name the engine gave in its `id name` line.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn send(&mut self, command: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{}", command)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|_| "Engine disconnected".to_string())
+    }
+
+    /// Read lines until one whose first token is `token`, and return them all, that one
+    /// included. Waits forever without a `timeout`.
+    pub fn wait_for(&mut self, token: &str, timeout: Option<Duration>) -> Result<Vec<String>, String> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut lines = vec![];
+        loop {
+            let line = match deadline {
+                Some(deadline) => self
+                    .lines
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    .map_err(|e| match e {
+                        RecvTimeoutError::Timeout => format!("Engine timed out waiting for {}", token),
+                        RecvTimeoutError::Disconnected => "Engine disconnected".to_string(),
+                    })?,
+                None => self.lines.recv().map_err(|_| "Engine disconnected".to_string())?,
+            };
+            let done = line.split_whitespace().next() == Some(token);
+            lines.push(line);
+            if done {
+                return Ok(lines);
+            }
+        }
+    }
+
+    /// Synchronize with `isready`.
+    pub fn ready(&mut self) -> Result<(), String> {
+        self.send("isready")?;
+        self.wait_for("readyok", Some(HANDSHAKE_TIMEOUT)).map(|_| ())
+    }
+
+    /// Tell the engine a new game starts.
+    pub fn new_game(&mut self) -> Result<(), String> {
+        self.send("ucinewgame")?;
+        self.ready()
+    }
+
+    /// Send a `position` and a `go` command, and wait up to `timeout` for the `bestmove`.
+    pub fn go(&mut self, position: &str, go: &str, timeout: Option<Duration>) -> Result<EngineMove, String> {
+        self.send(position)?;
+        self.send(go)?;
+        let lines = self.wait_for("bestmove", timeout)?;
+        let uci = lines
+            .last()
+            .and_then(|l| l.split_whitespace().nth(1))
+            .ok_or("Engine sent bestmove without a move")?
+            .to_string();
+        let score = lines.iter().rev().find_map(|l| Score::parse_info(l));
+        Ok(EngineMove { uci, score })
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let deadline = Instant::now() + QUIT_TIMEOUT;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,167 @@
+//! # Tunable Search Parameters
+//!
+//! Every constant of the search that is worth tuning, gathered in one registry: a
+//! [`SearchParams`] field for the search to read, and a [`Tunable`] entry with its default,
+//! range and SPSA step. From the registry, each parameter is exposed as a UCI `spin` option, so
+//! that it can be changed with `setoption` without recompiling, and the whole list can be
+//! printed for OpenBench ([`openbench_list`]) or weather-factory ([`weather_factory_json`]).
+//!
+//! Adding a parameter is a single line in the `tunables!` invocation below.
+//!
+//! ### Links
+//! - [OpenBench SPSA](https://github.com/AndyGrant/OpenBench/wiki/SPSA-Tuning-Workloads)
+//! - [weather-factory](https://github.com/jnlt3/weather-factory)
+
+/// A search parameter, as listed for tuning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tunable {
+    pub name: &'static str,
+    pub default: i32,
+    pub min: i32,
+    pub max: i32,
+    /// SPSA perturbation at the end of a tuning run (OpenBench's `c_end`).
+    pub step: i32,
+}
+
+/// SPSA learning rate at the end of a tuning run (OpenBench's `r_end`), the same for all.
+pub const R_END: f64 = 0.002;
+
+/// Define [`SearchParams`] and [`TUNABLES`] from a list of `name: default, min, max, step;`.
+macro_rules! tunables {
+    ($($(#[doc = $doc:literal])* $name:ident: $default:expr, $min:expr, $max:expr, $step:expr;)*) => {
+        /// The tunable constants of the search. See [`TUNABLES`] for their ranges.
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub struct SearchParams {
+            $($(#[doc = $doc])* pub $name: i32,)*
+        }
+
+        impl Default for SearchParams {
+            fn default() -> SearchParams {
+                SearchParams { $($name: $default,)* }
+            }
+        }
+
+        /// Every tunable parameter, in the order of the fields of [`SearchParams`].
+        pub const TUNABLES: &[Tunable] = &[
+            $(Tunable { name: stringify!($name), default: $default, min: $min, max: $max, step: $step },)*
+        ];
+
+        impl SearchParams {
+            /// The value of the parameter called `name`.
+            pub fn get(&self, name: &str) -> Option<i32> {
+                match name {
+                    $(stringify!($name) => Some(self.$name),)*
+                    _ => None,
+                }
+            }
+
+            fn field(&mut self, name: &str) -> Option<&mut i32> {
+                match name {
+                    $(stringify!($name) => Some(&mut self.$name),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+tunables! {
+    /// Half width of the aspiration window around the previous iteration's score.
+    aspiration_delta: 25, 5, 200, 5;
+    /// Null move reduction: `nmp_base + depth / nmp_divisor`.
+    nmp_base: 3, 1, 6, 1;
+    nmp_divisor: 4, 1, 12, 1;
+    /// Minimum depth to try a null move at.
+    nmp_min_depth: 3, 1, 8, 1;
+    /// Maximum depth for reverse futility pruning.
+    rfp_depth: 6, 1, 12, 1;
+    /// Reverse futility margin, per ply of depth.
+    rfp_margin: 80, 20, 300, 10;
+    /// Late move reductions: `(lmr_base + ln(depth) * ln(moves) * 100 / lmr_divisor) / 100`.
+    lmr_base: 75, 0, 200, 10;
+    lmr_divisor: 225, 100, 500, 20;
+    /// Moves searched at full depth before reducing.
+    lmr_min_moves: 3, 1, 10, 1;
+    /// History bonus, per squared ply of depth, for a quiet move that fails high.
+    history_bonus: 16, 1, 64, 4;
+}
+
+impl SearchParams {
+    /// Set a parameter, checking it is within its range. Names are case insensitive, as UCI
+    /// option names are.
+    pub fn set(&mut self, name: &str, value: i32) -> Result<(), String> {
+        let tunable = TUNABLES
+            .iter()
+            .find(|t| t.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("Unknown parameter: {}", name))?;
+        if !(tunable.min..=tunable.max).contains(&value) {
+            return Err(format!("{} must be between {} and {}", tunable.name, tunable.min, tunable.max));
+        }
+        *self.field(tunable.name).unwrap() = value;
+        Ok(())
+    }
+
+    /// The values of every parameter, in the order of [`TUNABLES`].
+    pub fn values(&self) -> Vec<i32> {
+        TUNABLES.iter().map(|t| self.get(t.name).unwrap()).collect()
+    }
+}
+
+/// The UCI `option` lines announcing every parameter as a `spin` option.
+pub fn uci_options() -> Vec<String> {
+    TUNABLES
+        .iter()
+        .map(|t| format!("option name {} type spin default {} min {} max {}", t.name, t.default, t.min, t.max))
+        .collect()
+}
+
+/// The parameters in OpenBench's SPSA input format: `name, int, default, min, max, c_end, r_end`.
+pub fn openbench_list() -> String {
+    TUNABLES
+        .iter()
+        .map(|t| format!("{}, int, {}, {}, {}, {}, {}\n", t.name, t.default, t.min, t.max, t.step, R_END))
+        .collect()
+}
+
+/// The parameters as a weather-factory config: a JSON object of `value`, `min_value`,
+/// `max_value` and `step` by name.
+pub fn weather_factory_json() -> String {
+    let entries: Vec<String> = TUNABLES
+        .iter()
+        .map(|t| format!(
+            "    \"{}\": {{\"value\": {}, \"min_value\": {}, \"max_value\": {}, \"step\": {}}}",
+            t.name, t.default, t.min, t.max, t.step
+        ))
+        .collect();
+    format!("{{\n{}\n}}\n", entries.join(",\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tunables::*;
+
+    #[test]
+    fn test_tunables() {
+        let mut params = SearchParams::default();
+        assert_eq!(params.values(), TUNABLES.iter().map(|t| t.default).collect::<Vec<_>>());
+        for t in TUNABLES {
+            assert!(t.min <= t.default && t.default <= t.max && t.step > 0, "{}", t.name);
+        }
+        let mut names: Vec<&str> = TUNABLES.iter().map(|t| t.name).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), TUNABLES.len());
+
+        params.set("LMR_Base", 100).unwrap();
+        assert_eq!(params.lmr_base, 100);
+        assert_eq!(params.get("lmr_base"), Some(100));
+        assert!(params.set("lmr_base", 1000).is_err());
+        assert!(params.set("nonexistent", 1).is_err());
+        assert_eq!(params.get("nonexistent"), None);
+
+        assert_eq!(uci_options()[0], "option name aspiration_delta type spin default 25 min 5 max 200");
+        assert_eq!(openbench_list().lines().nth(1), Some("nmp_base, int, 3, 1, 6, 1, 0.002"));
+        assert_eq!(openbench_list().lines().count(), TUNABLES.len());
+        assert!(weather_factory_json().contains("\"rfp_margin\": {\"value\": 80, \"min_value\": 20, \"max_value\": 300, \"step\": 10}"));
+    }
+}
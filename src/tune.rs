@@ -0,0 +1,298 @@
+//! # Texel Tuning
+//!
+//! Tune the weights of the hand-crafted evaluation on positions labeled with the result of the
+//! game they come from, by minimizing the mean squared error between the results and the
+//! evaluations mapped to expected scores:
+//!
+//! ```text
+//! E = 1/N * sum (result - sigmoid(K * eval))^2,    sigmoid(s) = 1 / (1 + 10^(-s / 400))
+//! ```
+//!
+//! Each position is first resolved with a quiescence search (see [`resolve`]), so that only
+//! quiet positions are evaluated. Since the evaluation is linear in its weights, a position is
+//! then just its [`trace`], and the gradient of the error can be computed exactly.
+//!
+//! `K` is fitted first, to the current weights, and kept fixed while the weights are optimized
+//! with Adam. The tuned weights can be written back as the tables of `src/eval.rs`
+//! ([`replace_eval_source`]) or as a plain weights file ([`weights_text`]).
+//!
+//! ### Links
+//! - [Texel's Tuning Method](https://www.chessprogramming.org/Texel%27s_Tuning_Method)
+//! - [Adam](https://arxiv.org/abs/1412.6980)
+
+use std::f64::consts::LN_10;
+
+use crate::types::*;
+use crate::eval::{trace, NUM_PARAMS};
+use crate::search::resolve;
+
+/// First line of the evaluation source written by [`eval_source`].
+const SOURCE_START: &str = "/// Piece values, indexed by `PieceType`.";
+/// First line after it, which is kept.
+const SOURCE_END: &str = "/// Piece-square tables, indexed by `PieceType`.";
+
+const TABLE_NAMES: [&str; 6] = ["PAWN", "KNIGHT", "BISHOP", "ROOK", "QUEEN", "KING"];
+
+/// A position to tune on: its trace, once resolved, and the result of its game.
+#[derive(Debug, Clone)]
+pub struct TuneEntry {
+    pub coefficients: Vec<(usize, i32)>,
+    /// The result for white: 0, 0.5 or 1.
+    pub result: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TuneOptions {
+    pub epochs: usize,
+    pub learning_rate: f64,
+    pub beta1: f64,
+    pub beta2: f64,
+}
+
+impl Default for TuneOptions {
+    fn default() -> TuneOptions {
+        TuneOptions {
+            epochs: 1000,
+            learning_rate: 1.0,
+            beta1: 0.9,
+            beta2: 0.999,
+        }
+    }
+}
+
+impl TuneEntry {
+    /// Resolve `board` with a quiescence search and trace the quiet position it leads to.
+    pub fn new(board: &Board, result: f64) -> TuneEntry {
+        TuneEntry {
+            coefficients: trace(&resolve(board)),
+            result,
+        }
+    }
+
+    /// The evaluation with the given weights, from white's point of view.
+    #[inline]
+    pub fn evaluate(&self, params: &[f64]) -> f64 {
+        self.coefficients.iter().map(|(i, c)| params[*i] * *c as f64).sum()
+    }
+}
+
+/// Parse a game result for white: `1-0`, `0-1`, `1/2-1/2`, or a number from 0 to 1 written
+/// with a decimal point (`1.0`), so that it can't be taken for the fullmove number of a FEN.
+pub fn parse_result(s: &str) -> Option<f64> {
+    match s {
+        "1-0" => Some(1.0),
+        "0-1" => Some(0.0),
+        "1/2-1/2" => Some(0.5),
+        _ if s.contains('.') => s.parse().ok().filter(|r| (0.0..=1.0).contains(r)),
+        _ => None,
+    }
+}
+
+/// Parse a labeled position: a FEN followed by the result, as in `<fen> [1.0]`, `<fen> 1/2-1/2`
+/// or the EPD style `<fen> c9 "0-1";`.
+pub fn parse_labeled(line: &str) -> Result<(Board, f64), String> {
+    let line = line.trim().trim_end_matches(';').trim_end();
+    let (fen, result) = line
+        .rsplit_once(char::is_whitespace)
+        .ok_or_else(|| format!("Missing result: {}", line))?;
+    let result = result.trim_matches(|c| c == '[' || c == ']' || c == '"');
+    let result = parse_result(result).ok_or_else(|| format!("Invalid result: {}", result))?;
+    let fen = fen.trim_end().strip_suffix(" c9").unwrap_or(fen);
+    Ok((Board::from_fen(fen)?, result))
+}
+
+/// The expected score for a white-relative evaluation.
+#[inline]
+pub fn sigmoid(score: f64, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+/// Mean squared error of the predictions of the weights.
+pub fn error(entries: &[TuneEntry], params: &[f64], k: f64) -> f64 {
+    let total: f64 = entries
+        .iter()
+        .map(|e| (e.result - sigmoid(e.evaluate(params), k)).powi(2))
+        .sum();
+    total / entries.len().max(1) as f64
+}
+
+/// The gradient of the error with respect to every weight, and the error itself.
+pub fn gradient(entries: &[TuneEntry], params: &[f64], k: f64) -> (Vec<f64>, f64) {
+    let mut gradient = vec![0.0; params.len()];
+    let mut total = 0.0;
+    for entry in entries {
+        let predicted = sigmoid(entry.evaluate(params), k);
+        let delta = predicted - entry.result;
+        total += delta * delta;
+        // d/dw (r - sigmoid)^2 = 2 * (sigmoid - r) * sigmoid * (1 - sigmoid) * K * ln(10) / 400 * c
+        let scale = 2.0 * delta * predicted * (1.0 - predicted) * k * LN_10 / 400.0;
+        for (i, c) in &entry.coefficients {
+            gradient[*i] += scale * *c as f64;
+        }
+    }
+    let n = entries.len().max(1) as f64;
+    gradient.iter_mut().for_each(|g| *g /= n);
+    (gradient, total / n)
+}
+
+/// Find the scaling constant `K` that best fits the current weights to the results, with a
+/// golden section search.
+pub fn fit_k(entries: &[TuneEntry], params: &[f64]) -> f64 {
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let (mut low, mut high) = (0.0, 10.0);
+    while high - low > 1e-6 {
+        let a = high - ratio * (high - low);
+        let b = low + ratio * (high - low);
+        if error(entries, params, a) < error(entries, params, b) {
+            high = b;
+        } else {
+            low = a;
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// Optimize the weights with Adam, on the whole set every epoch. `progress` gets the epoch and
+/// the error before its update.
+pub fn tune(
+    entries: &[TuneEntry],
+    params: &mut [f64],
+    k: f64,
+    options: &TuneOptions,
+    mut progress: impl FnMut(usize, f64),
+) {
+    let mut m = vec![0.0; params.len()];
+    let mut v = vec![0.0; params.len()];
+    for epoch in 1..=options.epochs {
+        let (gradient, error) = gradient(entries, params, k);
+        progress(epoch, error);
+        let correction1 = 1.0 - options.beta1.powi(epoch as i32);
+        let correction2 = 1.0 - options.beta2.powi(epoch as i32);
+        for (i, g) in gradient.into_iter().enumerate() {
+            m[i] = options.beta1 * m[i] + (1.0 - options.beta1) * g;
+            v[i] = options.beta2 * v[i] + (1.0 - options.beta2) * g * g;
+            let (m_hat, v_hat) = (m[i] / correction1, v[i] / correction2);
+            params[i] -= options.learning_rate * m_hat / (v_hat.sqrt() + 1e-8);
+        }
+    }
+}
+
+/// Write the weights as plain text: the piece values on a line, then each table a rank per line.
+pub fn weights_text(params: &[i32]) -> String {
+    let line = |values: &[i32]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" ") + "\n";
+    let mut text = line(&params[..5]);
+    for rank in params[5..].chunks(8) {
+        text += &line(rank);
+    }
+    text
+}
+
+/// Read weights written by [`weights_text`].
+pub fn parse_weights(text: &str) -> Result<Vec<i32>, String> {
+    let params = text
+        .split_whitespace()
+        .map(|w| w.parse().map_err(|_| format!("Invalid weight: {}", w)))
+        .collect::<Result<Vec<i32>, String>>()?;
+    if params.len() != NUM_PARAMS {
+        return Err(format!("Expected {} weights, found {}", NUM_PARAMS, params.len()));
+    }
+    Ok(params)
+}
+
+/// The Rust source of the piece values and piece-square tables, as written in `src/eval.rs`.
+pub fn eval_source(params: &[i32]) -> String {
+    let mut source = format!(
+        "{}\npub const PIECE_VALUES: [i32; 8] = [0, 0, {}, 0];\n\n",
+        SOURCE_START,
+        params[..5].iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")
+    );
+    source += "// Tables are written as seen from white's side of the board (rank 8 first),\n";
+    source += "// so a white piece on `sq` is looked up at `sq ^ 56` and a black one at `sq`.\n";
+    for (name, table) in TABLE_NAMES.iter().zip(params[5..].chunks(64)) {
+        let width = table.iter().map(|v| v.to_string().len()).max().unwrap_or(0).max(3);
+        source += &format!("#[rustfmt::skip]\nconst {}_TABLE: [i32; 64] = [\n", name);
+        for rank in table.chunks(8) {
+            let values: Vec<String> = rank.iter().map(|v| format!("{:>width$}", v)).collect();
+            source += &format!("    {},\n", values.join(","));
+        }
+        source += "];\n\n";
+    }
+    source
+}
+
+/// Replace the piece values and tables in the source of `src/eval.rs` with the given weights.
+pub fn replace_eval_source(source: &str, params: &[i32]) -> Result<String, String> {
+    let start = source.find(SOURCE_START).ok_or("Piece values not found in the source")?;
+    let end = source[start..].find(SOURCE_END).ok_or("Piece-square tables not found in the source")? + start;
+    Ok(format!("{}{}{}", &source[..start], eval_source(params), &source[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tune::*;
+    use crate::eval::params;
+
+    #[test]
+    fn test_parse_labeled() {
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        for (line, result) in [
+            (format!("{} [1.0]", fen), 1.0),
+            (format!("{} [0.5]", fen), 0.5),
+            (format!("{} 0-1", fen), 0.0),
+            (format!("{} c9 \"1/2-1/2\";", fen), 0.5),
+        ] {
+            let (board, parsed) = parse_labeled(&line).unwrap();
+            assert_eq!((board.fen(), parsed), (fen.to_string(), result));
+        }
+        assert!(parse_labeled(fen).is_err());
+        assert!(parse_labeled(&format!("{} [2.0]", fen)).is_err());
+    }
+
+    #[test]
+    fn test_source() {
+        // the tables are laid out by hand, so only the values have to round trip
+        let squeeze = |s: &str| s.split_whitespace().collect::<String>();
+        let current = params();
+        let source = include_str!("eval.rs");
+        assert_eq!(squeeze(&replace_eval_source(source, &current).unwrap()), squeeze(source));
+        assert_eq!(parse_weights(&weights_text(&current)).unwrap(), current);
+
+        let mut tuned = current.clone();
+        tuned[0] = 95;
+        tuned[5 + 64 + 8] = -1234;
+        let replaced = replace_eval_source(source, &tuned).unwrap();
+        assert!(replaced.contains("pub const PIECE_VALUES: [i32; 8] = [0, 0, 95, 320, 330, 500, 900, 0];"));
+        assert!(replaced.contains("\n    -1234,  -20,    0,    0,    0,    0,  -20,  -40,\n"));
+        assert!(replace_eval_source("fn main() {}", &tuned).is_err());
+    }
+
+    #[test]
+    fn test_tune() {
+        // label positions with what a pawn worth 150 would predict, and check the tuner gets
+        // the pawn value closer to it
+        let mut truth: Vec<f64> = params().into_iter().map(|w| w as f64).collect();
+        truth[0] = 150.0;
+        let entries: Vec<TuneEntry> = crate::bench::BENCH_FENS
+            .iter()
+            .map(|fen| {
+                let entry = TuneEntry::new(&Board::from(fen), 0.0);
+                let result = sigmoid(entry.evaluate(&truth), 1.0);
+                TuneEntry { result, ..entry }
+            })
+            .collect();
+
+        let mut weights: Vec<f64> = params().into_iter().map(|w| w as f64).collect();
+        let k = fit_k(&entries, &weights);
+        assert!(error(&entries, &weights, k) <= error(&entries, &weights, k * 1.1));
+        assert!(error(&entries, &weights, k) <= error(&entries, &weights, k * 0.9));
+
+        let before = error(&entries, &weights, 1.0);
+        let mut errors = vec![];
+        let options = TuneOptions { epochs: 200, ..Default::default() };
+        tune(&entries, &mut weights, 1.0, &options, |_, e| errors.push(e));
+        assert_eq!(errors.len(), 200);
+        assert_eq!(errors[0], before);
+        assert!(error(&entries, &weights, 1.0) < before / 2.0);
+        assert!(weights[0] > 100.0);
+    }
+}
@@ -3,6 +3,9 @@ pub mod square;
 pub mod bitboard;
 pub mod board;
 pub mod magic;
+pub mod pack;
+#[cfg(feature = "serde")]
+mod serialize;
 
 use std::fmt::{Display, Debug};
 
@@ -10,9 +13,10 @@ pub use piece::*;
 pub use square::*;
 pub use bitboard::*;
 pub use board::*;
+pub use pack::PACKED_SIZE;
 pub use magic::*;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Color {
     White,
     Black,
@@ -30,7 +34,7 @@ pub enum File {
     FileH = 7,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Rank {
     Rank1 = 0,
     Rank2 = 1,
@@ -55,13 +59,16 @@ pub enum Direction {
     DownLeft = -8 - 1,
 }
 
+/// The kind of a move. `Quiet` covers every regular move, including captures.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum MoveType {
     Quiet,
-    Promotion,
+    Promotion(PieceType),
     EnPassant,
     Castle
 }
 
+#[derive(Copy, Clone, PartialEq, Eq)]
 pub struct Move {
     pub from: Square,
     pub to: Square,
@@ -70,32 +77,96 @@ pub struct Move {
 
 
 impl File {
+    pub const ALL: [File; 8] = [
+        File::FileA, File::FileB, File::FileC, File::FileD,
+        File::FileE, File::FileF, File::FileG, File::FileH,
+    ];
+
+    /// Every file, from `FileA` to `FileH`.
+    pub fn all() -> std::array::IntoIter<File, 8> {
+        File::ALL.into_iter()
+    }
+
+    /// The lowercase letter of the file, as used in algebraic notation.
+    pub fn char(&self) -> char {
+        (b'a' + *self as u8) as char
+    }
+}
+
+impl Rank {
+    pub const ALL: [Rank; 8] = [
+        Rank::Rank1, Rank::Rank2, Rank::Rank3, Rank::Rank4,
+        Rank::Rank5, Rank::Rank6, Rank::Rank7, Rank::Rank8,
+    ];
+
+    /// Every rank, from `Rank1` to `Rank8`.
+    pub fn all() -> std::array::IntoIter<Rank, 8> {
+        Rank::ALL.into_iter()
+    }
+
+    /// The digit of the rank, as used in algebraic notation.
+    pub fn char(&self) -> char {
+        (b'1' + *self as u8) as char
+    }
+
+    /// The rank as seen by `color`: the same for white, mirrored for black (`Rank8` is `Rank1`).
+    pub fn relative(&self, color: Color) -> Rank {
+        match color {
+            Color::White => *self,
+            Color::Black => Rank::ALL[7 - *self as usize],
+        }
+    }
+}
+
+impl TryFrom<usize> for File {
+    type Error = String;
+
     fn try_from(file: usize) -> Result<File, String> {
+        File::ALL.get(file).copied().ok_or(format!("Invalid file: {}", file))
+    }
+}
+
+impl TryFrom<isize> for File {
+    type Error = String;
+
+    fn try_from(file: isize) -> Result<File, String> {
+        usize::try_from(file).map_err(|_| format!("Invalid file: {}", file)).and_then(File::try_from)
+    }
+}
+
+impl TryFrom<char> for File {
+    type Error = String;
+
+    fn try_from(file: char) -> Result<File, String> {
         match file {
-            0 => Ok(File::FileA),
-            1 => Ok(File::FileB),
-            2 => Ok(File::FileC),
-            3 => Ok(File::FileD),
-            4 => Ok(File::FileE),
-            5 => Ok(File::FileF),
-            6 => Ok(File::FileG),
-            7 => Ok(File::FileH),
+            'a'..='h' => File::try_from(file as usize - 'a' as usize),
             _ => Err(format!("Invalid file: {}", file)),
         }
     }
 }
 
-impl Rank {
+impl TryFrom<usize> for Rank {
+    type Error = String;
+
     fn try_from(rank: usize) -> Result<Rank, String> {
+        Rank::ALL.get(rank).copied().ok_or(format!("Invalid rank: {}", rank))
+    }
+}
+
+impl TryFrom<isize> for Rank {
+    type Error = String;
+
+    fn try_from(rank: isize) -> Result<Rank, String> {
+        usize::try_from(rank).map_err(|_| format!("Invalid rank: {}", rank)).and_then(Rank::try_from)
+    }
+}
+
+impl TryFrom<char> for Rank {
+    type Error = String;
+
+    fn try_from(rank: char) -> Result<Rank, String> {
         match rank {
-            0 => Ok(Rank::Rank1),
-            1 => Ok(Rank::Rank2),
-            2 => Ok(Rank::Rank3),
-            3 => Ok(Rank::Rank4),
-            4 => Ok(Rank::Rank5),
-            5 => Ok(Rank::Rank6),
-            6 => Ok(Rank::Rank7),
-            7 => Ok(Rank::Rank8),
+            '1'..='8' => Rank::try_from(rank as usize - '1' as usize),
             _ => Err(format!("Invalid rank: {}", rank)),
         }
     }
@@ -120,6 +191,23 @@ impl Move {
     pub fn print(&self) {
         println!("{} -> {}", self.from, self.to);
     }
+
+    /// The move in UCI long algebraic notation (e.g. `e2e4`, `e7e8q`).
+    ///
+    /// Castling moves are stored as the king capturing its own rook. That is how they are
+    /// written in Chess960 mode (`UCI_Chess960`, e.g. `e1h1`), while in standard chess the
+    /// king's destination is given instead (`e1g1`).
+    pub fn uci(&self, chess960: bool) -> String {
+        let to = match self.move_type {
+            MoveType::Castle if !chess960 => board::castling_squares(self.from, self.to).0,
+            _ => self.to,
+        };
+        let mut uci = format!("{}{}", self.from.algebraic(), to.algebraic());
+        if let MoveType::Promotion(ptype) = self.move_type {
+            uci.push(Piece::new(ptype, Color::Black).id());
+        }
+        uci
+    }
 }
 
 impl Display for Move {
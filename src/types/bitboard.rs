@@ -4,8 +4,9 @@
 //! Not only are they very memory efficient, but they also allow for very fast bitwise operations,
 //! which are used in move generation and evaluation.
 
-use crate::types::{Square, Direction};
+use crate::types::{Color, Square, Direction};
 use crate::util;
+use crate::pregen::{BETWEEN, LINE};
 
 /// The bitboard type. It is a 64 bit unsigned integer.
 #[derive(Copy, Clone, PartialEq, Eq)]
@@ -15,9 +16,16 @@ pub struct BitboardIter {
     bitboard: Bitboard,
 }
 
+/// Iterator over every subset of a bitboard, see [`Bitboard::subsets`].
+pub struct SubsetIter {
+    set: Bitboard,
+    subset: Bitboard,
+    done: bool,
+}
+
 impl Bitboard {
     pub const RANK_1: Bitboard = Bitboard(0xFF);
-    pub const RANK_2: Bitboard = Bitboard(0xFF << (8 * 1));
+    pub const RANK_2: Bitboard = Bitboard(0xFF << 8);
     pub const RANK_3: Bitboard = Bitboard(0xFF << (8 * 2));
     pub const RANK_4: Bitboard = Bitboard(0xFF << (8 * 3));
     pub const RANK_5: Bitboard = Bitboard(0xFF << (8 * 4));
@@ -58,11 +66,7 @@ impl Bitboard {
     /// assert_eq!(bitboard, Bitboard(0xF));
     /// ```
     pub fn squares(squares: &[Square]) -> Bitboard {
-        let mut bitboard = Bitboard(0);
-        for square in squares {
-            bitboard |= Bitboard::square(*square);
-        }
-        bitboard
+        squares.iter().copied().collect()
     }
 
     /// Generate a bitboard with the edges where the square provided _isn't_ set.
@@ -82,7 +86,37 @@ impl Bitboard {
     /// Generate a bitboard with the rank the square provided is on set.
     #[inline]
     pub fn rank(square: Square) -> Bitboard {
-        Bitboard::RANK_1 << (square.rank() as usize) * 8
+        Bitboard::RANK_1 << ((square.rank() as usize) * 8)
+    }
+
+    /// The squares strictly between `a` and `b`, if they are on the same rank, file or diagonal.
+    /// Used for pins, blocking checks and making sure castling paths are clear.
+    ///
+    /// # Example
+    /// ```
+    /// use tejuino::types::{Bitboard, Square};
+    ///
+    /// assert_eq!(Bitboard::between(Square::B2, Square::E5), Bitboard::squares(&[Square::C3, Square::D4]));
+    /// assert_eq!(Bitboard::between(Square::A1, Square::B3), Bitboard(0));
+    /// ```
+    #[inline]
+    pub fn between(a: Square, b: Square) -> Bitboard {
+        BETWEEN[a as usize][b]
+    }
+
+    /// The full rank, file or diagonal going through `a` and `b`, edge to edge, or an empty
+    /// bitboard if they aren't aligned.
+    ///
+    /// # Example
+    /// ```
+    /// use tejuino::types::{Bitboard, Square};
+    ///
+    /// assert_eq!(Bitboard::line(Square::C1, Square::C5), Bitboard::FILE_C);
+    /// assert_eq!(Bitboard::line(Square::A1, Square::B3), Bitboard(0));
+    /// ```
+    #[inline]
+    pub fn line(a: Square, b: Square) -> Bitboard {
+        LINE[a as usize][b]
     }
 
     /// Checks if a bitboard is empty.
@@ -91,14 +125,229 @@ impl Bitboard {
         self.0 == 0
     }
 
+    /// Number of squares set.
+    #[inline]
+    pub fn count(&self) -> u32 {
+        self.0.count_ones()
+    }
+
+    /// Checks if `sq` is set.
+    #[inline]
+    pub fn contains(&self, sq: Square) -> bool {
+        self.0 & (1 << sq as usize) != 0
+    }
+
+    /// Checks if exactly one square is set.
+    #[inline]
+    pub fn is_single(&self) -> bool {
+        self.0 != 0 && !self.more_than_one()
+    }
+
+    /// Checks if more than one square is set.
+    #[inline]
+    pub fn more_than_one(&self) -> bool {
+        self.0 & self.0.wrapping_sub(1) != 0
+    }
+
+    /// The lowest square set (closest to a1), if any.
+    #[inline]
+    pub fn lsb(&self) -> Option<Square> {
+        if self.empty() { None } else { Some(Square::from_index(self.0.trailing_zeros() as usize)) }
+    }
+
+    /// The highest square set (closest to h8), if any.
+    #[inline]
+    pub fn msb(&self) -> Option<Square> {
+        if self.empty() { None } else { Some(Square::from_index(63 - self.0.leading_zeros() as usize)) }
+    }
+
+    /// Remove the lowest square set, and return it.
+    ///
+    /// # Example
+    /// ```
+    /// use tejuino::types::{Bitboard, Square};
+    ///
+    /// let mut bitboard = Bitboard::squares(&[Square::C2, Square::A7]);
+    /// assert_eq!(bitboard.pop_lsb(), Some(Square::C2));
+    /// assert_eq!(bitboard.pop_lsb(), Some(Square::A7));
+    /// assert_eq!(bitboard.pop_lsb(), None);
+    /// ```
+    #[inline]
+    pub fn pop_lsb(&mut self) -> Option<Square> {
+        let sq = self.lsb()?;
+        self.0 &= self.0 - 1;
+        Some(sq)
+    }
+
+    /// Mirror the board vertically, so rank 1 becomes rank 8. Turns a position around to
+    /// the other side's point of view.
+    #[inline]
+    pub fn flip_vertical(&self) -> Bitboard {
+        Bitboard(self.0.swap_bytes())
+    }
+
+    /// Mirror the board horizontally, so the a file becomes the h file.
+    #[inline]
+    pub fn flip_horizontal(&self) -> Bitboard {
+        // swap neighbouring bits, then pairs, then nibbles, within every byte
+        const K1: u64 = 0x5555555555555555;
+        const K2: u64 = 0x3333333333333333;
+        const K4: u64 = 0x0F0F0F0F0F0F0F0F;
+        let mut b = self.0;
+        b = ((b >> 1) & K1) | ((b & K1) << 1);
+        b = ((b >> 2) & K2) | ((b & K2) << 2);
+        b = ((b >> 4) & K4) | ((b & K4) << 4);
+        Bitboard(b)
+    }
+
+    /// Mirror the board along the a1-h8 diagonal, so files become ranks.
+    #[inline]
+    pub fn flip_diagonal(&self) -> Bitboard {
+        // swap bits across the diagonal in blocks of 4x4, then 2x2, then 1x1
+        const K1: u64 = 0x5500550055005500;
+        const K2: u64 = 0x3333000033330000;
+        const K4: u64 = 0x0F0F0F0F00000000;
+        let mut b = self.0;
+        let mut t = K4 & (b ^ (b << 28));
+        b ^= t ^ (t >> 28);
+        t = K2 & (b ^ (b << 14));
+        b ^= t ^ (t >> 14);
+        t = K1 & (b ^ (b << 7));
+        b ^= t ^ (t >> 7);
+        Bitboard(b)
+    }
+
+    /// Rotate the board by 180 degrees, so a1 becomes h8.
+    #[inline]
+    pub fn rotate_180(&self) -> Bitboard {
+        Bitboard(self.0.reverse_bits())
+    }
+
+    /// Iterate over every subset of the squares set, from the empty one up to the full one,
+    /// using the Carry-Rippler trick. A bitboard with `n` squares set has `2^n` subsets.
+    ///
+    /// # Example
+    /// ```
+    /// use tejuino::types::{Bitboard, Square};
+    ///
+    /// let subsets: Vec<Bitboard> = Bitboard::squares(&[Square::A1, Square::C1]).subsets().collect();
+    /// assert_eq!(subsets, [Bitboard(0), Bitboard(0b001), Bitboard(0b100), Bitboard(0b101)]);
+    /// ```
+    pub fn subsets(&self) -> SubsetIter {
+        SubsetIter {
+            set: *self,
+            subset: Bitboard(0),
+            done: false,
+        }
+    }
+
+    /// Squares a shift by one step in direction `d` can land on. Bits shifted east off the h file
+    /// would wrap around to the a file of the next rank, and the other way around going west,
+    /// so those files are masked out.
+    #[inline]
+    pub const fn shift_mask(d: Direction) -> Bitboard {
+        match d {
+            Direction::Right | Direction::UpRight | Direction::DownRight => Bitboard(!Bitboard::FILE_A.0),
+            Direction::Left | Direction::UpLeft | Direction::DownLeft => Bitboard(!Bitboard::FILE_H.0),
+            Direction::Up | Direction::Down => Bitboard(!0),
+        }
+    }
+
     /// Shift a bitboard by a specified amount and direction. Set bits that go off the board are lost.
+    ///
+    /// # Example
+    /// ```
+    /// use tejuino::types::{Bitboard, Direction, Square};
+    ///
+    /// let bitboard = Bitboard::squares(&[Square::G4, Square::H4]);
+    /// assert_eq!(bitboard.shift(Direction::UpRight, 1), Bitboard::square(Square::H5));
+    /// assert_eq!(bitboard.shift(Direction::Left, 3), Bitboard::squares(&[Square::D4, Square::E4]));
+    /// ```
     #[inline]
     pub fn shift(&self, d: Direction, amt: usize) -> Bitboard {
-        match d {
-            Direction::Up => *self << (8 * amt),
-            Direction::Down => *self >> (8 * amt),
-            _ => *self,
+        let step = d as i32;
+        let mut b = *self;
+        for _ in 0..amt {
+            b.0 = if step > 0 { b.0 << step } else { b.0 >> -step };
+            b &= Bitboard::shift_mask(d);
         }
+        b
+    }
+
+    /// Fill every set square towards rank 8, including the squares themselves.
+    #[inline]
+    pub fn north_fill(&self) -> Bitboard {
+        let mut b = self.0;
+        b |= b << 8;
+        b |= b << 16;
+        b |= b << 32;
+        Bitboard(b)
+    }
+
+    /// Fill every set square towards rank 1, including the squares themselves.
+    #[inline]
+    pub fn south_fill(&self) -> Bitboard {
+        let mut b = self.0;
+        b |= b >> 8;
+        b |= b >> 16;
+        b |= b >> 32;
+        Bitboard(b)
+    }
+
+    /// Every file with at least one square set.
+    #[inline]
+    pub fn file_fill(&self) -> Bitboard {
+        self.north_fill() | self.south_fill()
+    }
+
+    /// Fill forward, from `color`'s point of view, including the squares themselves.
+    #[inline]
+    fn front_fill(&self, color: Color) -> Bitboard {
+        match color {
+            Color::White => self.north_fill(),
+            Color::Black => self.south_fill(),
+        }
+    }
+
+    /// Squares attacked by all the pawns of `color` in the bitboard.
+    ///
+    /// # Example
+    /// ```
+    /// use tejuino::types::{Bitboard, Color, Square};
+    ///
+    /// let pawns = Bitboard::squares(&[Square::A2, Square::E4]);
+    /// assert_eq!(pawns.pawn_attacks(Color::White), Bitboard::squares(&[Square::B3, Square::D5, Square::F5]));
+    /// ```
+    #[inline]
+    pub fn pawn_attacks(&self, color: Color) -> Bitboard {
+        match color {
+            Color::White => self.shift(Direction::UpLeft, 1) | self.shift(Direction::UpRight, 1),
+            Color::Black => self.shift(Direction::DownLeft, 1) | self.shift(Direction::DownRight, 1),
+        }
+    }
+
+    /// Squares in front of the pawns of `color`, all the way to the end of the board. A pawn with
+    /// no enemy pawn in its front span, nor in its attack span, is a passed pawn.
+    #[inline]
+    pub fn front_span(&self, color: Color) -> Bitboard {
+        let forward = match color {
+            Color::White => Direction::Up,
+            Color::Black => Direction::Down,
+        };
+        self.shift(forward, 1).front_fill(color)
+    }
+
+    /// Squares the pawns of `color` could ever attack while advancing.
+    #[inline]
+    pub fn pawn_attack_span(&self, color: Color) -> Bitboard {
+        self.pawn_attacks(color).front_fill(color)
+    }
+
+    /// The kings' squares and every square around them, the area king safety looks at.
+    #[inline]
+    pub fn king_ring(&self) -> Bitboard {
+        let row = *self | self.shift(Direction::Left, 1) | self.shift(Direction::Right, 1);
+        row | row.shift(Direction::Up, 1) | row.shift(Direction::Down, 1)
     }
     
     pub fn iter(&self) -> BitboardIter {
@@ -112,12 +361,39 @@ impl Iterator for BitboardIter {
     type Item = Square;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.bitboard.0 == 0 {
+        self.bitboard.pop_lsb()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.bitboard.count() as usize;
+        (count, Some(count))
+    }
+}
+
+impl ExactSizeIterator for BitboardIter {}
+
+impl Iterator for SubsetIter {
+    type Item = Bitboard;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
             return None;
         }
-        let square = self.bitboard.0.trailing_zeros() as usize;
-        self.bitboard.0 &= self.bitboard.0 - 1;
-        Some(Square::from(square))
+        let subset = self.subset;
+        self.subset.0 = self.set.0 & self.subset.0.wrapping_sub(self.set.0);
+        // back to the empty set once every subset was visited
+        self.done = self.subset.empty();
+        Some(subset)
+    }
+}
+
+impl FromIterator<Square> for Bitboard {
+    fn from_iter<I: IntoIterator<Item = Square>>(iter: I) -> Self {
+        let mut bitboard = Bitboard(0);
+        for sq in iter {
+            bitboard |= Bitboard::square(sq);
+        }
+        bitboard
     }
 }
 
@@ -204,9 +480,9 @@ impl std::fmt::Debug for Bitboard {
         // turn bitboard into a vector of chars to use in util::render_grid
         // a circle or other char for 1, a space for 0
         let mut chars: [char; 64] = [' '; 64];
-        for i in 0..64 {
+        for (i, c) in chars.iter_mut().enumerate() {
             if self.0 & (1 << i) != 0 {
-                chars[i] = '●';
+                *c = '●';
             }
         }
         write!(f, "\n{}", util::render_grid(&chars, true))
@@ -2,12 +2,80 @@ use std::fmt::{Display, Debug};
 
 use crate::util;
 use crate::types::*;
+use crate::movegen::get_moves;
+use crate::pregen::ATTACKS;
 
+/// Castling rights, stored as a set of flags.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CastlingRights(pub u8);
+
+/// Knight placements for Chess960 start positions, as indices into the five squares left
+/// after placing the bishops and queen (see [`Board::chess960`]).
+const CHESS960_KNIGHTS: [(usize, usize); 10] = [
+    (0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4),
+];
+
+#[derive(Clone)]
 pub struct Board {
     content: [Piece; 64],
     pub pieces: [Bitboard; 8],
     pub colors: [Bitboard; 2],
     pub side_to_move: Color,
+    pub castling: CastlingRights,
+    /// The rook each castling right refers to, indexed by [`CastlingRights::index`].
+    /// In Chess960, rooks can start on any file, so they need to be tracked.
+    pub castling_rooks: [Square; 4],
+    pub en_passant: Option<Square>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+}
+
+impl CastlingRights {
+    pub const NONE: CastlingRights = CastlingRights(0);
+    pub const WHITE_KINGSIDE: CastlingRights = CastlingRights(1);
+    pub const WHITE_QUEENSIDE: CastlingRights = CastlingRights(2);
+    pub const BLACK_KINGSIDE: CastlingRights = CastlingRights(4);
+    pub const BLACK_QUEENSIDE: CastlingRights = CastlingRights(8);
+    pub const ALL: CastlingRights = CastlingRights(15);
+
+    pub const EACH: [CastlingRights; 4] = [
+        CastlingRights::WHITE_KINGSIDE,
+        CastlingRights::WHITE_QUEENSIDE,
+        CastlingRights::BLACK_KINGSIDE,
+        CastlingRights::BLACK_QUEENSIDE,
+    ];
+
+    #[inline]
+    pub fn has(&self, rights: CastlingRights) -> bool {
+        self.0 & rights.0 != 0
+    }
+
+    /// The index of a single right, in the order of [`CastlingRights::EACH`].
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.0.trailing_zeros() as usize
+    }
+
+    #[inline]
+    pub fn color(&self) -> Color {
+        if self.index() < 2 { Color::White } else { Color::Black }
+    }
+
+    #[inline]
+    pub fn kingside(color: Color) -> CastlingRights {
+        match color {
+            Color::White => CastlingRights::WHITE_KINGSIDE,
+            Color::Black => CastlingRights::BLACK_KINGSIDE,
+        }
+    }
+
+    #[inline]
+    pub fn queenside(color: Color) -> CastlingRights {
+        match color {
+            Color::White => CastlingRights::WHITE_QUEENSIDE,
+            Color::Black => CastlingRights::BLACK_QUEENSIDE,
+        }
+    }
 }
 
 impl Board {
@@ -17,37 +85,218 @@ impl Board {
             pieces: [Bitboard(0); 8],
             colors: [Bitboard(0); 2],
             side_to_move: Color::White,
+            castling: CastlingRights::NONE,
+            castling_rooks: [Square::H1, Square::A1, Square::H8, Square::A8],
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
         }
     }
 
+    /// Create a board from a FEN string, panicking if it is invalid. See [`Board::from_fen`].
     pub fn from(fen: &str) -> Board {
+        Board::from_fen(fen).expect("FEN should be valid")
+    }
+
+    /// Parse a FEN string. Only the piece placement is mandatory; missing fields
+    /// take their default values, so EPD positions can be read as well. Each side must have
+    /// exactly one king, and there can't be more than 32 pieces.
+    pub fn from_fen(fen: &str) -> Result<Board, String> {
         let mut _board = Board::new();
-        let chunks: Vec<&str> = fen.split(' ').collect();
+        let chunks: Vec<&str> = fen.split_whitespace().collect();
+        if chunks.is_empty() {
+            return Err("Empty FEN".to_string());
+        }
         let mut file: usize = 0;
         let mut rank: usize = 7;
         for c in chunks[0].chars() {
             match c {
                 '/' => {
+                    if file != 8 || rank == 0 {
+                        return Err(format!("Invalid piece placement: {}", chunks[0]));
+                    }
                     file = 0;
                     rank -= 1;
                     continue;
                 }
-                c if c.is_numeric() => {
+                c if c.is_ascii_digit() => {
                     let num: u32 = c.to_digit(10).expect("Failed type conversion.");
                     file += usize::try_from(num).unwrap();
                 }
                 _ => {
                     let piece = Piece::from(c);
-                    let square_bb = Bitboard::square(Square::from(rank * 8 + file));
-                    _board.content[rank * 8 + file] = piece;
-                    _board.pieces[piece.ptype() as usize] |= square_bb;
-                    _board.colors[piece.color().unwrap() as usize] |= square_bb;
-                    _board.pieces[PieceType::All as usize] |= square_bb;
+                    if piece == Piece::None || file > 7 {
+                        return Err(format!("Invalid piece placement: {}", chunks[0]));
+                    }
+                    _board.put_piece(Square::from_index(rank * 8 + file), piece);
                     file += 1;
                 }
             }
+            if file > 8 {
+                return Err(format!("Invalid piece placement: {}", chunks[0]));
+            }
+        }
+        if file != 8 || rank != 0 {
+            return Err(format!("Invalid piece placement: {}", chunks[0]));
+        }
+        for color in [Color::White, Color::Black] {
+            if _board.by_piece(Piece::King(color)).count() != 1 {
+                return Err(format!("Expected one {:?} king: {}", color, chunks[0]));
+            }
+        }
+        if _board.pieces().count() > 32 {
+            return Err(format!("Too many pieces: {}", _board.pieces().count()));
+        }
+
+        _board.side_to_move = match chunks.get(1) {
+            None | Some(&"w") => Color::White,
+            Some(&"b") => Color::Black,
+            Some(other) => return Err(format!("Invalid side to move: {}", other)),
+        };
+
+        // Both X-FEN (`KQkq`, meaning the outermost rook on each side, or a file letter for
+        // an inner rook) and Shredder-FEN (`HAha`, always file letters) are accepted.
+        if let Some(castling) = chunks.get(2).filter(|c| **c != "-") {
+            for c in castling.chars() {
+                let invalid = || format!("Invalid castling rights: {}", castling);
+                let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+                let king = _board.by_piece(Piece::King(color)).iter().next().ok_or_else(invalid)?;
+                if king.relative_rank(color) != Rank::Rank1 {
+                    return Err(invalid());
+                }
+                let rooks = _board.by_piece(Piece::Rook(color)) & Bitboard::rank(king);
+                let rook = match c.to_ascii_lowercase() {
+                    'k' => rooks.iter().filter(|r| r.file() > king.file()).last(),
+                    'q' => rooks.iter().find(|r| r.file() < king.file()),
+                    f @ 'a'..='h' => rooks.iter().find(|r| r.file() as usize == f as usize - 'a' as usize),
+                    _ => None,
+                }.ok_or_else(invalid)?;
+                let rights = if rook.file() > king.file() {
+                    CastlingRights::kingside(color)
+                } else {
+                    CastlingRights::queenside(color)
+                };
+                _board.castling.0 |= rights.0;
+                _board.castling_rooks[rights.index()] = rook;
+            }
+        }
+
+        if let Some(ep) = chunks.get(3).filter(|c| **c != "-") {
+            _board.en_passant = Some(ep.parse().map_err(|_| format!("Invalid en passant square: {}", ep))?);
+        }
+
+        if let Some(halfmove) = chunks.get(4) {
+            _board.halfmove_clock = halfmove.parse().map_err(|_| format!("Invalid halfmove clock: {}", halfmove))?;
+        }
+
+        if let Some(fullmove) = chunks.get(5) {
+            _board.fullmove_number = fullmove.parse().map_err(|_| format!("Invalid fullmove number: {}", fullmove))?;
+        }
+
+        Ok(_board)
+    }
+
+    /// Export the position as a FEN string.
+    pub fn fen(&self) -> String {
+        let mut placement = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.content[rank * 8 + file] {
+                    Piece::None => empty += 1,
+                    piece => {
+                        if empty > 0 {
+                            placement += &empty.to_string();
+                            empty = 0;
+                        }
+                        placement.push(piece.id());
+                    }
+                }
+            }
+            if empty > 0 {
+                placement += &empty.to_string();
+            }
+            if rank > 0 {
+                placement.push('/');
+            }
+        }
+
+        let side = match self.side_to_move {
+            Color::White => "w",
+            Color::Black => "b",
+        };
+
+        // X-FEN: a file letter is only needed when the rook isn't the outermost one
+        let mut castling = String::new();
+        for (rights, c) in CastlingRights::EACH.into_iter().zip(['K', 'Q', 'k', 'q']) {
+            if !self.castling.has(rights) {
+                continue;
+            }
+            let color = rights.color();
+            let rook = self.castling_rooks[rights.index()];
+            let king = self.king_square(color);
+            let outer_rooks = (self.by_piece(Piece::Rook(color)) & Bitboard::rank(rook))
+                .iter()
+                .filter(|r| (r.file() > rook.file() && rook.file() > king.file())
+                         || (r.file() < rook.file() && rook.file() < king.file()))
+                .count();
+            if outer_rooks == 0 {
+                castling.push(c);
+            } else {
+                let file = (b'a' + rook.file() as u8) as char;
+                castling.push(if color == Color::White { file.to_ascii_uppercase() } else { file });
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
         }
-        _board
+
+        let ep = self.en_passant.map_or("-".to_string(), |sq| sq.algebraic());
+
+        format!("{} {} {} {} {} {}", placement, side, castling, ep, self.halfmove_clock, self.fullmove_number)
+    }
+
+    /// Set up the Chess960 start position with the given index (0 to 959), as numbered by
+    /// Scharnagl. Position 518 is the standard start position.
+    pub fn chess960(index: usize) -> Board {
+        assert!(index < 960, "Chess960 positions are numbered 0 to 959");
+        let mut files: [Option<PieceType>; 8] = [None; 8];
+        let mut n = index;
+        // light squared bishop on b, d, f or h, then dark squared one on a, c, e or g
+        files[(n % 4) * 2 + 1] = Some(PieceType::Bishop);
+        n /= 4;
+        files[(n % 4) * 2] = Some(PieceType::Bishop);
+        n /= 4;
+
+        let empty = |files: &[Option<PieceType>; 8]| -> Vec<usize> {
+            (0..8).filter(|f| files[*f].is_none()).collect()
+        };
+        files[empty(&files)[n % 6]] = Some(PieceType::Queen);
+        n /= 6;
+        let free = empty(&files);
+        let (a, b) = CHESS960_KNIGHTS[n];
+        files[free[a]] = Some(PieceType::Knight);
+        files[free[b]] = Some(PieceType::Knight);
+        // the king always ends up between the rooks
+        for (ptype, file) in [PieceType::Rook, PieceType::King, PieceType::Rook].into_iter().zip(empty(&files)) {
+            files[file] = Some(ptype);
+        }
+
+        let mut board = Board::new();
+        for (file, ptype) in File::all().zip(files) {
+            let ptype = ptype.expect("Every file should have a piece");
+            board.put_piece(Square::new(file, Rank::Rank1), Piece::new(ptype, Color::White));
+            board.put_piece(Square::new(file, Rank::Rank2), Piece::Pawn(Color::White));
+            board.put_piece(Square::new(file, Rank::Rank7), Piece::Pawn(Color::Black));
+            board.put_piece(Square::new(file, Rank::Rank8), Piece::new(ptype, Color::Black));
+        }
+        let rooks: Vec<File> = File::all().filter(|f| files[*f as usize] == Some(PieceType::Rook)).collect();
+        board.castling = CastlingRights::ALL;
+        board.castling_rooks = [
+            Square::new(rooks[1], Rank::Rank1), Square::new(rooks[0], Rank::Rank1),
+            Square::new(rooks[1], Rank::Rank8), Square::new(rooks[0], Rank::Rank8),
+        ];
+        board
     }
 
     #[inline]
@@ -75,11 +324,140 @@ impl Board {
         self.by_piece_type(PieceType::All)
     }
 
+    /// The square of the king of the given color.
+    #[inline]
+    pub fn king_square(&self, color: Color) -> Square {
+        self.by_piece(Piece::King(color))
+            .iter()
+            .next()
+            .expect("Board should have a king for each side")
+    }
+
+    /// Checks if `sq` is attacked by any piece of color `by`.
+    pub fn is_attacked(&self, sq: Square, by: Color) -> bool {
+        let occupied = self.pieces();
+        let them = self.by_color(by);
+        let queens = self.by_piece_type(PieceType::Queen);
+        let attackers = (ATTACKS.pawn[!by as usize][sq] & self.by_piece_type(PieceType::Pawn))
+            | (ATTACKS.knight[sq] & self.by_piece_type(PieceType::Knight))
+            | (ATTACKS.king[sq] & self.by_piece_type(PieceType::King))
+            | (get_moves(Piece::Bishop(by), sq, occupied) & (self.by_piece_type(PieceType::Bishop) | queens))
+            | (get_moves(Piece::Rook(by), sq, occupied) & (self.by_piece_type(PieceType::Rook) | queens));
+        !(attackers & them).empty()
+    }
+
+    /// Checks if the side to move is in check.
+    #[inline]
+    pub fn in_check(&self) -> bool {
+        self.is_attacked(self.king_square(self.side_to_move), !self.side_to_move)
+    }
+
+    pub(super) fn put_piece(&mut self, sq: Square, piece: Piece) {
+        let square_bb = Bitboard::square(sq);
+        self.content[sq as usize] = piece;
+        self.pieces[piece.ptype() as usize] |= square_bb;
+        self.colors[piece.color().unwrap() as usize] |= square_bb;
+        self.pieces[PieceType::All as usize] |= square_bb;
+    }
+
+    fn remove_piece(&mut self, sq: Square) -> Piece {
+        let piece = self.content[sq as usize];
+        if let Some(color) = piece.color() {
+            let square_bb = !Bitboard::square(sq);
+            self.content[sq as usize] = Piece::None;
+            self.pieces[piece.ptype() as usize] &= square_bb;
+            self.colors[color as usize] &= square_bb;
+            self.pieces[PieceType::All as usize] &= square_bb;
+        }
+        piece
+    }
+
+    /// Play a move on the board. The move is assumed to be (pseudo)legal.
+    pub fn make_move(&mut self, m: Move) {
+        let us = self.side_to_move;
+        let piece = self.remove_piece(m.from);
+        let mut captured = Piece::None;
+
+        match m.move_type {
+            MoveType::Quiet => {
+                captured = self.remove_piece(m.to);
+                self.put_piece(m.to, piece);
+            }
+            MoveType::Promotion(ptype) => {
+                captured = self.remove_piece(m.to);
+                self.put_piece(m.to, Piece::new(ptype, us));
+            }
+            MoveType::EnPassant => {
+                self.put_piece(m.to, piece);
+                // the captured pawn sits beside the moving pawn, not on the target square
+                self.remove_piece(Square::new(m.to.file(), m.from.rank()));
+            }
+            MoveType::Castle => {
+                // castling moves are encoded as the king capturing its own rook
+                let rook = self.remove_piece(m.to);
+                let (king_to, rook_to) = castling_squares(m.from, m.to);
+                self.put_piece(king_to, piece);
+                self.put_piece(rook_to, rook);
+            }
+        }
+
+        if piece.ptype() == PieceType::King {
+            self.castling.0 &= !(CastlingRights::kingside(us).0 | CastlingRights::queenside(us).0);
+        }
+        for rights in CastlingRights::EACH {
+            let rook = self.castling_rooks[rights.index()];
+            if rook == m.from || rook == m.to {
+                self.castling.0 &= !rights.0;
+            }
+        }
+
+        self.en_passant = None;
+        if piece.ptype() == PieceType::Pawn && (m.to as isize - m.from as isize).abs() == 16 {
+            self.en_passant = Some(Square::from_index((m.from as usize + m.to as usize) / 2));
+        }
+
+        if piece.ptype() == PieceType::Pawn || captured != Piece::None {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+
+        if us == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.side_to_move = !us;
+    }
+
+    /// Find the legal move matching a move in UCI notation. See [`Move::uci`] for the meaning of `chess960`.
+    pub fn parse_uci(&self, uci: &str, chess960: bool) -> Result<Move, String> {
+        crate::movegen::legal_moves(self)
+            .into_iter()
+            .find(|m| m.uci(chess960) == uci)
+            .ok_or(format!("Illegal UCI move: {}", uci))
+    }
+
     pub fn print(&self) {
         println!("{:?}", self);
     }
 }
 
+/// Where the king and rook end up after castling: on the g and f files when castling
+/// kingside, and on the c and d files when castling queenside, whatever file they started on.
+pub fn castling_squares(king: Square, rook: Square) -> (Square, Square) {
+    let rank = king.rank();
+    if rook.file() > king.file() {
+        (Square::new(File::FileG, rank), Square::new(File::FileF, rank))
+    } else {
+        (Square::new(File::FileC, rank), Square::new(File::FileD, rank))
+    }
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Board::new()
+    }
+}
+
 // implement the debug display trait for Board
 impl Display for Board {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
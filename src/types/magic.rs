@@ -12,12 +12,12 @@ pub type MagicTable = [SuperMagic; 64];
 
 /// A SuperMagic stores all necessary info for getting a magic bitboard for a square,
 /// along with an offset, which allows for all of squares to be stored in a single array.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct SuperMagic {
-    mask: Bitboard,
-    magic: u64,
-    shift: u8,
-    offset: usize,
+    pub mask: Bitboard,
+    pub magic: u64,
+    pub shift: u8,
+    pub offset: usize,
 }
 
 /// Stores individual magic bitboards.
@@ -32,18 +32,41 @@ pub struct MagicBitboard {
     table: Vec<Bitboard>,
 }
 
+/// A "black magic": blockers are hashed with every square outside of the mask set, which
+/// tends to cluster the indices, and the tables of all squares overlap in a single array.
+/// The offset can be negative, as the lowest index of a square is usually well above zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlackMagic {
+    pub not_mask: Bitboard,
+    pub magic: u64,
+    pub shift: u8,
+    pub offset: isize,
+}
+
+pub struct BlackMagicBitboard {
+    magics: [BlackMagic; 64],
+    table: Vec<Bitboard>,
+}
+
 pub struct MagicBitboardSquare<'a> {
     magic: &'a SuperMagic,
     table: &'a [Bitboard],
 }
 
+/// A way of looking up sliding piece attacks, such as magic or PEXT bitboards.
+pub trait SliderAttacks {
+    /// The squares attacked from `sq`, with the given pieces on the board.
+    fn attacks(&self, sq: Square, occupied: Bitboard) -> Bitboard;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MagicPiece {
     Bishop,
     Rook,
 }
 
 impl SuperMagic {
-    pub fn new(mask: Bitboard, magic: u64, shift: u8, offset: usize) -> SuperMagic {
+    pub const fn new(mask: Bitboard, magic: u64, shift: u8, offset: usize) -> SuperMagic {
         SuperMagic {
             mask,
             magic,
@@ -61,6 +84,15 @@ impl MagicBitboard {
         }
     }
 
+    pub fn magics(&self) -> &MagicTable {
+        &self.magics
+    }
+
+    /// Number of entries in the shared attack table.
+    pub fn table_size(&self) -> usize {
+        self.table.len()
+    }
+
     pub fn get<'a>(&'a self, square: Square) -> MagicBitboardSquare<'a> {
         MagicBitboardSquare {
             magic: &self.magics[square],
@@ -77,6 +109,55 @@ impl Index<Bitboard> for MagicBitboardSquare<'_> {
     }
 }
 
+impl BlackMagic {
+    pub const fn new(not_mask: Bitboard, magic: u64, shift: u8, offset: isize) -> BlackMagic {
+        BlackMagic {
+            not_mask,
+            magic,
+            shift,
+            offset,
+        }
+    }
+
+    #[inline]
+    pub fn index(&self, occupied: Bitboard) -> usize {
+        let hash = (occupied.0 | self.not_mask.0).wrapping_mul(self.magic) >> self.shift;
+        (self.offset + hash as isize) as usize
+    }
+}
+
+impl BlackMagicBitboard {
+    pub fn new(magics: [BlackMagic; 64], table: Vec<Bitboard>) -> BlackMagicBitboard {
+        BlackMagicBitboard {
+            magics,
+            table,
+        }
+    }
+
+    pub fn magics(&self) -> &[BlackMagic; 64] {
+        &self.magics
+    }
+
+    /// Number of entries in the shared attack table.
+    pub fn table_size(&self) -> usize {
+        self.table.len()
+    }
+}
+
+impl SliderAttacks for BlackMagicBitboard {
+    #[inline]
+    fn attacks(&self, sq: Square, occupied: Bitboard) -> Bitboard {
+        self.table[self.magics[sq as usize].index(occupied)]
+    }
+}
+
+impl SliderAttacks for MagicBitboard {
+    #[inline]
+    fn attacks(&self, sq: Square, occupied: Bitboard) -> Bitboard {
+        self.get(sq)[occupied]
+    }
+}
+
 impl Index<Square> for MagicTable {
     type Output = SuperMagic;
 
@@ -0,0 +1,216 @@
+//! # Packed Positions
+//!
+//! A fixed size binary encoding of a position, 32 bytes instead of the 60 or so of a FEN string,
+//! for storing training data and game databases. All multi-byte fields are little-endian:
+//!
+//! | bytes  | field     |                                                               |
+//! |--------|-----------|---------------------------------------------------------------|
+//! | 0..8   | occupancy | bitboard of the occupied squares                              |
+//! | 8..24  | pieces    | a 4 bit code per occupied square, in square order, low nibble first |
+//! | 24     | side      | side to move, 0 white, 1 black                                |
+//! | 25     | halfmove  | halfmove clock, capped at 255                                 |
+//! | 26..28 | fullmove  | fullmove number, capped at 65535                              |
+//! | 28..32 | unused    | left zero, for callers to store their own data next to the position |
+//!
+//! A piece code is the color in bit 3 (set for black) and one of:
+//!
+//! | code  | piece                                          |
+//! |-------|------------------------------------------------|
+//! | 0..=5 | pawn, knight, bishop, rook, queen, king        |
+//! | 6     | rook that can still castle                     |
+//! | 7     | pawn that just moved two squares, and can be taken en passant |
+//!
+//! So castling rights and the en passant square don't need fields of their own, and since the
+//! castling rooks are marked, Chess960 positions round trip exactly.
+//!
+//! ### Links
+//! - [marlinformat](https://github.com/jnlt3/marlinflow)
+
+use crate::types::*;
+
+/// Size of a packed position, in bytes.
+pub const PACKED_SIZE: usize = 32;
+
+const CASTLING_ROOK: u8 = 6;
+const EN_PASSANT_PAWN: u8 = 7;
+
+impl Board {
+    /// Pack the position into 32 bytes. See the [module](self) documentation for the format.
+    ///
+    /// # Example
+    /// ```
+    /// use tejuino::types::Board;
+    ///
+    /// let board = Board::from("r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 2");
+    /// assert_eq!(Board::unpack(&board.pack()).unwrap().fen(), board.fen());
+    /// ```
+    pub fn pack(&self) -> [u8; PACKED_SIZE] {
+        let mut bytes = [0; PACKED_SIZE];
+        let occupied = self.pieces();
+        // no more than Board::from_fen accepts, and moves never add any
+        debug_assert!(occupied.count() <= 32, "A board has at most 32 pieces");
+        bytes[0..8].copy_from_slice(&occupied.0.to_le_bytes());
+
+        // the pawn that made en passant possible, if it is there
+        let ep_pawn = self.en_passant
+            .and_then(|ep| ep.offset(0, if self.side_to_move == Color::White { -1 } else { 1 }))
+            .filter(|sq| self.at(*sq) == Piece::Pawn(!self.side_to_move));
+
+        for (i, sq) in occupied.iter().enumerate() {
+            let piece = self.at(sq);
+            let color = piece.color().unwrap();
+            let castles = CastlingRights::EACH
+                .iter()
+                .any(|rights| self.castling.has(*rights) && self.castling_rooks[rights.index()] == sq);
+            let code = if ep_pawn == Some(sq) {
+                EN_PASSANT_PAWN
+            } else if castles && piece == Piece::Rook(color) {
+                CASTLING_ROOK
+            } else {
+                piece.ptype() as u8 - PieceType::Pawn as u8
+            };
+            bytes[8 + i / 2] |= ((color as u8) << 3 | code) << (4 * (i % 2));
+        }
+
+        bytes[24] = self.side_to_move as u8;
+        bytes[25] = self.halfmove_clock.min(u8::MAX as u32) as u8;
+        bytes[26..28].copy_from_slice(&(self.fullmove_number.min(u16::MAX as u32) as u16).to_le_bytes());
+        bytes
+    }
+
+    /// Unpack a position packed with [`Board::pack`]. The unused bytes are ignored.
+    pub fn unpack(bytes: &[u8; PACKED_SIZE]) -> Result<Board, String> {
+        let occupied = Bitboard(u64::from_le_bytes(bytes[0..8].try_into().unwrap()));
+        if occupied.count() > 32 {
+            return Err(format!("Too many pieces: {}", occupied.count()));
+        }
+        let mut board = Board::new();
+        board.side_to_move = match bytes[24] {
+            0 => Color::White,
+            1 => Color::Black,
+            other => return Err(format!("Invalid side to move: {}", other)),
+        };
+        board.halfmove_clock = bytes[25] as u32;
+        board.fullmove_number = u16::from_le_bytes(bytes[26..28].try_into().unwrap()) as u32;
+
+        let mut castling_rooks = vec![];
+        for (i, sq) in occupied.iter().enumerate() {
+            let code = bytes[8 + i / 2] >> (4 * (i % 2)) & 0xF;
+            let color = if code & 8 == 0 { Color::White } else { Color::Black };
+            let ptype = match code & 7 {
+                0 | EN_PASSANT_PAWN => PieceType::Pawn,
+                1 => PieceType::Knight,
+                2 => PieceType::Bishop,
+                3 | CASTLING_ROOK => PieceType::Rook,
+                4 => PieceType::Queen,
+                _ => PieceType::King,
+            };
+            board.put_piece(sq, Piece::new(ptype, color));
+
+            match code & 7 {
+                CASTLING_ROOK => castling_rooks.push((sq, color)),
+                EN_PASSANT_PAWN => {
+                    if color == board.side_to_move || sq.relative_rank(color) != Rank::Rank4 || board.en_passant.is_some() {
+                        return Err(format!("Invalid en passant pawn on {}", sq.algebraic()));
+                    }
+                    board.en_passant = sq.offset(0, if color == Color::White { -1 } else { 1 });
+                }
+                _ => {}
+            }
+        }
+        for color in [Color::White, Color::Black] {
+            if board.by_piece(Piece::King(color)).count() != 1 {
+                return Err(format!("Expected one {:?} king", color));
+            }
+        }
+
+        for (rook, color) in castling_rooks {
+            let king = board.king_square(color);
+            let invalid = || format!("Invalid castling rook on {}", rook.algebraic());
+            if king.relative_rank(color) != Rank::Rank1 || rook.rank() != king.rank() {
+                return Err(invalid());
+            }
+            let rights = if rook.file() > king.file() {
+                CastlingRights::kingside(color)
+            } else {
+                CastlingRights::queenside(color)
+            };
+            if board.castling.has(rights) {
+                return Err(invalid());
+            }
+            board.castling.0 |= rights.0;
+            board.castling_rooks[rights.index()] = rook;
+        }
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::*;
+    use crate::movegen::legal_moves;
+
+    /// Checks every position of the move tree up to `depth` round trips.
+    fn assert_round_trips(board: &Board, depth: usize) -> usize {
+        let unpacked = Board::unpack(&board.pack()).unwrap();
+        assert_eq!(unpacked.fen(), board.fen());
+        for rights in CastlingRights::EACH.into_iter().filter(|r| board.castling.has(*r)) {
+            assert_eq!(unpacked.castling_rooks[rights.index()], board.castling_rooks[rights.index()]);
+        }
+        if depth == 0 {
+            return 1;
+        }
+        legal_moves(board).into_iter().map(|m| {
+            let mut next = board.clone();
+            next.make_move(m);
+            assert_round_trips(&next, depth - 1)
+        }).sum::<usize>() + 1
+    }
+
+    #[test]
+    fn test_pack() {
+        // the perft positions, and a Chess960 one with an inner rook
+        let fens = [
+            crate::BOARD_START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "1rk2r1r/8/8/8/8/8/8/1RK1R2R w EBfb - 0 1",
+        ];
+        let positions: usize = fens.iter().map(|fen| assert_round_trips(&Board::from(fen), 3)).sum();
+        assert!(positions > 10000);
+
+        let board = Board::from("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3");
+        let packed = board.pack();
+        assert_eq!(packed[28..], [0; 4]);
+        assert_eq!(Board::unpack(&packed).unwrap().en_passant, Some(Square::F6));
+
+        // the clocks saturate
+        let board = Board::from("8/8/4k3/8/8/3K4/8/8 b - - 300 70000");
+        let unpacked = Board::unpack(&board.pack()).unwrap();
+        assert_eq!((unpacked.halfmove_clock, unpacked.fullmove_number), (255, 65535));
+
+        // boards that wouldn't fit can't be made in the first place
+        assert!(Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPPPPPP/RNBQKBNR w - - 0 1").is_err());
+        let full = Board::from("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1");
+        assert_eq!(Board::unpack(&full.pack()).unwrap().fen(), full.fen());
+
+        let start = Board::from(crate::BOARD_START_FEN).pack();
+        let mut invalid = start;
+        invalid[24] = 2;
+        assert!(Board::unpack(&invalid).is_err());
+        // a second white king instead of the queen
+        let mut invalid = start;
+        invalid[9] = 0x25;
+        assert!(Board::unpack(&invalid).is_err());
+        // an en passant pawn on its starting square
+        let mut invalid = start;
+        invalid[12] = 0x77;
+        assert!(Board::unpack(&invalid).is_err());
+        // a castling rook on the wrong side's back rank
+        let mut invalid = start;
+        invalid[8] = 0x1E;
+        assert!(Board::unpack(&invalid).is_err());
+    }
+}
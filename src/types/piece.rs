@@ -1,6 +1,6 @@
 use crate::types::Color;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Piece {
     None,
     Pawn(Color),
@@ -11,6 +11,7 @@ pub enum Piece {
     King(Color),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PieceType {
     All,
     None,
@@ -23,6 +24,18 @@ pub enum PieceType {
 }
 
 impl Piece {
+    pub fn new(ptype: PieceType, color: Color) -> Piece {
+        match ptype {
+            PieceType::Pawn => Piece::Pawn(color),
+            PieceType::Knight => Piece::Knight(color),
+            PieceType::Bishop => Piece::Bishop(color),
+            PieceType::Rook => Piece::Rook(color),
+            PieceType::Queen => Piece::Queen(color),
+            PieceType::King => Piece::King(color),
+            PieceType::All | PieceType::None => Piece::None,
+        }
+    }
+
     pub fn from(c: char) -> Piece {
         let color = if c.is_lowercase() {
             Color::Black
@@ -0,0 +1,165 @@
+//! # Serde Support
+//!
+//! `Serialize` and `Deserialize` for the core types, enabled by the `serde` feature. Everything
+//! is written as the same short strings used in the chess formats, so that positions and moves
+//! stay readable (and easy to produce from other tools) in JSON datasets:
+//!
+//! - [`Square`] as its algebraic name, `"e4"`.
+//! - [`Piece`] as its FEN letter, `"N"` or `"n"`, and `" "` for [`Piece::None`].
+//! - [`Bitboard`] in hexadecimal, `"0x000000000000FF00"`.
+//! - [`Move`] in UCI, with castling as the king taking its own rook (`"e1h1"`).
+//! - [`Board`] as FEN.
+//!
+//! A UCI move doesn't say whether it castles or takes en passant, so a deserialized [`Move`] is
+//! always a quiet move or a promotion. To get the exact move, look it up in its position with
+//! [`Board::parse_uci`] (with `chess960` set).
+//!
+//! ### Links
+//! - [Serde](https://serde.rs)
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::types::*;
+
+/// Deserialize a string and convert it with `parse`, reporting its errors as serde errors.
+fn parse_str<'de, D, T>(deserializer: D, parse: impl FnOnce(&str) -> Result<T, String>) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    parse(&s).map_err(D::Error::custom)
+}
+
+impl Serialize for Square {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.algebraic())
+    }
+}
+
+impl<'de> Deserialize<'de> for Square {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Square, D::Error> {
+        parse_str(deserializer, str::parse)
+    }
+}
+
+impl Serialize for Piece {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Piece {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Piece, D::Error> {
+        parse_str(deserializer, |s| {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c == ' ' || Piece::from(c) != Piece::None => Ok(Piece::from(c)),
+                _ => Err(format!("Invalid piece: {:?}", s)),
+            }
+        })
+    }
+}
+
+impl Serialize for Bitboard {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("0x{:016X}", self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Bitboard {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Bitboard, D::Error> {
+        parse_str(deserializer, |s| {
+            let digits = s.strip_prefix("0x").unwrap_or(s);
+            u64::from_str_radix(digits, 16)
+                .map(Bitboard)
+                .map_err(|_| format!("Invalid bitboard: {}", s))
+        })
+    }
+}
+
+impl Serialize for Move {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.uci(true))
+    }
+}
+
+impl<'de> Deserialize<'de> for Move {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Move, D::Error> {
+        parse_str(deserializer, |s| {
+            let invalid = || format!("Invalid UCI move: {}", s);
+            let (from, to, promotion) = match (s.get(0..2), s.get(2..4), s.get(4..)) {
+                (Some(from), Some(to), Some(promotion)) => (from, to, promotion),
+                _ => return Err(invalid()),
+            };
+            let (from, to) = (from.parse().map_err(|_| invalid())?, to.parse().map_err(|_| invalid())?);
+            let move_type = match promotion {
+                "" => MoveType::Quiet,
+                "n" | "b" | "r" | "q" => MoveType::Promotion(Piece::from(promotion.chars().next().unwrap()).ptype()),
+                _ => return Err(invalid()),
+            };
+            Ok(Move::new(from, to, move_type))
+        })
+    }
+}
+
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.fen())
+    }
+}
+
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Board, D::Error> {
+        parse_str(deserializer, Board::from_fen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::*;
+    use crate::movegen::legal_moves;
+
+    fn round_trip<T>(value: T, json: &str)
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug,
+    {
+        assert_eq!(serde_json::to_string(&value).unwrap(), json);
+        assert_eq!(serde_json::from_str::<T>(json).unwrap(), value);
+    }
+
+    #[test]
+    fn test_serde() {
+        round_trip(Square::E4, "\"e4\"");
+        round_trip(Piece::Knight(Color::Black), "\"n\"");
+        round_trip(Piece::None, "\" \"");
+        round_trip(Bitboard::RANK_2, "\"0x000000000000FF00\"");
+        round_trip(Move::new(Square::E7, Square::E8, MoveType::Promotion(PieceType::Queen)), "\"e7e8q\"");
+        round_trip(vec![Square::A1, Square::H8], "[\"a1\",\"h8\"]");
+        for bad in ["\"e9\"", "\"x\"", "\"E4\"", "\"e2e4\"", "4"] {
+            assert!(serde_json::from_str::<Square>(bad).is_err());
+        }
+        for bad in ["\"e2\"", "\"e2e9\"", "\"e2e4k\"", "\"e7e8qq\"", "4"] {
+            assert!(serde_json::from_str::<Move>(bad).is_err());
+        }
+        assert!(serde_json::from_str::<Piece>("\"x\"").is_err());
+        assert!(serde_json::from_str::<Bitboard>("\"0xZZ\"").is_err());
+
+        // castling is written as the king taking its rook, and read back with the position
+        let board = Board::from("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        let castle = Move::new(Square::E1, Square::H1, MoveType::Castle);
+        assert_eq!(serde_json::to_string(&castle).unwrap(), "\"e1h1\"");
+        let read: Move = serde_json::from_str("\"e1h1\"").unwrap();
+        assert_eq!(board.parse_uci(&read.uci(true), true), Ok(castle));
+
+        // every legal move of a position survives a round trip through its UCI string
+        let json = serde_json::to_string(&board).unwrap();
+        assert_eq!(json, "\"r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1\"");
+        let board: Board = serde_json::from_str(&json).unwrap();
+        for m in legal_moves(&board) {
+            let read: Move = serde_json::from_str(&serde_json::to_string(&m).unwrap()).unwrap();
+            assert_eq!(board.parse_uci(&read.uci(true), true), Ok(m));
+        }
+        assert!(serde_json::from_str::<Board>("\"not a fen\"").is_err());
+    }
+}
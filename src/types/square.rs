@@ -1,9 +1,11 @@
-use crate::types::{File, Rank, Direction};
+use std::str::FromStr;
 
-const FILES: &[&'static str] = &["A", "B", "C", "D", "E", "F", "G", "H"];
-const RANKS: &[&'static str] = &["1", "2", "3", "4", "5", "6", "7", "8"];
+use crate::types::{Color, File, Rank, Direction};
 
-#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+const FILES: &[&str] = &["A", "B", "C", "D", "E", "F", "G", "H"];
+const RANKS: &[&str] = &["1", "2", "3", "4", "5", "6", "7", "8"];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Hash)]
 pub enum Square {
     A1 =  0, B1, C1, D1, E1, F1, G1, H1,
     A2 =  8, B2, C2, D2, E2, F2, G2, H2,
@@ -13,7 +15,6 @@ pub enum Square {
     A6 = 40, B6, C6, D6, E6, F6, G6, H6,
     A7 = 48, B7, C7, D7, E7, F7, G7, H7,
     A8 = 56, B8, C8, D8, E8, F8, G8, H8,
-    InvalidSquare = 64,
 }
 
 pub struct SquareIter {
@@ -51,16 +52,46 @@ impl Iterator for SquareIter {
 }
 
 impl Square {
+    /// The square on `file` and `rank`.
+    pub const fn new(file: File, rank: Rank) -> Square {
+        ALL_SQUARES[rank as usize * 8 + file as usize]
+    }
+
+    /// The square with index `index`, from `A1` (0) to `H8` (63).
+    ///
+    /// Panics if `index` is out of the board, use [`Square::try_from`] for unchecked input.
+    pub const fn from_index(index: usize) -> Square {
+        ALL_SQUARES[index]
+    }
+
     pub fn in_range(n: isize) -> bool {
-        n >= 0 && n < 64
+        (0..64).contains(&n)
     }
 
     pub fn file(&self) -> File {
-        File::try_from((*self as usize) & 7).expect("Square should be valid (0 <= square <= 63)")
+        File::ALL[(*self as usize) & 7]
     }
 
     pub fn rank(&self) -> Rank {
-        Rank::try_from((*self as usize) >> 3).expect("Square should be valid (0 <= square <= 63)")
+        Rank::ALL[(*self as usize) >> 3]
+    }
+
+    /// The rank of the square as seen by `color`, so that its own back rank is always `Rank1`.
+    pub fn relative_rank(&self, color: Color) -> Rank {
+        self.rank().relative(color)
+    }
+
+    /// The square mirrored vertically (e.g. `E2` becomes `E7`).
+    pub fn flip(&self) -> Square {
+        ALL_SQUARES[*self as usize ^ 56]
+    }
+
+    /// The square as seen by `color`: itself for white, flipped for black.
+    pub fn relative(&self, color: Color) -> Square {
+        match color {
+            Color::White => *self,
+            Color::Black => self.flip(),
+        }
     }
 
     // Manhattan distance (king moves)
@@ -71,20 +102,32 @@ impl Square {
     }
 
     pub fn safe_step(&self, step: isize) -> bool {
-        if Square::in_range(*self as isize + step) {
-            let to = *self + step;
-            self.dist(to) <= 2
-        } else {
-            false
-        }
+        self.step(step).is_some()
     }
 
+    /// The square `step` indices away, as long as it is a king or knight move away, i.e. the
+    /// step doesn't leave the board or wrap around to the other side of it.
     pub fn step(&self, step: isize) -> Option<Square> {
-        if self.safe_step(step) {
-            Some(*self + step)
-        } else {
-            None
-        }
+        let to = Square::try_from(*self as isize + step).ok()?;
+        (self.dist(to) <= 2).then_some(to)
+    }
+
+    /// The square one step away in direction `dir`, if it is on the board.
+    pub fn shift(&self, dir: Direction) -> Option<Square> {
+        let to = Square::try_from(*self as isize + dir as isize).ok()?;
+        (self.dist(to) == 1).then_some(to)
+    }
+
+    /// The square `files` files and `ranks` ranks away, if it is on the board.
+    pub fn offset(&self, files: isize, ranks: isize) -> Option<Square> {
+        let file = File::try_from(self.file() as isize + files).ok()?;
+        let rank = Rank::try_from(self.rank() as isize + ranks).ok()?;
+        Some(Square::new(file, rank))
+    }
+
+    /// The lowercase algebraic name of the square, as used in FEN, SAN and UCI (e.g. `e4`).
+    pub fn algebraic(&self) -> String {
+        self.to_string().to_lowercase()
     }
 
     pub fn all() -> SquareIter {
@@ -104,6 +147,23 @@ impl Square {
     }
 }
 
+/// Parse a lowercase algebraic square name such as `e4`, as written by [`Square::algebraic`].
+impl FromStr for Square {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Square, String> {
+        let mut chars = name.chars();
+        match (chars.next(), chars.next(), chars.next()) {
+            (Some(file), Some(rank), None) => {
+                let file = File::try_from(file).map_err(|_| format!("Invalid square: {}", name))?;
+                let rank = Rank::try_from(rank).map_err(|_| format!("Invalid square: {}", name))?;
+                Ok(Square::new(file, rank))
+            }
+            _ => Err(format!("Invalid square: {}", name)),
+        }
+    }
+}
+
 impl From<Square> for usize {
     fn from(sq: Square) -> usize {
         sq as usize
@@ -116,23 +176,19 @@ impl From<Square> for isize {
     }
 }
 
-impl From<usize> for Square {
-    fn from(n: usize) -> Self {
-        if n < 64 {
-            ALL_SQUARES[n]
-        } else {
-            Square::InvalidSquare
-        }
+impl TryFrom<u8> for Square {
+    type Error = String;
+
+    fn try_from(n: u8) -> Result<Square, String> {
+        ALL_SQUARES.get(n as usize).copied().ok_or(format!("Invalid square: {}", n))
     }
 }
 
-impl From<isize> for Square {
-    fn from(n: isize) -> Self {
-        if n >= 0 && n < 64 {
-            ALL_SQUARES[n as usize]
-        } else {
-            Square::InvalidSquare
-        }
+impl TryFrom<isize> for Square {
+    type Error = String;
+
+    fn try_from(n: isize) -> Result<Square, String> {
+        u8::try_from(n).map_err(|_| format!("Invalid square: {}", n)).and_then(Square::try_from)
     }
 }
 
@@ -141,63 +197,3 @@ impl std::fmt::Display for Square {
         write!(f, "{}{}", FILES[self.file() as usize], RANKS[self.rank() as usize])
     }
 }
-
-impl std::ops::Add<isize> for Square {
-    type Output = Square;
-
-    fn add(self, rhs: isize) -> Self::Output {
-        let n: isize = self.into();
-        Square::from(n + rhs)
-    }
-}
-
-impl std::ops::Add<Direction> for Square {
-    type Output = Square;
-
-    fn add(self, rhs: Direction) -> Self::Output {
-        let n: isize = self.into();
-        Square::from(n + rhs as isize)
-    }
-}
-
-impl std::ops::Sub<isize> for Square {
-    type Output = Square;
-
-    fn sub(self, rhs: isize) -> Self::Output {
-        let n: isize = self.into();
-        Square::from(n - rhs)
-    }
-}
-
-impl std::ops::Sub<Direction> for Square {
-    type Output = Square;
-
-    fn sub(self, rhs: Direction) -> Self::Output {
-        let n: isize = self.into();
-        Square::from(n - rhs as isize)
-    }
-}
-
-impl std::ops::AddAssign<isize> for Square {
-    fn add_assign(&mut self, rhs: isize) {
-        *self = Square::from(*self as isize + rhs);
-    }
-}
-
-impl std::ops::AddAssign<Direction> for Square {
-    fn add_assign(&mut self, rhs: Direction) {
-        *self = Square::from(*self as isize + rhs as isize);
-    }
-}
-
-impl std::ops::SubAssign<isize> for Square {
-    fn sub_assign(&mut self, rhs: isize) {
-        *self = Square::from(*self as isize - rhs);
-    }
-}
-
-impl std::ops::SubAssign<Direction> for Square {
-    fn sub_assign(&mut self, rhs: Direction) {
-        *self = Square::from(*self as isize - rhs as isize);
-    }
-}
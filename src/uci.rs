@@ -0,0 +1,351 @@
+//! # UCI
+//!
+//! The Universal Chess Interface, the text protocol GUIs and match runners use to talk to the
+//! engine over stdin and stdout. Supported commands:
+//!
+//! - `uci`, `isready`, `ucinewgame` and `quit`.
+//! - `setoption name <name> value <value>`: `UCI_Chess960`, and every search parameter of the
+//!   [`tunables`](crate::tunables) registry as a `spin` option.
+//! - `position (startpos | fen <fen>) [moves <move>...]`.
+//! - `go [depth N] [nodes N] [movetime MS] [wtime MS] [btime MS] [winc MS] [binc MS]
+//!   [movestogo N] [infinite]`. The search runs on its own thread, and replies with `bestmove`
+//!   once a limit is reached or on `stop`. `isready` is still answered while it runs; any other
+//!   command waits for it to finish, except `quit`, which stops it.
+//!
+//! The non-standard `tunables` command prints the search parameters in OpenBench's SPSA format.
+//!
+//! ### Links
+//! - [UCI](https://backscattering.de/chess/uci/)
+
+use std::io::{BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::BOARD_START_FEN;
+use crate::types::*;
+use crate::search::{is_mate_score, Limits, Search, SearchResult, MATE};
+use crate::tunables::{openbench_list, uci_options, SearchParams};
+
+/// Time kept in reserve on every move, for the communication with the GUI.
+const MOVE_OVERHEAD: Duration = Duration::from_millis(20);
+
+/// A search running in the background, and the flag that stops it.
+struct Running {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+    infinite: bool,
+}
+
+/// The state of a UCI session: the position to search, the options set, and the search running.
+pub struct Uci {
+    board: Board,
+    params: SearchParams,
+    chess960: bool,
+    /// Where replies go. Shared with the search thread, which sends `bestmove` when it's done.
+    out: Arc<Mutex<dyn Write + Send>>,
+    search: Option<Running>,
+}
+
+impl Default for Uci {
+    fn default() -> Uci {
+        Uci::new()
+    }
+}
+
+impl Uci {
+    /// A session replying on stdout.
+    pub fn new() -> Uci {
+        Uci::with_output(std::io::stdout())
+    }
+
+    /// A session writing its replies to `out`.
+    pub fn with_output(out: impl Write + Send + 'static) -> Uci {
+        Uci {
+            board: Board::from(BOARD_START_FEN),
+            params: SearchParams::default(),
+            chess960: false,
+            out: Arc::new(Mutex::new(out)),
+            search: None,
+        }
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn params(&self) -> &SearchParams {
+        &self.params
+    }
+
+    /// Handle a command. Returns `false` on `quit`. Invalid commands are reported as
+    /// `info string` lines and otherwise ignored, as GUIs expect.
+    pub fn command(&mut self, line: &str) -> Result<bool, String> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first().copied() {
+            Some("isready") => return send(&self.out, "readyok"),
+            Some("stop") => {
+                self.stop();
+                return Ok(true);
+            }
+            Some("quit") => {
+                self.stop();
+                return Ok(false);
+            }
+            _ => self.wait(),
+        }
+        let result = match tokens.first().copied() {
+            Some("uci") => {
+                let mut lines = vec![
+                    format!("id name Tejuino {}", env!("CARGO_PKG_VERSION")),
+                    "id author the Tejuino developers".to_string(),
+                    "option name UCI_Chess960 type check default false".to_string(),
+                ];
+                lines.extend(uci_options());
+                lines.push("uciok".to_string());
+                Ok(lines.join("\n"))
+            }
+            Some("setoption") => self.set_option(&tokens[1..]).map(|_| String::new()),
+            Some("ucinewgame") => Ok(String::new()),
+            Some("position") => self.set_position(&tokens[1..]).map(|_| String::new()),
+            Some("go") => self.go(&tokens[1..]).map(|_| String::new()),
+            Some("tunables") => Ok(openbench_list().trim_end().to_string()),
+            Some(other) => Err(format!("Unknown command: {}", other)),
+            None => Ok(String::new()),
+        };
+        send(&self.out, &result.unwrap_or_else(|e| format!("info string {}", e)))
+    }
+
+    /// Stop the running search, if any, and wait for it to send `bestmove`.
+    pub fn stop(&mut self) {
+        if let Some(running) = &self.search {
+            running.stop.store(true, Ordering::Relaxed);
+        }
+        self.wait();
+    }
+
+    /// Wait for the running search, if any, to reach its limits and send `bestmove`.
+    pub fn wait(&mut self) {
+        if let Some(running) = self.search.take() {
+            running.handle.join().expect("The search thread shouldn't panic");
+        }
+    }
+
+    /// `name <name...> value <value>`
+    fn set_option(&mut self, tokens: &[&str]) -> Result<(), String> {
+        let value_at = tokens.iter().position(|t| *t == "value").unwrap_or(tokens.len());
+        if tokens.first() != Some(&"name") || value_at < 2 {
+            return Err(format!("Invalid option: {}", tokens.join(" ")));
+        }
+        let name = tokens[1..value_at].join(" ");
+        let value = tokens.get(value_at + 1..).unwrap_or_default().join(" ");
+        if name.eq_ignore_ascii_case("UCI_Chess960") {
+            self.chess960 = value.parse().map_err(|_| format!("Invalid value for {}: {}", name, value))?;
+            return Ok(());
+        }
+        let value = value.parse().map_err(|_| format!("Invalid value for {}: {}", name, value))?;
+        self.params.set(&name, value)
+    }
+
+    /// `(startpos | fen <fen>) [moves <move>...]`
+    fn set_position(&mut self, tokens: &[&str]) -> Result<(), String> {
+        let moves_at = tokens.iter().position(|t| *t == "moves").unwrap_or(tokens.len());
+        let mut board = match tokens.first() {
+            Some(&"startpos") => Board::from(BOARD_START_FEN),
+            Some(&"fen") => Board::from_fen(&tokens[1..moves_at].join(" "))?,
+            _ => return Err(format!("Invalid position: {}", tokens.join(" "))),
+        };
+        for uci in tokens.get(moves_at + 1..).unwrap_or_default() {
+            let m = board.parse_uci(uci, self.chess960)?;
+            board.make_move(m);
+        }
+        self.board = board;
+        Ok(())
+    }
+
+    /// Start searching the current position on another thread, which replies with `info` and
+    /// `bestmove` when done.
+    fn go(&mut self, tokens: &[&str]) -> Result<(), String> {
+        let limits = self.limits(tokens)?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut search = Search::new(limits).with_params(self.params.clone()).with_stop(stop.clone());
+        let (board, chess960, out) = (self.board.clone(), self.chess960, self.out.clone());
+        let handle = std::thread::spawn(move || {
+            let start = Instant::now();
+            let result = search.run(&board);
+            let best = result.best_move.map_or("0000".to_string(), |m| m.uci(chess960));
+            let reply = format!("{}\nbestmove {}", info(&result, start.elapsed(), chess960), best);
+            // nowhere left to report a failed write to
+            let _ = send(&out, &reply);
+        });
+        let infinite = limits.depth.is_none() && limits.time.is_none() && limits.nodes.is_none();
+        self.search = Some(Running { stop, handle, infinite });
+        Ok(())
+    }
+
+    fn limits(&self, tokens: &[&str]) -> Result<Limits, String> {
+        let value = |name: &str| -> Result<Option<u64>, String> {
+            tokens
+                .iter()
+                .position(|t| *t == name)
+                .map(|i| {
+                    let v = tokens.get(i + 1).ok_or_else(|| format!("Missing value for {}", name))?;
+                    v.parse().map_err(|_| format!("Invalid value for {}: {}", name, v))
+                })
+                .transpose()
+        };
+        let (time, increment) = match self.board.side_to_move {
+            Color::White => (value("wtime")?, value("winc")?),
+            Color::Black => (value("btime")?, value("binc")?),
+        };
+        // a fraction of the time left, plus most of the increment
+        let moves_to_go = value("movestogo")?.unwrap_or(30).max(1);
+        let allocated = time.map(|time| {
+            let share = time / moves_to_go + increment.unwrap_or(0) * 3 / 4;
+            Duration::from_millis(share.min(time * 3 / 4)).saturating_sub(MOVE_OVERHEAD)
+        });
+        Ok(Limits {
+            depth: value("depth")?.map(|d| d as u32),
+            time: value("movetime")?.map(|t| Duration::from_millis(t).saturating_sub(MOVE_OVERHEAD)).or(allocated),
+            nodes: value("nodes")?,
+        })
+    }
+
+}
+
+fn info(result: &SearchResult, elapsed: Duration, chess960: bool) -> String {
+    let score = if is_mate_score(result.score) {
+        let moves = (MATE - result.score.abs() + 1) / 2;
+        format!("mate {}", if result.score > 0 { moves } else { -moves })
+    } else {
+        format!("cp {}", result.score)
+    };
+    let pv: Vec<String> = result.pv.iter().map(|m| m.uci(chess960)).collect();
+    let nps = (result.nodes as f64 / elapsed.as_secs_f64().max(1e-9)) as u64;
+    let mut info = format!(
+        "info depth {} score {} nodes {} time {} nps {}",
+        result.depth, score, result.nodes, elapsed.as_millis(), nps
+    );
+    if !pv.is_empty() {
+        info += &format!(" pv {}", pv.join(" "));
+    }
+    info
+}
+
+/// Write `reply` and flush it, unless it's empty.
+fn send(out: &Mutex<dyn Write + Send>, reply: &str) -> Result<bool, String> {
+    if !reply.is_empty() {
+        let mut out = out.lock().expect("No thread should panic while writing");
+        writeln!(out, "{}", reply).map_err(|e| e.to_string())?;
+        out.flush().map_err(|e| e.to_string())?;
+    }
+    Ok(true)
+}
+
+/// Run a UCI session over stdin and stdout until `quit` or the end of the input.
+pub fn run() -> Result<(), String> {
+    crate::init();
+    let mut uci = Uci::new();
+    for line in std::io::stdin().lock().lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        if !uci.command(&line)? {
+            return Ok(());
+        }
+    }
+    // at the end of the input, finish the last search unless nothing would ever stop it
+    if uci.search.as_ref().is_some_and(|s| s.infinite) {
+        uci.stop();
+    }
+    uci.wait();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::uci::*;
+
+    /// Output shared with the test, to read the replies back.
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Output {
+        fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+        }
+    }
+
+    /// Send `command` and wait for the search it starts, if any, to finish.
+    fn reply(uci: &mut Uci, out: &Output, command: &str) -> String {
+        assert!(uci.command(command).unwrap());
+        uci.wait();
+        out.take()
+    }
+
+    #[test]
+    fn test_uci() {
+        let out = Output::default();
+        let mut uci = Uci::with_output(out.clone());
+        let handshake = reply(&mut uci, &out, "uci");
+        assert!(handshake.contains("option name lmr_base type spin default 75 min 0 max 200\n"));
+        assert!(handshake.ends_with("uciok\n"));
+        assert_eq!(reply(&mut uci, &out, "isready"), "readyok\n");
+
+        assert_eq!(reply(&mut uci, &out, "setoption name nmp_base value 4"), "");
+        assert_eq!(uci.params().nmp_base, 4);
+        assert!(reply(&mut uci, &out, "setoption name nmp_base value 40").starts_with("info string"));
+        assert!(reply(&mut uci, &out, "setoption name Hash value 16").starts_with("info string"));
+
+        assert_eq!(reply(&mut uci, &out, "position startpos moves e2e4 e7e5 g1f3"), "");
+        assert_eq!(uci.board().fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+        assert!(reply(&mut uci, &out, "position startpos moves e2e5").starts_with("info string"));
+
+        // back rank mate in one
+        reply(&mut uci, &out, "position fen 6k1/5ppp/8/8/8/8/8/4R1K1 w - - 0 1");
+        let go = reply(&mut uci, &out, "go depth 3");
+        assert!(go.contains("score mate 1"), "{}", go);
+        assert!(go.ends_with("bestmove e1e8\n"), "{}", go);
+        assert!(reply(&mut uci, &out, "go movetime 50").ends_with("bestmove e1e8\n"));
+        assert!(reply(&mut uci, &out, "go wtime 1000 btime 1000 winc 10 binc 10").ends_with("bestmove e1e8\n"));
+
+        // castling moves depend on UCI_Chess960
+        reply(&mut uci, &out, "position fen 4k3/8/8/8/8/8/8/4K2R w K - 0 1 moves e1g1");
+        assert_eq!(uci.board().at(Square::G1), Piece::King(Color::White));
+        reply(&mut uci, &out, "setoption name UCI_Chess960 value true");
+        reply(&mut uci, &out, "position fen 4k3/8/8/8/8/8/8/4K2R w K - 0 1 moves e1h1");
+        assert_eq!(uci.board().at(Square::G1), Piece::King(Color::White));
+
+        assert!(reply(&mut uci, &out, "tunables").starts_with("aspiration_delta, int, 25"));
+        assert!(!uci.command("quit").unwrap());
+    }
+
+    #[test]
+    fn test_stop() {
+        let out = Output::default();
+        let mut uci = Uci::with_output(out.clone());
+        uci.command("go infinite").unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        uci.command("isready").unwrap();
+        assert_eq!(out.take(), "readyok\n");
+        uci.command("stop").unwrap();
+        let reply = out.take();
+        assert!(reply.starts_with("info depth"), "{}", reply);
+        assert!(reply.lines().last().unwrap().starts_with("bestmove "), "{}", reply);
+        assert_ne!(reply.lines().last().unwrap(), "bestmove 0000");
+
+        // quit stops the search too, and it still replies
+        uci.command("go infinite").unwrap();
+        assert!(!uci.command("quit").unwrap());
+        assert!(out.take().lines().last().unwrap().starts_with("bestmove "));
+    }
+}
@@ -18,6 +18,7 @@ impl PRNG {
 
     // xorshift64* algorithm
     #[inline]
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> u64 {
         let mut x = self.state;
         x ^= x.wrapping_shr(12);
@@ -38,7 +39,7 @@ pub fn render_grid(content: &[char; 64], legend: bool) -> String {
     let mut out = String::new();
     for i in 0..8 {
         for j in 0..8 {
-            let line: String = std::iter::repeat(HORIZONTAL_LINE).take(3).collect();
+            let line: String = std::iter::repeat_n(HORIZONTAL_LINE, 3).collect();
             let lead = GRID_CHARS[if i == 0 { 0 } else { 1 }][if j == 0 { 0 } else { 1 }];
             out += format!("{}{}", lead, line).as_str();
         }
@@ -57,7 +58,7 @@ pub fn render_grid(content: &[char; 64], legend: bool) -> String {
         out += formatted.as_str();
     }
     for j in 0..8 {
-        let line: String = std::iter::repeat(HORIZONTAL_LINE).take(3).collect();
+        let line: String = std::iter::repeat_n(HORIZONTAL_LINE, 3).collect();
         let lead = GRID_CHARS[2][if j == 0 { 0 } else { 1 }];
         out += format!("{}{}", lead, line).as_str();
     }
@@ -39,7 +39,7 @@ mod tests {
     fn test_attack() {
         let board = Board::from("rnbqkbnr/p1pppppp/8/2B5/4P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 1");
         let square = Square::C5;
-        let attack_bb = attack(
+        let attack_bb = get_moves(
             board.at(square),
             square,
             board.pieces[PieceType::All as usize]
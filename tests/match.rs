@@ -0,0 +1,102 @@
+#[cfg(test)]
+mod tests {
+    use tejuino::datagen::GameResult;
+    use tejuino::tournament::*;
+    use tejuino::tournament::engine::Engine;
+
+    fn engine(name: &str) -> EngineConfig {
+        EngineConfig {
+            name: name.to_string(),
+            command: env!("CARGO_BIN_EXE_tejuino").to_string(),
+            time_control: TimeControl { nodes: Some(300), ..Default::default() },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_engine_process() {
+        let mut engine = Engine::start(env!("CARGO_BIN_EXE_tejuino"), &[], None, &[]).unwrap();
+        assert!(engine.name().unwrap().starts_with("Tejuino"));
+        engine.new_game().unwrap();
+        let reply = engine.go("position fen 6k1/5ppp/8/8/8/8/8/4R1K1 w - - 0 1", "go depth 3", None).unwrap();
+        assert_eq!(reply.uci, "e1e8");
+        assert_eq!(reply.score, Some(engine::Score::Mate(1)));
+    }
+
+    #[test]
+    fn test_match() {
+        let options = MatchOptions {
+            engines: vec![engine("a"), engine("b")],
+            concurrency: 2,
+            adjudication: Adjudication { max_moves: Some(12), ..Default::default() },
+            ..Default::default()
+        };
+        let mut finished = 0;
+        let outcomes = run_match(&options, |_| {
+            finished += 1;
+            Ok(true)
+        })
+        .unwrap();
+        assert_eq!((finished, outcomes.len()), (2, 2));
+        for (i, outcome) in outcomes.iter().enumerate() {
+            assert_eq!(outcome.pairing.number, i + 1);
+            let game = &outcome.game;
+            assert_eq!(game.tag("White"), Some(["a", "b"][i]));
+            assert_eq!(game.tag("Result"), Some(game.result.as_str()));
+            assert!(game.moves.len() <= 24);
+            game.replay().unwrap();
+        }
+
+        // a queen up: the side without it resigns straight away, whoever plays it
+        let options = MatchOptions {
+            openings: read_openings("4k3/8/8/8/8/8/8/3QK3 b - -".as_bytes(), OpeningFormat::Epd, None).unwrap(),
+            adjudication: Adjudication {
+                resign: Some(ResignAdjudication { move_count: 1, score: 500 }),
+                ..Default::default()
+            },
+            ..options
+        };
+        let outcomes = run_match(&options, |_| Ok(true)).unwrap();
+        for outcome in &outcomes {
+            assert_eq!((outcome.result, outcome.reason.as_str()), (GameResult::WhiteWin, "White wins by adjudication"));
+            assert_eq!(outcome.game.tag("Termination"), Some("adjudication"));
+        }
+        let table = standings(2, &outcomes);
+        assert_eq!((table[0].wins, table[0].losses, table[1].points()), (1, 1, 1.0));
+        assert_eq!(pentanomial(&outcomes, 0, 1).0, [0, 0, 1, 0, 0]);
+
+        // stopping after the first game, as an SPRT would
+        let options = MatchOptions { concurrency: 1, rounds: 3, ..options };
+        assert_eq!(run_match(&options, |_| Ok(false)).unwrap().len(), 1);
+
+        let missing = EngineConfig { command: "/nonexistent/engine".to_string(), ..engine("c") };
+        let options = MatchOptions { engines: vec![engine("a"), missing], ..Default::default() };
+        assert!(run_match(&options, |_| Ok(true)).is_err());
+        let unlimited = EngineConfig { time_control: TimeControl::default(), ..engine("d") };
+        let options = MatchOptions { engines: vec![engine("a"), unlimited], ..Default::default() };
+        assert!(run_match(&options, |_| Ok(true)).is_err());
+    }
+
+    #[test]
+    fn test_hanging_engine() {
+        // answers the handshake, but never a go
+        let script = "while read line; do case $line in uci) echo uciok;; isready) echo readyok;; quit) exit;; esac; done";
+        let hanging = EngineConfig {
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            ..engine("hangs")
+        };
+        let options = MatchOptions {
+            engines: vec![engine("a"), hanging],
+            move_timeout: std::time::Duration::from_millis(200),
+            ..Default::default()
+        };
+        let outcomes = run_match(&options, |_| Ok(true)).unwrap();
+        assert_eq!(outcomes.len(), 2);
+        for outcome in &outcomes {
+            let hanging = if outcome.pairing.white == 1 { "White" } else { "Black" };
+            assert_eq!(outcome.reason, format!("{} loses on time", hanging));
+            assert_eq!(outcome.game.tag("Termination"), Some("time forfeit"));
+        }
+    }
+}